@@ -0,0 +1,427 @@
+// src/acpi.rs
+// ====================
+// ACPI - Descoberta do RSDP, RSDT/XSDT, MADT, FADT e HPET
+// ====================
+
+use core::mem;
+use core::ptr;
+use alloc::vec::Vec;
+use spin::Mutex;
+use x86_64::PhysAddr;
+use crate::memory;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    RsdpNotFound,
+    BadChecksum([u8; 4]),
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct Rsdp {
+    pub signature: [u8; 8],
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub revision: u8,
+    pub rsdt_address: u32,
+    // ACPI 2.0+
+    pub length: u32,
+    pub xsdt_address: u64,
+    pub extended_checksum: u8,
+    pub reserved: [u8; 3],
+}
+
+const RSDP_V1_SIZE: usize = 20;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+const SDT_HEADER_SIZE: usize = mem::size_of::<SdtHeader>();
+
+struct AcpiTables {
+    rsdp: Rsdp,
+    tables: Vec<([u8; 4], PhysAddr)>,
+}
+
+static TABLES: Mutex<Option<AcpiTables>> = Mutex::new(None);
+
+// --- Acesso à memória física ---
+
+unsafe fn read_phys<T: Copy>(addr: PhysAddr) -> T {
+    ptr::read_unaligned(memory::phys_to_virt(addr).as_ptr::<T>())
+}
+
+unsafe fn phys_bytes(addr: PhysAddr, len: usize) -> &'static [u8] {
+    core::slice::from_raw_parts(memory::phys_to_virt(addr).as_ptr::<u8>(), len)
+}
+
+/// Bytes crus de uma tabela (cabeçalho incluso), via mapeamento físico.
+pub fn table_bytes(addr: PhysAddr) -> &'static [u8] {
+    let header = read_header(addr);
+    unsafe { phys_bytes(addr, header.length as usize) }
+}
+
+fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == 0
+}
+
+// Campos little-endian; fora da tabela lê 0 (FADT de ACPI 1.0 é mais curto)
+fn le_u16(bytes: &[u8], i: usize) -> u16 {
+    bytes.get(i..i + 2).map_or(0, |b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn le_u32(bytes: &[u8], i: usize) -> u32 {
+    bytes.get(i..i + 4).map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn le_u64(bytes: &[u8], i: usize) -> u64 {
+    bytes.get(i..i + 8).map_or(0, |b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+// --- RSDP ---
+
+// Procura "RSD PTR " alinhado a 16 bytes em [start, start + len)
+fn scan_rsdp(start: u64, len: u64) -> Option<PhysAddr> {
+    (start..start + len).step_by(16).map(PhysAddr::new).find(|&addr| {
+        let bytes = unsafe { phys_bytes(addr, RSDP_V1_SIZE) };
+        &bytes[..8] == b"RSD PTR " && checksum_ok(bytes)
+    })
+}
+
+fn find_rsdp() -> Option<PhysAddr> {
+    // Primeiro KiB da EBDA (segmento em 0x40E), depois a área da BIOS
+    let ebda = (unsafe { read_phys::<u16>(PhysAddr::new(0x40E)) } as u64) << 4;
+    if (0x80000..0xA0000).contains(&ebda) {
+        if let Some(addr) = scan_rsdp(ebda, 1024) {
+            return Some(addr);
+        }
+    }
+    scan_rsdp(0xE0000, 0x20000)
+}
+
+// --- RSDT / XSDT ---
+
+pub fn read_header(addr: PhysAddr) -> SdtHeader {
+    unsafe { read_phys(addr) }
+}
+
+fn table_valid(addr: PhysAddr) -> bool {
+    checksum_ok(table_bytes(addr))
+}
+
+/// Localiza o RSDP e indexa as tabelas listadas no RSDT/XSDT.
+pub fn init() -> Result<(), AcpiError> {
+    let rsdp_addr = find_rsdp().ok_or(AcpiError::RsdpNotFound)?;
+    let rsdp: Rsdp = unsafe { read_phys(rsdp_addr) };
+
+    // ACPI 2.0+: XSDT com ponteiros de 64 bits, se o checksum estendido bater
+    let extended_ok = rsdp.revision >= 2
+        && checksum_ok(unsafe { phys_bytes(rsdp_addr, rsdp.length as usize) });
+    if rsdp.revision >= 2 && !extended_ok {
+        crate::klog!("ACPI: checksum estendido do RSDP inválido, usando o RSDT");
+    }
+    let (root, entry_size) = if extended_ok && rsdp.xsdt_address != 0 {
+        (PhysAddr::new(rsdp.xsdt_address), 8)
+    } else {
+        (PhysAddr::new(rsdp.rsdt_address as u64), 4)
+    };
+    let root_header = read_header(root);
+    if !table_valid(root) {
+        return Err(AcpiError::BadChecksum(root_header.signature));
+    }
+
+    let count = (root_header.length as usize - SDT_HEADER_SIZE) / entry_size;
+    let mut tables = Vec::with_capacity(count);
+    for i in 0..count {
+        let entry = root + (SDT_HEADER_SIZE + i * entry_size) as u64;
+        let addr = unsafe {
+            if entry_size == 8 { read_phys::<u64>(entry) } else { read_phys::<u32>(entry) as u64 }
+        };
+        let addr = PhysAddr::new(addr);
+        let header = read_header(addr);
+        if table_valid(addr) {
+            tables.push((header.signature, addr));
+        } else {
+            crate::klog!("ACPI: tabela {:?} com checksum inválido, ignorada",
+                core::str::from_utf8(&header.signature).unwrap_or("????"));
+        }
+    }
+
+    // O DSDT não aparece no RSDT/XSDT: só o FADT aponta para ele
+    if let Some(&(_, fadt_addr)) = tables.iter().find(|(sig, _)| sig == b"FACP") {
+        let dsdt = parse_fadt(table_bytes(fadt_addr)).dsdt;
+        if dsdt.as_u64() != 0 && table_valid(dsdt) {
+            tables.push((*b"DSDT", dsdt));
+        }
+    }
+
+    *TABLES.lock() = Some(AcpiTables { rsdp, tables });
+    Ok(())
+}
+
+pub fn find_table(signature: &[u8; 4]) -> Option<PhysAddr> {
+    let tables = TABLES.lock();
+    tables.as_ref()?.tables.iter().find(|(sig, _)| sig == signature).map(|&(_, addr)| addr)
+}
+
+/// Todas as tabelas válidas, na ordem do RSDT/XSDT (DSDT por último).
+pub fn tables() -> Vec<([u8; 4], PhysAddr)> {
+    TABLES.lock().as_ref().map_or(Vec::new(), |t| t.tables.clone())
+}
+
+pub fn rsdp() -> Option<Rsdp> {
+    TABLES.lock().as_ref().map(|t| t.rsdp)
+}
+
+// --- MADT ("APIC") ---
+
+#[derive(Debug, Clone, Copy)]
+pub struct IoApicInfo {
+    pub id: u8,
+    pub address: u32,
+    pub gsi_base: u32,
+}
+
+/// Redirecionamento de IRQ ISA -> GSI, com polaridade/gatilho do MADT.
+#[derive(Debug, Clone, Copy)]
+pub struct InterruptOverride {
+    pub source: u8,
+    pub gsi: u32,
+    pub flags: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct Madt {
+    pub local_apic_address: u64,
+    pub has_legacy_pics: bool,
+    pub local_apic_ids: Vec<u8>,
+    pub io_apics: Vec<IoApicInfo>,
+    pub overrides: Vec<InterruptOverride>,
+}
+
+const MADT_PCAT_COMPAT: u32 = 1;
+const MADT_LOCAL_APIC: u8 = 0;
+const MADT_IO_APIC: u8 = 1;
+const MADT_INTERRUPT_OVERRIDE: u8 = 2;
+const MADT_LOCAL_APIC_OVERRIDE: u8 = 5;
+
+pub fn madt() -> Option<Madt> {
+    let bytes = table_bytes(find_table(b"APIC")?);
+    let u16_at = |i: usize| le_u16(bytes, i);
+    let u32_at = |i: usize| le_u32(bytes, i);
+
+    let mut madt = Madt {
+        local_apic_address: u32_at(SDT_HEADER_SIZE) as u64,
+        has_legacy_pics: u32_at(SDT_HEADER_SIZE + 4) & MADT_PCAT_COMPAT != 0,
+        local_apic_ids: Vec::new(),
+        io_apics: Vec::new(),
+        overrides: Vec::new(),
+    };
+
+    // Entradas de tamanho variável: [tipo, tamanho, ...]
+    let mut i = SDT_HEADER_SIZE + 8;
+    while i + 2 <= bytes.len() {
+        let (kind, len) = (bytes[i], bytes[i + 1] as usize);
+        if len < 2 || i + len > bytes.len() {
+            break;
+        }
+        match kind {
+            // Só CPUs habilitadas (flags bit 0)
+            MADT_LOCAL_APIC if u32_at(i + 4) & 1 != 0 => madt.local_apic_ids.push(bytes[i + 3]),
+            MADT_IO_APIC => madt.io_apics.push(IoApicInfo {
+                id: bytes[i + 2],
+                address: u32_at(i + 4),
+                gsi_base: u32_at(i + 8),
+            }),
+            MADT_INTERRUPT_OVERRIDE => madt.overrides.push(InterruptOverride {
+                source: bytes[i + 3],
+                gsi: u32_at(i + 4),
+                flags: u16_at(i + 8),
+            }),
+            MADT_LOCAL_APIC_OVERRIDE => {
+                madt.local_apic_address = le_u64(bytes, i + 4);
+            }
+            _ => {}
+        }
+        i += len;
+    }
+    Some(madt)
+}
+
+// --- Generic Address Structure ---
+
+pub const GAS_SYSTEM_MEMORY: u8 = 0;
+pub const GAS_SYSTEM_IO: u8 = 1;
+
+/// Registrador descrito pelo firmware (memória, porta de I/O, PCI...).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GenericAddress {
+    pub address_space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+fn gas_at(bytes: &[u8], i: usize) -> GenericAddress {
+    GenericAddress {
+        address_space: bytes.get(i).copied().unwrap_or(0),
+        bit_width: bytes.get(i + 1).copied().unwrap_or(0),
+        bit_offset: bytes.get(i + 2).copied().unwrap_or(0),
+        access_size: bytes.get(i + 3).copied().unwrap_or(0),
+        address: le_u64(bytes, i + 4),
+    }
+}
+
+// --- FADT ("FACP") ---
+
+const FADT_TMR_VAL_EXT: u32 = 1 << 8;
+const FADT_RESET_REG_SUP: u32 = 1 << 10;
+const BOOT_ARCH_8042: u16 = 1 << 1;
+
+/// Registradores de gerenciamento de energia do FADT. Portas já resolvidas
+/// entre os campos de 32 bits e os X_ (GAS) do ACPI 2.0+.
+#[derive(Debug, Clone, Copy)]
+pub struct Fadt {
+    pub revision: u8,
+    pub dsdt: PhysAddr,
+    pub sci_interrupt: u16,
+    pub smi_command: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub pm1a_event: u32,
+    pub pm1b_event: u32,
+    pub pm1a_control: u32,
+    pub pm1b_control: u32,
+    pub pm_timer: u32,
+    pub pm_timer_32bit: bool,
+    pub century_register: u8,
+    pub has_8042: bool,
+    pub flags: u32,
+    /// Registrador de reset e o valor a escrever, se suportado (flags bit 10).
+    pub reset: Option<(GenericAddress, u8)>,
+}
+
+// Porta de I/O do bloco: o campo legado, ou o X_ se o legado vier zerado
+fn io_block(bytes: &[u8], legacy: usize, extended: usize) -> u32 {
+    match le_u32(bytes, legacy) {
+        0 => {
+            let gas = gas_at(bytes, extended);
+            if gas.address_space == GAS_SYSTEM_IO { gas.address as u32 } else { 0 }
+        }
+        port => port,
+    }
+}
+
+fn parse_fadt(bytes: &[u8]) -> Fadt {
+    let flags = le_u32(bytes, 112);
+    // X_DSDT (ACPI 2.0+) tem prioridade sobre o ponteiro de 32 bits
+    let dsdt = match le_u64(bytes, 140) {
+        0 => le_u32(bytes, 40) as u64,
+        x_dsdt => x_dsdt,
+    };
+    Fadt {
+        revision: bytes[8],
+        dsdt: PhysAddr::new(dsdt),
+        sci_interrupt: le_u16(bytes, 46),
+        smi_command: le_u32(bytes, 48),
+        acpi_enable: bytes.get(52).copied().unwrap_or(0),
+        acpi_disable: bytes.get(53).copied().unwrap_or(0),
+        pm1a_event: io_block(bytes, 56, 148),
+        pm1b_event: io_block(bytes, 60, 160),
+        pm1a_control: io_block(bytes, 64, 172),
+        pm1b_control: io_block(bytes, 68, 184),
+        pm_timer: io_block(bytes, 76, 208),
+        pm_timer_32bit: flags & FADT_TMR_VAL_EXT != 0,
+        century_register: bytes.get(108).copied().unwrap_or(0),
+        // IAPC_BOOT_ARCH só existe a partir do ACPI 2.0; antes, assume 8042
+        has_8042: bytes[8] < 2 || le_u16(bytes, 109) & BOOT_ARCH_8042 != 0,
+        flags,
+        reset: (flags & FADT_RESET_REG_SUP != 0 && bytes.len() > 128)
+            .then(|| (gas_at(bytes, 116), bytes[128])),
+    }
+}
+
+pub fn fadt() -> Option<Fadt> {
+    Some(parse_fadt(table_bytes(find_table(b"FACP")?)))
+}
+
+// --- HPET ---
+
+#[derive(Debug, Clone, Copy)]
+pub struct HpetInfo {
+    pub base: GenericAddress,
+    pub hpet_number: u8,
+    pub hardware_revision: u8,
+    pub comparators: u8,
+    pub counter_64bit: bool,
+    pub legacy_replacement: bool,
+    pub vendor_id: u16,
+    /// Menor período seguro em modo periódico, em ticks do contador.
+    pub minimum_tick: u16,
+}
+
+pub fn hpet() -> Option<HpetInfo> {
+    let bytes = table_bytes(find_table(b"HPET")?);
+    let block_id = le_u32(bytes, SDT_HEADER_SIZE);
+    Some(HpetInfo {
+        base: gas_at(bytes, SDT_HEADER_SIZE + 4),
+        hpet_number: bytes.get(SDT_HEADER_SIZE + 16).copied().unwrap_or(0),
+        hardware_revision: block_id as u8,
+        comparators: ((block_id >> 8) & 0x1F) as u8 + 1,
+        counter_64bit: block_id & (1 << 13) != 0,
+        legacy_replacement: block_id & (1 << 15) != 0,
+        vendor_id: (block_id >> 16) as u16,
+        minimum_tick: le_u16(bytes, SDT_HEADER_SIZE + 17),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fadt_v1_short_table() {
+        // FADT do ACPI 1.0: 116 bytes, sem X_ nem registrador de reset
+        let mut bytes = [0u8; 116];
+        bytes[..4].copy_from_slice(b"FACP");
+        bytes[8] = 1;
+        bytes[40..44].copy_from_slice(&0x7FE0_0000u32.to_le_bytes());
+        bytes[48..52].copy_from_slice(&0xB2u32.to_le_bytes());
+        bytes[52] = 0xF1;
+        bytes[64..68].copy_from_slice(&0x604u32.to_le_bytes());
+        bytes[108] = 0x32;
+        bytes[112..116].copy_from_slice(&FADT_RESET_REG_SUP.to_le_bytes());
+
+        let fadt = parse_fadt(&bytes);
+        assert_eq!(fadt.dsdt.as_u64(), 0x7FE0_0000);
+        assert_eq!(fadt.smi_command, 0xB2);
+        assert_eq!(fadt.acpi_enable, 0xF1);
+        assert_eq!(fadt.pm1a_control, 0x604);
+        assert_eq!(fadt.pm1b_control, 0);
+        assert_eq!(fadt.century_register, 0x32);
+        assert!(fadt.has_8042);
+        assert!(fadt.reset.is_none());
+    }
+
+    #[test]
+    fn test_io_block_falls_back_to_gas() {
+        let mut bytes = [0u8; 244];
+        bytes[172] = GAS_SYSTEM_IO;
+        bytes[176..184].copy_from_slice(&0x1804u64.to_le_bytes());
+        assert_eq!(io_block(&bytes, 64, 172), 0x1804);
+        bytes[172] = GAS_SYSTEM_MEMORY;
+        assert_eq!(io_block(&bytes, 64, 172), 0);
+    }
+}
@@ -0,0 +1,284 @@
+// src/allocator.rs
+// ====================
+// HEAP DO KERNEL - Lista ligada de blocos livres (first-fit, com coalescência)
+// ====================
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem;
+use core::ptr;
+use x86_64::VirtAddr;
+use x86_64::structures::paging::PageTableFlags;
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::Size4KiB;
+use x86_64::instructions::interrupts;
+use spin::Mutex;
+use crate::slab::{ClassStats, SlabAllocator, CLASS_COUNT};
+
+pub const HEAP_START: u64 = 0x4444_4444_0000;
+pub const HEAP_SIZE: usize = 4 * 1024 * 1024; // 4 MiB
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeapStats {
+    pub heap_size: usize,
+    pub in_use: usize,
+    pub peak: usize,
+    pub allocations: u64,
+    pub frees: u64,
+    pub free_blocks: usize,
+    pub largest_free: usize,
+}
+
+impl HeapStats {
+    /// Fragmentação externa em %: quanto do espaço livre não está no maior bloco.
+    pub fn fragmentation(&self) -> usize {
+        let free = self.heap_size - self.in_use;
+        (self.largest_free * 100).checked_div(free).map_or(0, |pct| 100 - pct)
+    }
+}
+
+// Cabeçalho gravado dentro de cada bloco livre
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+const MIN_BLOCK: usize = mem::size_of::<FreeBlock>();
+
+/// Blocos livres ordenados por endereço, para que vizinhos se fundam no free.
+pub struct LinkedListHeap {
+    head: *mut FreeBlock,
+    heap_size: usize,
+    in_use: usize,
+    peak: usize,
+    allocations: u64,
+    frees: u64,
+}
+
+// Os ponteiros só são tocados com o Mutex do alocador travado
+unsafe impl Send for LinkedListHeap {}
+
+impl LinkedListHeap {
+    pub const fn empty() -> Self {
+        LinkedListHeap {
+            head: ptr::null_mut(),
+            heap_size: 0,
+            in_use: 0,
+            peak: 0,
+            allocations: 0,
+            frees: 0,
+        }
+    }
+
+    /// # Safety
+    /// `[start, start + size)` precisa estar mapeado e sem outro dono.
+    pub unsafe fn init(&mut self, start: usize, size: usize) {
+        self.heap_size = size;
+        self.insert_free(start, size);
+    }
+
+    /// Tamanho e alinhamento reais usados para `layout` (todo bloco precisa
+    /// caber um `FreeBlock` quando voltar para a lista).
+    fn adjust(layout: Layout) -> (usize, usize) {
+        let align = layout.align().max(mem::align_of::<FreeBlock>());
+        let size = layout.size().max(MIN_BLOCK);
+        (align_up(size, mem::align_of::<FreeBlock>()), align)
+    }
+
+    pub fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = Self::adjust(layout);
+
+        let mut prev: *mut FreeBlock = ptr::null_mut();
+        let mut current = self.head;
+        unsafe {
+            while !current.is_null() {
+                let block_start = current as usize;
+                let block_end = block_start + (*current).size;
+
+                // Sobra na frente precisa virar um bloco livre válido
+                let mut alloc_start = align_up(block_start, align);
+                if alloc_start != block_start && alloc_start - block_start < MIN_BLOCK {
+                    alloc_start = align_up(block_start + MIN_BLOCK, align);
+                }
+                let alloc_end = alloc_start.saturating_add(size);
+                let tail = block_end.saturating_sub(alloc_end);
+
+                if alloc_end <= block_end && (tail == 0 || tail >= MIN_BLOCK) {
+                    let next = (*current).next;
+                    if prev.is_null() { self.head = next; } else { (*prev).next = next; }
+
+                    if alloc_start > block_start {
+                        self.insert_free(block_start, alloc_start - block_start);
+                    }
+                    if tail > 0 {
+                        self.insert_free(alloc_end, tail);
+                    }
+
+                    self.in_use += size;
+                    self.peak = self.peak.max(self.in_use);
+                    self.allocations += 1;
+                    return alloc_start as *mut u8;
+                }
+                prev = current;
+                current = (*current).next;
+            }
+        }
+        ptr::null_mut()
+    }
+
+    /// # Safety
+    /// `ptr` precisa ter vindo de `allocate` com o mesmo `layout`.
+    pub unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = Self::adjust(layout);
+        self.insert_free(ptr as usize, size);
+        self.in_use -= size;
+        self.frees += 1;
+    }
+
+    // Insere mantendo a ordem por endereço e funde com os vizinhos
+    unsafe fn insert_free(&mut self, addr: usize, size: usize) {
+        let mut prev: *mut FreeBlock = ptr::null_mut();
+        let mut next = self.head;
+        while !next.is_null() && (next as usize) < addr {
+            prev = next;
+            next = (*next).next;
+        }
+
+        let block = addr as *mut FreeBlock;
+        block.write(FreeBlock { size, next });
+        if !next.is_null() && addr + size == next as usize {
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+
+        if prev.is_null() {
+            self.head = block;
+        } else if prev as usize + (*prev).size == addr {
+            (*prev).size += (*block).size;
+            (*prev).next = (*block).next;
+        } else {
+            (*prev).next = block;
+        }
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        let mut free_blocks = 0;
+        let mut largest_free = 0;
+        let mut current = self.head;
+        while !current.is_null() {
+            unsafe {
+                free_blocks += 1;
+                largest_free = largest_free.max((*current).size);
+                current = (*current).next;
+            }
+        }
+        HeapStats {
+            heap_size: self.heap_size,
+            in_use: self.in_use,
+            peak: self.peak,
+            allocations: self.allocations,
+            frees: self.frees,
+            free_blocks,
+            largest_free,
+        }
+    }
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+// O slab atende objetos pequenos; o resto (e as páginas dos slabs) vem da lista ligada
+struct KernelHeap {
+    slabs: SlabAllocator,
+    heap: LinkedListHeap,
+}
+
+impl KernelHeap {
+    fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        match SlabAllocator::class_for(layout) {
+            Some(class) => self.slabs.allocate(class, &mut self.heap),
+            None => self.heap.allocate(layout),
+        }
+    }
+
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        match SlabAllocator::class_for(layout) {
+            Some(class) => self.slabs.deallocate(class, ptr),
+            None => self.heap.deallocate(ptr, layout),
+        }
+    }
+}
+
+/// Alocador global: trava o heap com interrupções desligadas, para que um
+/// handler nunca encontre o lock preso pelo código que ele interrompeu.
+pub struct KernelAllocator {
+    inner: Mutex<KernelHeap>,
+}
+
+unsafe impl GlobalAlloc for KernelAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        interrupts::without_interrupts(|| self.inner.lock().allocate(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        interrupts::without_interrupts(|| self.inner.lock().deallocate(ptr, layout))
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: KernelAllocator = KernelAllocator {
+    inner: Mutex::new(KernelHeap {
+        slabs: SlabAllocator::new(),
+        heap: LinkedListHeap::empty(),
+    }),
+};
+
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    panic!("falha de alocação: {:?}", layout);
+}
+
+/// Mapeia a região do heap e entrega ao alocador global.
+pub fn init_heap() -> Result<(), MapToError<Size4KiB>> {
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+    crate::memory::map_range(VirtAddr::new(HEAP_START), HEAP_SIZE as u64, flags)?;
+    unsafe {
+        ALLOCATOR.inner.lock().heap.init(HEAP_START as usize, HEAP_SIZE);
+    }
+    Ok(())
+}
+
+pub fn stats() -> HeapStats {
+    interrupts::without_interrupts(|| ALLOCATOR.inner.lock().heap.stats())
+}
+
+pub fn slab_stats() -> [ClassStats; CLASS_COUNT] {
+    interrupts::without_interrupts(|| ALLOCATOR.inner.lock().slabs.stats())
+}
+
+// Caminhos explícitos para o benchmark do slab (ignoram o roteamento por tamanho)
+
+/// # Safety
+/// Mesmo contrato de `GlobalAlloc::alloc`.
+pub unsafe fn bench_alloc(layout: Layout, via_slab: bool) -> *mut u8 {
+    interrupts::without_interrupts(|| {
+        let mut inner = ALLOCATOR.inner.lock();
+        let inner = &mut *inner;
+        match SlabAllocator::class_for(layout) {
+            Some(class) if via_slab => inner.slabs.allocate(class, &mut inner.heap),
+            _ => inner.heap.allocate(layout),
+        }
+    })
+}
+
+/// # Safety
+/// `ptr` precisa ter vindo de `bench_alloc` com o mesmo `layout` e `via_slab`.
+pub unsafe fn bench_dealloc(ptr: *mut u8, layout: Layout, via_slab: bool) {
+    interrupts::without_interrupts(|| {
+        let mut inner = ALLOCATOR.inner.lock();
+        match SlabAllocator::class_for(layout) {
+            Some(class) if via_slab => inner.slabs.deallocate(class, ptr),
+            _ => inner.heap.deallocate(ptr, layout),
+        }
+    })
+}
@@ -0,0 +1,207 @@
+// src/apic.rs
+// ====================
+// LOCAL APIC + I/O APIC - Substituto do par de 8259
+// ====================
+
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::vec::Vec;
+use spin::Mutex;
+use x86_64::{PhysAddr, VirtAddr};
+use x86_64::registers::model_specific::Msr;
+use crate::acpi::{InterruptOverride, Madt};
+use crate::cpuid::{self, Feature};
+use crate::memory;
+use crate::timer;
+
+const IA32_APIC_BASE: u32 = 0x1B;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+
+// Registradores do Local APIC (offsets na página MMIO)
+const LAPIC_ID: u32 = 0x20;
+const LAPIC_TPR: u32 = 0x80;
+const LAPIC_EOI: u32 = 0xB0;
+const LAPIC_SVR: u32 = 0xF0;
+const LAPIC_LVT_TIMER: u32 = 0x320;
+const LAPIC_LVT_ERROR: u32 = 0x370;
+const LAPIC_TIMER_INITIAL: u32 = 0x380;
+const LAPIC_TIMER_CURRENT: u32 = 0x390;
+const LAPIC_TIMER_DIVIDE: u32 = 0x3E0;
+
+const SVR_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_DIVIDE_BY_16: u32 = 0x3;
+
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+pub const ERROR_VECTOR: u8 = 0xFE;
+
+// Registradores do I/O APIC (acesso indireto via IOREGSEL/IOWIN)
+const IOAPIC_REGSEL: u64 = 0x00;
+const IOAPIC_WIN: u64 = 0x10;
+const IOAPIC_VER: u32 = 0x01;
+const IOAPIC_REDTBL: u32 = 0x10;
+
+const REDIR_ACTIVE_LOW: u32 = 1 << 13;
+const REDIR_LEVEL: u32 = 1 << 15;
+const REDIR_MASKED: u32 = 1 << 16;
+
+// Flags do override no MADT (polaridade bits 0-1, gatilho bits 2-3)
+const MPS_POLARITY_LOW: u16 = 0b11;
+const MPS_TRIGGER_LEVEL: u16 = 0b11 << 2;
+
+static LAPIC_BASE: AtomicU64 = AtomicU64::new(0);
+
+struct IoApic {
+    base: VirtAddr,
+    gsi_base: u32,
+    entries: u32,
+}
+
+impl IoApic {
+    fn read(&self, reg: u32) -> u32 {
+        unsafe {
+            ptr::write_volatile((self.base + IOAPIC_REGSEL).as_mut_ptr::<u32>(), reg);
+            ptr::read_volatile((self.base + IOAPIC_WIN).as_ptr::<u32>())
+        }
+    }
+
+    fn write(&self, reg: u32, value: u32) {
+        unsafe {
+            ptr::write_volatile((self.base + IOAPIC_REGSEL).as_mut_ptr::<u32>(), reg);
+            ptr::write_volatile((self.base + IOAPIC_WIN).as_mut_ptr::<u32>(), value);
+        }
+    }
+
+    fn set_redirection(&self, pin: u32, low: u32, high: u32) {
+        // Destino primeiro; a parte baixa (com a máscara) por último
+        self.write(IOAPIC_REDTBL + pin * 2 + 1, high);
+        self.write(IOAPIC_REDTBL + pin * 2, low);
+    }
+}
+
+struct IoApicState {
+    io_apics: Vec<IoApic>,
+    overrides: Vec<InterruptOverride>,
+}
+
+static IO_APICS: Mutex<IoApicState> = Mutex::new(IoApicState {
+    io_apics: Vec::new(),
+    overrides: Vec::new(),
+});
+
+/// CPUID.01h:EDX[9] + MSR IA32_APIC_BASE.
+pub fn is_supported() -> bool {
+    cpuid::has(Feature::Apic) && cpuid::has(Feature::Msr)
+}
+
+fn lapic_read(reg: u32) -> u32 {
+    let base = LAPIC_BASE.load(Ordering::Relaxed);
+    unsafe { ptr::read_volatile((base + reg as u64) as *const u32) }
+}
+
+fn lapic_write(reg: u32, value: u32) {
+    let base = LAPIC_BASE.load(Ordering::Relaxed);
+    unsafe { ptr::write_volatile((base + reg as u64) as *mut u32, value) }
+}
+
+pub fn lapic_id() -> u8 {
+    (lapic_read(LAPIC_ID) >> 24) as u8
+}
+
+/// Mapeia e liga o Local APIC e todos os I/O APICs do MADT, com todas as
+/// entradas de redirecionamento mascaradas.
+pub fn init(madt: &Madt) -> Result<(), &'static str> {
+    if !is_supported() {
+        return Err("CPU sem APIC");
+    }
+    if madt.io_apics.is_empty() {
+        return Err("MADT sem I/O APIC");
+    }
+
+    let mut msr = Msr::new(IA32_APIC_BASE);
+    let base_msr = unsafe { msr.read() };
+    unsafe { msr.write(base_msr | APIC_BASE_ENABLE); }
+    // O MADT pode ter um override de endereço; o MSR é a fonte primária
+    let lapic_phys = match base_msr & 0xF_FFFF_F000 {
+        0 => madt.local_apic_address,
+        addr => addr,
+    };
+    let lapic = memory::map_mmio(PhysAddr::new(lapic_phys), 4096).map_err(|_| "falha ao mapear o LAPIC")?;
+    LAPIC_BASE.store(lapic.as_u64(), Ordering::SeqCst);
+
+    lapic_write(LAPIC_TPR, 0);
+    lapic_write(LAPIC_LVT_ERROR, ERROR_VECTOR as u32);
+    lapic_write(LAPIC_SVR, SVR_ENABLE | SPURIOUS_VECTOR as u32);
+
+    let mut state = IO_APICS.lock();
+    for info in madt.io_apics.iter() {
+        let base = memory::map_mmio(PhysAddr::new(info.address as u64), 4096)
+            .map_err(|_| "falha ao mapear o I/O APIC")?;
+        let mut io_apic = IoApic { base, gsi_base: info.gsi_base, entries: 0 };
+        io_apic.entries = ((io_apic.read(IOAPIC_VER) >> 16) & 0xFF) + 1;
+        for pin in 0..io_apic.entries {
+            io_apic.set_redirection(pin, REDIR_MASKED, 0);
+        }
+        state.io_apics.push(io_apic);
+    }
+    state.overrides = madt.overrides.clone();
+    Ok(())
+}
+
+/// Encaminha a IRQ ISA `irq` para `vector` no LAPIC deste processador,
+/// respeitando os overrides do MADT (ex.: IRQ 0 -> GSI 2 no QEMU).
+pub fn route_irq(irq: u8, vector: u8) -> bool {
+    let state = IO_APICS.lock();
+    let (gsi, flags) = state.overrides.iter()
+        .find(|o| o.source == irq)
+        .map_or((irq as u32, 0), |o| (o.gsi, o.flags));
+
+    let Some(io_apic) = state.io_apics.iter()
+        .find(|io| gsi >= io.gsi_base && gsi < io.gsi_base + io.entries) else {
+        return false;
+    };
+
+    // ISA padrão: ativo em alto, borda; o override pode mudar os dois
+    let mut low = vector as u32;
+    if flags & MPS_POLARITY_LOW == MPS_POLARITY_LOW {
+        low |= REDIR_ACTIVE_LOW;
+    }
+    if flags & MPS_TRIGGER_LEVEL == MPS_TRIGGER_LEVEL {
+        low |= REDIR_LEVEL;
+    }
+    io_apic.set_redirection(gsi - io_apic.gsi_base, low, (lapic_id() as u32) << 24);
+    true
+}
+
+pub fn eoi() {
+    lapic_write(LAPIC_EOI, 0);
+}
+
+/// Mede quantos ciclos do timer do LAPIC (divisor 16) cabem em um tick do
+/// PIT. Precisa das interrupções ligadas e do PIT ainda entregando IRQ 0.
+pub fn calibrate_timer(pit_ticks: u64) -> u32 {
+    lapic_write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+    lapic_write(LAPIC_LVT_TIMER, LVT_MASKED);
+
+    // Começa alinhado na borda de um tick
+    let start = timer::ticks();
+    while timer::ticks() == start {
+        x86_64::instructions::hlt();
+    }
+    lapic_write(LAPIC_TIMER_INITIAL, u32::MAX);
+    let begin = timer::ticks();
+    while timer::ticks() < begin + pit_ticks {
+        x86_64::instructions::hlt();
+    }
+    let elapsed = u32::MAX - lapic_read(LAPIC_TIMER_CURRENT);
+    lapic_write(LAPIC_TIMER_INITIAL, 0);
+    elapsed / pit_ticks as u32
+}
+
+/// Timer periódico do LAPIC disparando `vector` a cada `counts` ciclos.
+pub fn start_timer(vector: u8, counts: u32) {
+    lapic_write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+    lapic_write(LAPIC_LVT_TIMER, LVT_TIMER_PERIODIC | vector as u32);
+    lapic_write(LAPIC_TIMER_INITIAL, counts);
+}
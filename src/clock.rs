@@ -0,0 +1,104 @@
+// src/clock.rs
+// ====================
+// CLOCKSOURCE - Relógio monotônico em ns (TSC calibrado pelo HPET)
+// ====================
+
+use core::arch::x86_64::_rdtsc;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use crate::cpuid::{self, Feature};
+use crate::{hpet, timer};
+
+/// Janela de calibração do TSC contra o HPET.
+const CALIBRATION_NS: u64 = 10_000_000;
+
+// ns = (ciclos * TSC_MULT) >> TSC_SHIFT
+const TSC_SHIFT: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Source {
+    Pit = 0,
+    Hpet = 1,
+    Tsc = 2,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Source::Pit => "PIT",
+            Source::Hpet => "HPET",
+            Source::Tsc => "TSC",
+        })
+    }
+}
+
+static SOURCE: AtomicU8 = AtomicU8::new(Source::Pit as u8);
+static TSC_MULT: AtomicU64 = AtomicU64::new(0);
+static TSC_KHZ: AtomicU64 = AtomicU64::new(0);
+// Leituras do TSC e do HPET no fim da calibração (origem do relógio TSC)
+static TSC_BASE: AtomicU64 = AtomicU64::new(0);
+static NANOS_BASE: AtomicU64 = AtomicU64::new(0);
+
+/// Escolhe a melhor fonte: TSC invariante calibrado > HPET > ticks do PIT.
+/// Precisa do HPET já inicializado para usar o TSC.
+pub fn init() {
+    if !hpet::is_available() {
+        crate::klog!("Clock: sem HPET, usando o PIT ({} Hz)", timer::TIMER_HZ);
+        return;
+    }
+    SOURCE.store(Source::Hpet as u8, Ordering::SeqCst);
+    // CPUID.80000007h:EDX[8]: taxa constante, inclusive em C-states
+    if !cpuid::has(Feature::InvariantTsc) {
+        crate::klog!("Clock: TSC não invariante, usando o HPET");
+        return;
+    }
+
+    // Mede ciclos do TSC numa janela do HPET, começando na borda de um tick
+    let start_ns = hpet::nanos();
+    let mut begin_ns = hpet::nanos();
+    while begin_ns == start_ns {
+        begin_ns = hpet::nanos();
+    }
+    let begin_tsc = unsafe { _rdtsc() };
+    let mut end_ns = begin_ns;
+    while end_ns - begin_ns < CALIBRATION_NS {
+        end_ns = hpet::nanos();
+    }
+    let end_tsc = unsafe { _rdtsc() };
+
+    let cycles = end_tsc - begin_tsc;
+    let elapsed = end_ns - begin_ns;
+    TSC_KHZ.store(cycles * 1_000_000 / elapsed, Ordering::SeqCst);
+    TSC_MULT.store((((elapsed as u128) << TSC_SHIFT) / cycles as u128) as u64, Ordering::SeqCst);
+    TSC_BASE.store(end_tsc, Ordering::SeqCst);
+    NANOS_BASE.store(end_ns, Ordering::SeqCst);
+    SOURCE.store(Source::Tsc as u8, Ordering::SeqCst);
+    crate::klog!("Clock: TSC invariante a {} MHz (calibrado pelo HPET)", tsc_khz() / 1000);
+}
+
+pub fn source() -> Source {
+    match SOURCE.load(Ordering::Relaxed) {
+        2 => Source::Tsc,
+        1 => Source::Hpet,
+        _ => Source::Pit,
+    }
+}
+
+/// Frequência do TSC em kHz (0 se não calibrado).
+pub fn tsc_khz() -> u64 {
+    TSC_KHZ.load(Ordering::Relaxed)
+}
+
+/// Nanossegundos monotônicos, na escala do HPET.
+pub fn nanos() -> u64 {
+    match source() {
+        Source::Tsc => {
+            let cycles = unsafe { _rdtsc() }.wrapping_sub(TSC_BASE.load(Ordering::Relaxed));
+            let ns = (cycles as u128 * TSC_MULT.load(Ordering::Relaxed) as u128) >> TSC_SHIFT;
+            NANOS_BASE.load(Ordering::Relaxed) + ns as u64
+        }
+        Source::Hpet => hpet::nanos(),
+        Source::Pit => timer::uptime_ms() * 1_000_000,
+    }
+}
@@ -0,0 +1,41 @@
+// src/config.rs
+// ====================
+// CONFIGURAÇÃO DE BOOT - Linhas `set chave=valor` e `export` do /etc/tri-shellrc
+// ====================
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const RC_PATH: &str = "/etc/tri-shellrc";
+
+/// Valor de `set <key>=<valor>` no tri-shellrc (aspas simples removidas).
+/// A última ocorrência vence, como num shell.
+pub fn get(key: &str) -> Option<String> {
+    let rc = crate::virtual_fs::read_file(RC_PATH)?;
+    let rc = core::str::from_utf8(&rc).ok()?;
+    rc.lines()
+        .rev()
+        .filter_map(|line| line.trim().strip_prefix("set "))
+        .filter_map(|assignment| assignment.split_once('='))
+        .find(|(name, _)| name.trim() == key)
+        .map(|(_, value)| String::from(value.trim().trim_matches('\'')))
+}
+
+/// Linhas `export NOME=valor`, no formato `NOME=valor` do envp. Um nome
+/// repetido fica só com a última definição.
+pub fn exports() -> Vec<String> {
+    let Some(rc) = crate::virtual_fs::read_file(RC_PATH) else {
+        return Vec::new();
+    };
+    let rc = String::from_utf8_lossy(&rc);
+    let mut env: Vec<String> = Vec::new();
+    for (name, value) in rc.lines()
+        .filter_map(|line| line.trim().strip_prefix("export "))
+        .filter_map(|assignment| assignment.split_once('='))
+    {
+        let name = name.trim();
+        env.retain(|entry| entry.split('=').next() != Some(name));
+        env.push(alloc::format!("{}={}", name, value.trim().trim_matches('\'')));
+    }
+    env
+}
@@ -0,0 +1,252 @@
+// src/cpuid.rs
+// ====================
+// CPUID - Fabricante, modelo e recursos da CPU (consultado uma vez, em cache)
+// ====================
+
+use core::arch::x86_64::{__cpuid, __cpuid_count, CpuidResult};
+use core::fmt;
+use lazy_static::lazy_static;
+
+const LEAF_VENDOR: u32 = 0x0000_0000;
+const LEAF_FEATURES: u32 = 0x0000_0001;
+const LEAF_EXTENDED_FEATURES: u32 = 0x0000_0007;
+const LEAF_HYPERVISOR: u32 = 0x4000_0000;
+const LEAF_EXT_MAX: u32 = 0x8000_0000;
+const LEAF_EXT_FEATURES: u32 = 0x8000_0001;
+const LEAF_BRAND: u32 = 0x8000_0002; // até 0x8000_0004
+const LEAF_ADVANCED_PM: u32 = 0x8000_0007;
+
+// Registrador onde cada bit de recurso mora
+#[derive(Clone, Copy)]
+enum Reg {
+    Leaf1Ecx,
+    Leaf1Edx,
+    Leaf7Ebx,
+    Leaf7Ecx,
+    Leaf7Edx,
+    ExtEcx,
+    ExtEdx,
+    AdvancedPmEdx,
+}
+
+macro_rules! features {
+    ($($variant:ident = ($reg:ident, $bit:expr, $name:expr),)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Feature {
+            $($variant,)*
+        }
+
+        impl Feature {
+            pub const ALL: &'static [Feature] = &[$(Feature::$variant,)*];
+
+            fn location(self) -> (Reg, u32) {
+                match self {
+                    $(Feature::$variant => (Reg::$reg, $bit),)*
+                }
+            }
+
+            /// Nome no estilo do /proc/cpuinfo do Linux.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Feature::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+features! {
+    Fpu = (Leaf1Edx, 0, "fpu"),
+    Pse = (Leaf1Edx, 3, "pse"),
+    Tsc = (Leaf1Edx, 4, "tsc"),
+    Msr = (Leaf1Edx, 5, "msr"),
+    Pae = (Leaf1Edx, 6, "pae"),
+    Apic = (Leaf1Edx, 9, "apic"),
+    Mtrr = (Leaf1Edx, 12, "mtrr"),
+    Pge = (Leaf1Edx, 13, "pge"),
+    Pat = (Leaf1Edx, 16, "pat"),
+    Fxsr = (Leaf1Edx, 24, "fxsr"),
+    Sse = (Leaf1Edx, 25, "sse"),
+    Sse2 = (Leaf1Edx, 26, "sse2"),
+    Sse3 = (Leaf1Ecx, 0, "pni"),
+    Pclmulqdq = (Leaf1Ecx, 1, "pclmulqdq"),
+    Ssse3 = (Leaf1Ecx, 9, "ssse3"),
+    Fma = (Leaf1Ecx, 12, "fma"),
+    Pcid = (Leaf1Ecx, 17, "pcid"),
+    Sse41 = (Leaf1Ecx, 19, "sse4_1"),
+    Sse42 = (Leaf1Ecx, 20, "sse4_2"),
+    X2apic = (Leaf1Ecx, 21, "x2apic"),
+    Popcnt = (Leaf1Ecx, 23, "popcnt"),
+    TscDeadline = (Leaf1Ecx, 24, "tsc_deadline_timer"),
+    Aes = (Leaf1Ecx, 25, "aes"),
+    Xsave = (Leaf1Ecx, 26, "xsave"),
+    Osxsave = (Leaf1Ecx, 27, "osxsave"),
+    Avx = (Leaf1Ecx, 28, "avx"),
+    Rdrand = (Leaf1Ecx, 30, "rdrand"),
+    Hypervisor = (Leaf1Ecx, 31, "hypervisor"),
+    Fsgsbase = (Leaf7Ebx, 0, "fsgsbase"),
+    Bmi1 = (Leaf7Ebx, 3, "bmi1"),
+    Avx2 = (Leaf7Ebx, 5, "avx2"),
+    Smep = (Leaf7Ebx, 7, "smep"),
+    Bmi2 = (Leaf7Ebx, 8, "bmi2"),
+    Invpcid = (Leaf7Ebx, 10, "invpcid"),
+    Avx512f = (Leaf7Ebx, 16, "avx512f"),
+    Rdseed = (Leaf7Ebx, 18, "rdseed"),
+    Smap = (Leaf7Ebx, 20, "smap"),
+    Umip = (Leaf7Ecx, 2, "umip"),
+    Pku = (Leaf7Ecx, 3, "pku"),
+    MdClear = (Leaf7Edx, 10, "md_clear"),
+    Lahf = (ExtEcx, 0, "lahf_lm"),
+    Syscall = (ExtEdx, 11, "syscall"),
+    Nx = (ExtEdx, 20, "nx"),
+    Page1Gb = (ExtEdx, 26, "pdpe1gb"),
+    Rdtscp = (ExtEdx, 27, "rdtscp"),
+    LongMode = (ExtEdx, 29, "lm"),
+    InvariantTsc = (AdvancedPmEdx, 8, "constant_tsc"),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CpuInfo {
+    pub vendor: [u8; 12],
+    pub brand: [u8; 48],
+    pub hypervisor: [u8; 12],
+    pub family: u32,
+    pub model: u32,
+    pub stepping: u32,
+    pub max_leaf: u32,
+    pub max_extended_leaf: u32,
+    /// Núcleos lógicos por pacote (CPUID.01h:EBX[23:16]).
+    pub logical_cpus: u32,
+    leaf1: CpuidResult,
+    leaf7: CpuidResult,
+    ext1: CpuidResult,
+    advanced_pm: CpuidResult,
+}
+
+const EMPTY_LEAF: CpuidResult = CpuidResult { eax: 0, ebx: 0, ecx: 0, edx: 0 };
+
+fn regs_to_bytes(regs: &[u32], out: &mut [u8]) {
+    for (chunk, reg) in out.chunks_mut(4).zip(regs) {
+        chunk.copy_from_slice(&reg.to_le_bytes());
+    }
+}
+
+fn trimmed(bytes: &[u8]) -> &str {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..end]).unwrap_or("?").trim()
+}
+
+impl CpuInfo {
+    fn detect() -> CpuInfo {
+        let mut info = CpuInfo {
+            vendor: [0; 12],
+            brand: [0; 48],
+            hypervisor: [0; 12],
+            family: 0,
+            model: 0,
+            stepping: 0,
+            max_leaf: 0,
+            max_extended_leaf: 0,
+            logical_cpus: 0,
+            leaf1: EMPTY_LEAF,
+            leaf7: EMPTY_LEAF,
+            ext1: EMPTY_LEAF,
+            advanced_pm: EMPTY_LEAF,
+        };
+
+        let vendor = __cpuid(LEAF_VENDOR);
+        info.max_leaf = vendor.eax;
+        // A ordem do fabricante é EBX, EDX, ECX ("Genu" "ineI" "ntel")
+        regs_to_bytes(&[vendor.ebx, vendor.edx, vendor.ecx], &mut info.vendor);
+
+        info.leaf1 = __cpuid(LEAF_FEATURES);
+        let signature = info.leaf1.eax;
+        let base_family = (signature >> 8) & 0xF;
+        let base_model = (signature >> 4) & 0xF;
+        info.stepping = signature & 0xF;
+        info.family = if base_family == 0xF { base_family + ((signature >> 20) & 0xFF) } else { base_family };
+        info.model = if base_family == 0x6 || base_family == 0xF {
+            base_model + (((signature >> 16) & 0xF) << 4)
+        } else {
+            base_model
+        };
+        info.logical_cpus = (info.leaf1.ebx >> 16) & 0xFF;
+
+        if info.max_leaf >= LEAF_EXTENDED_FEATURES {
+            info.leaf7 = __cpuid_count(LEAF_EXTENDED_FEATURES, 0);
+        }
+
+        info.max_extended_leaf = __cpuid(LEAF_EXT_MAX).eax;
+        if info.max_extended_leaf >= LEAF_EXT_FEATURES {
+            info.ext1 = __cpuid(LEAF_EXT_FEATURES);
+        }
+        if info.max_extended_leaf >= LEAF_BRAND + 2 {
+            for i in 0..3 {
+                let r = __cpuid(LEAF_BRAND + i);
+                regs_to_bytes(&[r.eax, r.ebx, r.ecx, r.edx], &mut info.brand[i as usize * 16..]);
+            }
+        }
+        if info.max_extended_leaf >= LEAF_ADVANCED_PM {
+            info.advanced_pm = __cpuid(LEAF_ADVANCED_PM);
+        }
+
+        if info.has(Feature::Hypervisor) {
+            let r = __cpuid(LEAF_HYPERVISOR);
+            regs_to_bytes(&[r.ebx, r.ecx, r.edx], &mut info.hypervisor);
+        }
+        info
+    }
+
+    pub fn has(&self, feature: Feature) -> bool {
+        let (reg, bit) = feature.location();
+        let value = match reg {
+            Reg::Leaf1Ecx => self.leaf1.ecx,
+            Reg::Leaf1Edx => self.leaf1.edx,
+            Reg::Leaf7Ebx => self.leaf7.ebx,
+            Reg::Leaf7Ecx => self.leaf7.ecx,
+            Reg::Leaf7Edx => self.leaf7.edx,
+            Reg::ExtEcx => self.ext1.ecx,
+            Reg::ExtEdx => self.ext1.edx,
+            Reg::AdvancedPmEdx => self.advanced_pm.edx,
+        };
+        value & (1 << bit) != 0
+    }
+
+    pub fn vendor_str(&self) -> &str {
+        trimmed(&self.vendor)
+    }
+
+    pub fn brand_str(&self) -> &str {
+        trimmed(&self.brand)
+    }
+
+    /// Assinatura do hypervisor ("TCGTCGTCGTCG", "KVMKVMKVM"...), se houver.
+    pub fn hypervisor_str(&self) -> Option<&str> {
+        self.has(Feature::Hypervisor).then(|| trimmed(&self.hypervisor))
+    }
+
+    /// Recursos presentes, na ordem de `Feature::ALL`.
+    pub fn features(&self) -> impl Iterator<Item = Feature> + '_ {
+        Feature::ALL.iter().copied().filter(|&f| self.has(f))
+    }
+}
+
+impl fmt::Display for CpuInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (família {:#x}, modelo {:#x}, stepping {})",
+            self.brand_str(), self.family, self.model, self.stepping)
+    }
+}
+
+lazy_static! {
+    static ref CPU: CpuInfo = CpuInfo::detect();
+}
+
+pub fn info() -> &'static CpuInfo {
+    &CPU
+}
+
+/// Atalho para `info().has(feature)`.
+pub fn has(feature: Feature) -> bool {
+    CPU.has(feature)
+}
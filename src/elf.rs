@@ -0,0 +1,323 @@
+// src/elf.rs
+// ====================
+// CARREGADOR ELF64 - Validação, PT_LOAD num espaço novo e pilha inicial
+// (argv/envp/auxv) no formato da ABI System V
+// ====================
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use x86_64::structures::paging::PageTableFlags;
+use x86_64::VirtAddr;
+use crate::memory::AddressSpace;
+use crate::usermode::{self, USER_STACK_SIZE, USER_STACK_TOP};
+
+const ET_EXEC: u16 = 2;
+const EM_X86_64: u16 = 0x3E;
+const PT_LOAD: u32 = 1;
+const PT_PHDR: u32 = 6;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const MAX_PHDRS: usize = 64;
+const PAGE_SIZE: u64 = 4096;
+
+// Tipos do vetor auxiliar usados aqui
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_ENTRY: u64 = 9;
+pub const AT_RANDOM: u64 = 25;
+
+/// Parte da pilha reservada para argv/envp/auxv; o resto fica para o programa.
+const MAX_STACK_IMAGE: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramHeader {
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+}
+
+#[derive(Debug)]
+pub struct Elf {
+    pub entry: u64,
+    pub phoff: u64,
+    pub phnum: usize,
+    // Endereço dado por um PT_PHDR, se houver
+    pub phdr_vaddr: Option<u64>,
+    pub segments: Vec<ProgramHeader>,
+}
+
+fn le_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn le_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn le_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Valida o cabeçalho e os program headers. Só aceita executáveis estáticos
+/// x86_64 (ET_EXEC) com segmentos inteiros dentro do arquivo e da faixa do
+/// usuário, abaixo da pilha.
+pub fn parse(data: &[u8]) -> Result<Elf, &'static str> {
+    if data.len() < EHDR_SIZE || &data[..4] != b"\x7fELF" {
+        return Err("não é um ELF");
+    }
+    if data[4] != 2 || data[5] != 1 || data[6] != 1 {
+        return Err("só ELF64 little-endian versão 1");
+    }
+    if le_u16(data, 16) != ET_EXEC {
+        return Err("não é um executável estático (ET_EXEC)");
+    }
+    if le_u16(data, 18) != EM_X86_64 {
+        return Err("arquitetura não é x86_64");
+    }
+    let entry = le_u64(data, 24);
+    let phoff = le_u64(data, 32);
+    let phentsize = le_u16(data, 54) as usize;
+    let phnum = le_u16(data, 56) as usize;
+    if phentsize != PHDR_SIZE || phnum == 0 || phnum > MAX_PHDRS {
+        return Err("tabela de program headers inválida");
+    }
+    let table_end = phoff.checked_add((phnum * PHDR_SIZE) as u64);
+    if phoff < EHDR_SIZE as u64 || table_end.is_none_or(|end| end > data.len() as u64) {
+        return Err("program headers fora do arquivo");
+    }
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let at = phoff as usize + i * PHDR_SIZE;
+        let header = ProgramHeader {
+            kind: le_u32(data, at),
+            flags: le_u32(data, at + 4),
+            offset: le_u64(data, at + 8),
+            vaddr: le_u64(data, at + 16),
+            filesz: le_u64(data, at + 32),
+            memsz: le_u64(data, at + 40),
+        };
+        if header.kind != PT_LOAD {
+            continue;
+        }
+        if header.filesz > header.memsz {
+            return Err("segmento com filesz > memsz");
+        }
+        if header.offset.checked_add(header.filesz).is_none_or(|end| end > data.len() as u64) {
+            return Err("segmento fora do arquivo");
+        }
+        let user_limit = USER_STACK_TOP - USER_STACK_SIZE;
+        if header.vaddr.checked_add(header.memsz).is_none_or(|end| end > user_limit) {
+            return Err("segmento fora da faixa do usuário");
+        }
+        segments.push(header);
+    }
+    if segments.is_empty() {
+        return Err("nenhum segmento PT_LOAD");
+    }
+    let entry_ok = segments.iter().any(|s| s.flags & PF_X != 0 && (s.vaddr..s.vaddr + s.memsz).contains(&entry));
+    if !entry_ok {
+        return Err("entrada fora de um segmento executável");
+    }
+
+    let phdr_vaddr = (0..phnum)
+        .map(|i| phoff as usize + i * PHDR_SIZE)
+        .find(|&at| le_u32(data, at) == PT_PHDR)
+        .map(|at| le_u64(data, at + 16));
+    Ok(Elf { entry, phoff, phnum, phdr_vaddr, segments })
+}
+
+impl Elf {
+    /// Endereço dos program headers na memória (AT_PHDR): o do PT_PHDR ou,
+    /// sem ele, o do PT_LOAD que cobre o offset da tabela.
+    pub fn phdr_address(&self) -> Option<u64> {
+        if self.phdr_vaddr.is_some() {
+            return self.phdr_vaddr;
+        }
+        self.segments.iter()
+            .find(|s| (s.offset..s.offset + s.filesz).contains(&self.phoff))
+            .map(|s| s.vaddr + (self.phoff - s.offset))
+    }
+}
+
+/// Permissões de cada página: a união dos segmentos que a tocam, para que
+/// dois segmentos dividindo uma página não percam escrita ou execução.
+pub fn page_permissions(segments: &[ProgramHeader]) -> BTreeMap<u64, (bool, bool)> {
+    let mut pages = BTreeMap::new();
+    for segment in segments.iter().filter(|s| s.memsz > 0) {
+        let first = segment.vaddr / PAGE_SIZE;
+        let last = (segment.vaddr + segment.memsz - 1) / PAGE_SIZE;
+        for page in first..=last {
+            let entry = pages.entry(page * PAGE_SIZE).or_insert((false, false));
+            entry.0 |= segment.flags & PF_W != 0;
+            entry.1 |= segment.flags & PF_X != 0;
+        }
+    }
+    pages
+}
+
+/// Imagem do topo da pilha inicial e o RSP resultante (apontando para argc):
+///   argc | argv[] | NULL | envp[] | NULL | auxv (tipo, valor)... | AT_NULL
+///   ... strings de argv/envp e 16 bytes do AT_RANDOM ... | top
+/// O RSP sai alinhado em 16, como a ABI exige na entrada do `_start`.
+pub fn build_stack(top: u64, argv: &[&str], envp: &[&str], auxv: &[(u64, u64)], random: [u8; 16])
+    -> Result<(u64, Vec<u8>), &'static str>
+{
+    let strings_len: usize = argv.iter().chain(envp).map(|s| s.len() + 1).sum::<usize>() + random.len();
+    if strings_len > MAX_STACK_IMAGE {
+        return Err("argumentos grandes demais");
+    }
+    let strings_start = (top - strings_len as u64) & !15;
+    let words = 1 + (argv.len() + 1) + (envp.len() + 1) + (auxv.len() + 2) * 2;
+    let rsp = (strings_start - words as u64 * 8) & !15;
+    let size = (top - rsp) as usize;
+    if size > MAX_STACK_IMAGE {
+        return Err("argumentos grandes demais");
+    }
+
+    let mut image = alloc::vec![0u8; size];
+    let mut cursor = (strings_start - rsp) as usize;
+    let mut place = |bytes: &[u8], image: &mut Vec<u8>| {
+        let address = rsp + cursor as u64;
+        image[cursor..cursor + bytes.len()].copy_from_slice(bytes);
+        cursor += bytes.len();
+        address
+    };
+    let mut pointers = Vec::new();
+    for s in argv.iter().chain(envp) {
+        let address = place(s.as_bytes(), &mut image);
+        place(&[0], &mut image);
+        pointers.push(address);
+    }
+    let random_address = place(&random, &mut image);
+
+    let (argv_ptrs, envp_ptrs) = pointers.split_at(argv.len());
+    let mut vector = Vec::with_capacity(words);
+    vector.push(argv.len() as u64);
+    vector.extend_from_slice(argv_ptrs);
+    vector.push(0);
+    vector.extend_from_slice(envp_ptrs);
+    vector.push(0);
+    for &(kind, value) in auxv {
+        vector.extend_from_slice(&[kind, value]);
+    }
+    vector.extend_from_slice(&[AT_RANDOM, random_address, AT_NULL, 0]);
+    for (i, word) in vector.iter().enumerate() {
+        image[i * 8..i * 8 + 8].copy_from_slice(&word.to_le_bytes());
+    }
+    Ok((rsp, image))
+}
+
+// Semente para o AT_RANDOM: não é criptográfica, só varia entre execuções
+fn random_bytes() -> [u8; 16] {
+    let mut x = unsafe { core::arch::x86_64::_rdtsc() } ^ crate::clock::nanos() ^ 0x9E37_79B9_7F4A_7C15;
+    let mut out = [0u8; 16];
+    for chunk in out.chunks_mut(8) {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        chunk.copy_from_slice(&x.to_le_bytes());
+    }
+    out
+}
+
+/// Carrega o ELF num espaço novo, com a pilha pronta. Devolve o espaço,
+/// a entrada e o RSP inicial.
+pub fn load(data: &[u8], argv: &[&str], envp: &[&str]) -> Result<(AddressSpace, VirtAddr, VirtAddr), &'static str> {
+    let elf = parse(data)?;
+    let mut space = AddressSpace::new().map_err(|_| "sem memória")?;
+
+    for (&page, &(writable, executable)) in page_permissions(&elf.segments).iter() {
+        let mut flags = PageTableFlags::empty();
+        if writable {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        if !executable {
+            flags |= PageTableFlags::NO_EXECUTE;
+        }
+        usermode::map_user_region(&mut space, page, PAGE_SIZE, flags)?;
+    }
+    // O resto até memsz (.bss) já está zerado pelas páginas novas
+    for segment in elf.segments.iter() {
+        let bytes = &data[segment.offset as usize..(segment.offset + segment.filesz) as usize];
+        usermode::copy_to_space(&mut space, segment.vaddr, bytes)?;
+    }
+
+    let top = usermode::map_user_stack(&mut space)?;
+    let mut auxv = alloc::vec![
+        (AT_PHENT, PHDR_SIZE as u64),
+        (AT_PHNUM, elf.phnum as u64),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_ENTRY, elf.entry),
+    ];
+    if let Some(phdr) = elf.phdr_address() {
+        auxv.push((AT_PHDR, phdr));
+    }
+    let (rsp, image) = build_stack(top.as_u64(), argv, envp, &auxv, random_bytes())?;
+    usermode::copy_to_space(&mut space, rsp, &image)?;
+    Ok((space, VirtAddr::new(elf.entry), VirtAddr::new(rsp)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_page_gets_union_of_permissions() {
+        let text = ProgramHeader { kind: PT_LOAD, flags: PF_X | 4, offset: 0, vaddr: 0x1000, filesz: 0x1800, memsz: 0x1800 };
+        let data = ProgramHeader { kind: PT_LOAD, flags: PF_W | 4, offset: 0x1800, vaddr: 0x2800, filesz: 0x10, memsz: 0x2000 };
+        let pages = page_permissions(&[text, data]);
+        assert_eq!(pages.get(&0x1000), Some(&(false, true)));
+        assert_eq!(pages.get(&0x2000), Some(&(true, true)));
+        assert_eq!(pages.get(&0x4000), Some(&(true, false)));
+        assert_eq!(pages.len(), 4);
+    }
+
+    #[test]
+    fn stack_layout_follows_sysv() {
+        let top = 0x8000;
+        let (rsp, image) = build_stack(top, &["/bin/x", "a"], &["K=V"], &[(AT_PAGESZ, 4096)], [7; 16]).unwrap();
+        assert_eq!(rsp % 16, 0);
+        assert_eq!(rsp + image.len() as u64, top);
+        let word = |i: usize| u64::from_le_bytes(image[i * 8..i * 8 + 8].try_into().unwrap());
+        let string = |addr: u64| {
+            let start = (addr - rsp) as usize;
+            let end = start + image[start..].iter().position(|&b| b == 0).unwrap();
+            core::str::from_utf8(&image[start..end]).unwrap()
+        };
+        assert_eq!(word(0), 2);
+        assert_eq!(string(word(1)), "/bin/x");
+        assert_eq!(string(word(2)), "a");
+        assert_eq!(word(3), 0);
+        assert_eq!(string(word(4)), "K=V");
+        assert_eq!(word(5), 0);
+        assert_eq!((word(6), word(7)), (AT_PAGESZ, 4096));
+        assert_eq!(word(8), AT_RANDOM);
+        assert_eq!(image[(word(9) - rsp) as usize], 7);
+        assert_eq!((word(10), word(11)), (AT_NULL, 0));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert!(parse(b"nope").is_err());
+        let mut header = [0u8; 64];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[4] = 1; // ELF32
+        assert_eq!(parse(&header).unwrap_err(), "só ELF64 little-endian versão 1");
+        // e_phoff perto do fim do espaço: a soma não pode dar a volta
+        header[4..7].copy_from_slice(&[2, 1, 1]);
+        header[16..20].copy_from_slice(&[2, 0, 0x3E, 0]);
+        header[32..40].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        header[54..58].copy_from_slice(&[56, 0, 1, 0]);
+        assert_eq!(parse(&header).unwrap_err(), "program headers fora do arquivo");
+    }
+}
@@ -0,0 +1,206 @@
+// src/fpu.rs
+// ====================
+// FPU/SSE - Inicialização, FXSAVE/XSAVE e troca preguiçosa (CR0.TS + #NM)
+// ====================
+//
+// Política de ponto flutuante do kernel:
+// - O kernel é compilado para x86_64-unknown-none, que é soft-float: o
+//   compilador nunca emite x87/SSE no código Rust do kernel. `f32`/`f64`
+//   (ex.: tri_motor::estimate_uniqueness) viram chamadas de compiler_builtins
+//   e só usam registradores inteiros, então são seguros em qualquer contexto,
+//   inclusive em handlers de interrupção.
+// - Os registradores x87/SSE/AVX pertencem às threads e programas. Cada dono
+//   tem um `FpuState`; a troca é preguiçosa: `switch_to` só liga CR0.TS e o
+//   #NM salva/restaura na primeira instrução de FPU do novo dono.
+// - Código do kernel que usar SIMD explícito (asm, intrinsics) precisa estar
+//   dentro de `kernel_fpu`, que salva o estado do dono atual antes.
+
+use core::arch::asm;
+use core::arch::x86_64::__cpuid_count;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use x86_64::instructions::interrupts;
+use x86_64::registers::control::{Cr0, Cr0Flags, Cr4, Cr4Flags};
+use x86_64::registers::xcontrol::{XCr0, XCr0Flags};
+use crate::cpuid::{self, Feature};
+
+const LEAF_XSAVE: u32 = 0xD;
+
+/// Área de estado: 512 bytes do FXSAVE + cabeçalho XSAVE + AVX (832 bytes).
+/// AVX-512 precisaria de ~2,7 KiB e fica desligado no XCR0.
+const AREA_SIZE: usize = 1024;
+
+// Valores do FNINIT/reset para a imagem inicial
+const FCW_DEFAULT: u16 = 0x037F;
+const MXCSR_DEFAULT: u32 = 0x1F80;
+const MXCSR_OFFSET: usize = 24;
+
+static USE_XSAVE: AtomicBool = AtomicBool::new(false);
+static XSAVE_SIZE: AtomicUsize = AtomicUsize::new(512);
+// Estado carregado nos registradores agora / estado que deveria estar
+static OWNER: AtomicPtr<FpuState> = AtomicPtr::new(ptr::null_mut());
+static CURRENT: AtomicPtr<FpuState> = AtomicPtr::new(ptr::null_mut());
+
+/// Imagem FXSAVE/XSAVE de um contexto (alinhamento de 64 exigido pelo XSAVE).
+#[repr(C, align(64))]
+pub struct FpuState {
+    area: [u8; AREA_SIZE],
+}
+
+impl FpuState {
+    /// Estado limpo, igual ao de um FNINIT com MXCSR padrão. O cabeçalho
+    /// XSAVE zerado faz o XRSTOR carregar a configuração inicial do AVX.
+    pub fn new() -> FpuState {
+        let mut area = [0u8; AREA_SIZE];
+        area[..2].copy_from_slice(&FCW_DEFAULT.to_le_bytes());
+        area[MXCSR_OFFSET..MXCSR_OFFSET + 4].copy_from_slice(&MXCSR_DEFAULT.to_le_bytes());
+        FpuState { area }
+    }
+
+    /// Salva os registradores atuais nesta área. CR0.TS precisa estar limpo.
+    pub fn save(&mut self) {
+        let area = self.area.as_mut_ptr();
+        unsafe {
+            if USE_XSAVE.load(Ordering::Relaxed) {
+                asm!("xsave64 [{}]", in(reg) area, in("eax") u32::MAX, in("edx") u32::MAX,
+                    options(nostack, preserves_flags));
+            } else {
+                asm!("fxsave64 [{}]", in(reg) area, options(nostack, preserves_flags));
+            }
+        }
+    }
+
+    /// Carrega esta área nos registradores. CR0.TS precisa estar limpo.
+    pub fn restore(&self) {
+        let area = self.area.as_ptr();
+        unsafe {
+            if USE_XSAVE.load(Ordering::Relaxed) {
+                asm!("xrstor64 [{}]", in(reg) area, in("eax") u32::MAX, in("edx") u32::MAX,
+                    options(nostack, preserves_flags));
+            } else {
+                asm!("fxrstor64 [{}]", in(reg) area, options(nostack, preserves_flags));
+            }
+        }
+    }
+}
+
+impl Default for FpuState {
+    fn default() -> Self {
+        FpuState::new()
+    }
+}
+
+fn clear_task_switched() {
+    unsafe { Cr0::update(|cr0| cr0.remove(Cr0Flags::TASK_SWITCHED)) }
+}
+
+fn set_task_switched() {
+    unsafe { Cr0::update(|cr0| cr0.insert(Cr0Flags::TASK_SWITCHED)) }
+}
+
+fn reset_registers() {
+    unsafe {
+        asm!("fninit", options(nomem, nostack, preserves_flags));
+        asm!("ldmxcsr [{}]", in(reg) &MXCSR_DEFAULT, options(nostack, preserves_flags));
+    }
+}
+
+/// Liga x87/SSE (e AVX via XSAVE, se houver) e deixa os registradores limpos.
+pub fn init() -> Result<(), &'static str> {
+    if !cpuid::has(Feature::Fpu) || !cpuid::has(Feature::Fxsr) || !cpuid::has(Feature::Sse2) {
+        return Err("CPU sem x87/FXSR/SSE2");
+    }
+    unsafe {
+        // MP: WAIT respeita o TS; sem EM (sem emulação); NE: erros via #MF
+        Cr0::update(|cr0| {
+            cr0.remove(Cr0Flags::EMULATE_COPROCESSOR | Cr0Flags::TASK_SWITCHED);
+            cr0.insert(Cr0Flags::MONITOR_COPROCESSOR | Cr0Flags::NUMERIC_ERROR);
+        });
+        Cr4::update(|cr4| cr4.insert(Cr4Flags::OSFXSR | Cr4Flags::OSXMMEXCPT_ENABLE));
+    }
+
+    if cpuid::has(Feature::Xsave) {
+        unsafe { Cr4::update(|cr4| cr4.insert(Cr4Flags::OSXSAVE)) };
+        let mut xcr0 = XCr0Flags::X87 | XCr0Flags::SSE;
+        if cpuid::has(Feature::Avx) {
+            xcr0 |= XCr0Flags::AVX;
+        }
+        unsafe { XCr0::write(xcr0) };
+        // EBX: tamanho da área para os componentes ligados no XCR0
+        let size = __cpuid_count(LEAF_XSAVE, 0).ebx as usize;
+        if size <= AREA_SIZE {
+            XSAVE_SIZE.store(size, Ordering::SeqCst);
+            USE_XSAVE.store(true, Ordering::SeqCst);
+        }
+    }
+
+    reset_registers();
+    crate::klog!("FPU: x87/SSE ligados, salvamento via {} ({} bytes)",
+        if uses_xsave() { "XSAVE" } else { "FXSAVE" }, XSAVE_SIZE.load(Ordering::Relaxed));
+    Ok(())
+}
+
+pub fn uses_xsave() -> bool {
+    USE_XSAVE.load(Ordering::Relaxed)
+}
+
+/// Troca de contexto: `state` passa a ser o dono da FPU. Não salva nada
+/// agora; só liga CR0.TS se os registradores forem de outro dono.
+pub fn switch_to(state: *mut FpuState) {
+    CURRENT.store(state, Ordering::SeqCst);
+    if OWNER.load(Ordering::SeqCst) == state {
+        clear_task_switched();
+    } else {
+        set_task_switched();
+    }
+}
+
+/// Esquece `state` (contexto destruído): os registradores não são salvos nele.
+pub fn release(state: *mut FpuState) {
+    let _ = OWNER.compare_exchange(state, ptr::null_mut(), Ordering::SeqCst, Ordering::SeqCst);
+    let _ = CURRENT.compare_exchange(state, ptr::null_mut(), Ordering::SeqCst, Ordering::SeqCst);
+}
+
+/// #NM com CR0.TS ligado: salva o dono anterior e carrega o atual.
+pub fn handle_device_not_available() {
+    clear_task_switched();
+    let owner = OWNER.load(Ordering::SeqCst);
+    let current = CURRENT.load(Ordering::SeqCst);
+    if owner == current {
+        return;
+    }
+    unsafe {
+        if let Some(owner) = owner.as_mut() {
+            owner.save();
+        }
+        match current.as_ref() {
+            Some(current) => current.restore(),
+            None => reset_registers(),
+        }
+    }
+    OWNER.store(current, Ordering::SeqCst);
+}
+
+/// Roda `f` com a FPU disponível para o kernel (SIMD explícito), inclusive
+/// em handlers de interrupção. O estado do dono é salvo antes e recarregado
+/// pelo #NM quando ele voltar a usar a FPU.
+// Ainda sem chamador: hoje nenhum código do kernel usa SIMD explícito, e o
+// primeiro que usar tem que passar por aqui
+#[allow(dead_code)]
+pub fn kernel_fpu<R>(f: impl FnOnce() -> R) -> R {
+    interrupts::without_interrupts(|| {
+        // Com ou sem TS, os registradores ainda são do OWNER (troca preguiçosa)
+        clear_task_switched();
+        if let Some(owner) = unsafe { OWNER.load(Ordering::SeqCst).as_mut() } {
+            owner.save();
+        }
+        OWNER.store(ptr::null_mut(), Ordering::SeqCst);
+        reset_registers();
+        let result = f();
+        // Os registradores agora são lixo do kernel: o próximo uso dá #NM
+        if !CURRENT.load(Ordering::SeqCst).is_null() {
+            set_task_switched();
+        }
+        result
+    })
+}
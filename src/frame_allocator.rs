@@ -0,0 +1,206 @@
+// src/frame_allocator.rs
+// ====================
+// ALOCADOR DE FRAMES FÍSICOS - Bitmap sobre o memory map do bootloader
+// ====================
+
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use x86_64::PhysAddr;
+use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame, Size4KiB};
+use spin::Mutex;
+
+pub const FRAME_SIZE: u64 = 4096;
+
+// Até 4 GiB de memória física (1 bit por frame de 4 KiB = 128 KiB de bitmap)
+const MAX_FRAMES: usize = 1 << 20;
+const BITMAP_WORDS: usize = MAX_FRAMES / 64;
+const MAX_USABLE_REGIONS: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    pub total_bytes: u64,
+    pub usable_bytes: u64,
+    pub used_bytes: u64,
+    pub free_bytes: u64,
+    pub reserved_bytes: u64,
+}
+
+/// Bit em 1 = frame ocupado (ou inexistente/reservado); bit em 0 = livre.
+pub struct BitmapFrameAllocator {
+    bitmap: [u64; BITMAP_WORDS],
+    // Faixas Usable do memory map: [início, fim) em números de frame
+    usable: [(u64, u64); MAX_USABLE_REGIONS],
+    usable_count: usize,
+    total_frames: u64,
+    usable_frames: u64,
+    used_frames: u64,
+    next_word: usize,
+}
+
+impl BitmapFrameAllocator {
+    pub const fn new() -> Self {
+        BitmapFrameAllocator {
+            bitmap: [u64::MAX; BITMAP_WORDS],
+            usable: [(0, 0); MAX_USABLE_REGIONS],
+            usable_count: 0,
+            total_frames: 0,
+            usable_frames: 0,
+            used_frames: 0,
+            next_word: 0,
+        }
+    }
+
+    /// Marca como livres apenas as regiões `Usable`; todo o resto fica reservado.
+    pub fn init(&mut self, memory_map: &MemoryMap) {
+        for region in memory_map.iter() {
+            let start = region.range.start_frame_number;
+            let end = region.range.end_frame_number.min(MAX_FRAMES as u64);
+            if region.region_type == MemoryRegionType::Empty || start >= end {
+                continue;
+            }
+            self.total_frames += end - start;
+
+            if region.region_type != MemoryRegionType::Usable {
+                continue;
+            }
+            if self.usable_count == MAX_USABLE_REGIONS {
+                crate::serial_println!("frame_allocator: regiões demais, ignorando {:#x}", region.range.start_addr());
+                continue;
+            }
+            self.usable[self.usable_count] = (start, end);
+            self.usable_count += 1;
+            for frame in start..end {
+                self.clear_bit(frame as usize);
+            }
+            self.usable_frames += end - start;
+        }
+    }
+
+    pub fn allocate(&mut self) -> Option<PhysFrame> {
+        for i in 0..BITMAP_WORDS {
+            let word_idx = (self.next_word + i) % BITMAP_WORDS;
+            let word = self.bitmap[word_idx];
+            if word == u64::MAX {
+                continue;
+            }
+            let bit = (!word).trailing_zeros() as usize;
+            let frame = word_idx * 64 + bit;
+            self.bitmap[word_idx] |= 1 << bit;
+            self.used_frames += 1;
+            self.next_word = word_idx;
+            return Some(PhysFrame::containing_address(PhysAddr::new(frame as u64 * FRAME_SIZE)));
+        }
+        None
+    }
+
+    /// Devolve um frame. Frames fora das regiões Usable ou já livres indicam
+    /// bug no chamador, então geram panic em vez de corromper o bitmap.
+    pub fn free(&mut self, frame: PhysFrame) {
+        let number = frame.start_address().as_u64() / FRAME_SIZE;
+        let is_usable = self.usable[..self.usable_count]
+            .iter()
+            .any(|&(start, end)| number >= start && number < end);
+        assert!(is_usable, "free de frame não utilizável: {:#x}", frame.start_address().as_u64());
+
+        let (word_idx, bit) = (number as usize / 64, number as usize % 64);
+        assert!(self.bitmap[word_idx] & (1 << bit) != 0, "double free do frame {:#x}", frame.start_address().as_u64());
+        self.bitmap[word_idx] &= !(1 << bit);
+        self.used_frames -= 1;
+        if word_idx < self.next_word {
+            self.next_word = word_idx;
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            total_bytes: self.total_frames * FRAME_SIZE,
+            usable_bytes: self.usable_frames * FRAME_SIZE,
+            used_bytes: self.used_frames * FRAME_SIZE,
+            free_bytes: (self.usable_frames - self.used_frames) * FRAME_SIZE,
+            reserved_bytes: (self.total_frames - self.usable_frames) * FRAME_SIZE,
+        }
+    }
+
+    fn clear_bit(&mut self, frame: usize) {
+        self.bitmap[frame / 64] &= !(1 << (frame % 64));
+    }
+}
+
+static FRAME_ALLOCATOR: Mutex<BitmapFrameAllocator> = Mutex::new(BitmapFrameAllocator::new());
+
+pub fn init(memory_map: &MemoryMap) {
+    FRAME_ALLOCATOR.lock().init(memory_map);
+}
+
+pub fn allocate_frame() -> Option<PhysFrame> {
+    FRAME_ALLOCATOR.lock().allocate()
+}
+
+pub fn free_frame(frame: PhysFrame) {
+    FRAME_ALLOCATOR.lock().free(frame);
+}
+
+pub fn stats() -> FrameStats {
+    FRAME_ALLOCATOR.lock().stats()
+}
+
+/// Handle sem estado para os traits do x86_64 (usado pelo mapeador de páginas).
+pub struct GlobalFrameAllocator;
+
+unsafe impl FrameAllocator<Size4KiB> for GlobalFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        allocate_frame()
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for GlobalFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        free_frame(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bootloader::bootinfo::{FrameRange, MemoryRegion};
+
+    fn test_map() -> MemoryMap {
+        let mut map = MemoryMap::new();
+        map.add_region(MemoryRegion {
+            range: FrameRange::new(0, 0x1000),
+            region_type: MemoryRegionType::FrameZero,
+        });
+        map.add_region(MemoryRegion {
+            range: FrameRange::new(0x1000, 0x5000),
+            region_type: MemoryRegionType::Usable,
+        });
+        map.add_region(MemoryRegion {
+            range: FrameRange::new(0x5000, 0x7000),
+            region_type: MemoryRegionType::Kernel,
+        });
+        map
+    }
+
+    #[test]
+    fn test_stats_after_init() {
+        let mut alloc = BitmapFrameAllocator::new();
+        alloc.init(&test_map());
+        let stats = alloc.stats();
+        assert_eq!(stats.total_bytes, 7 * FRAME_SIZE);
+        assert_eq!(stats.usable_bytes, 4 * FRAME_SIZE);
+        assert_eq!(stats.reserved_bytes, 3 * FRAME_SIZE);
+        assert_eq!(stats.used_bytes, 0);
+    }
+
+    #[test]
+    fn test_allocate_only_usable_and_free() {
+        let mut alloc = BitmapFrameAllocator::new();
+        alloc.init(&test_map());
+        let frames: [PhysFrame; 4] = core::array::from_fn(|_| alloc.allocate().unwrap());
+        assert_eq!(frames[0].start_address().as_u64(), 0x1000);
+        assert_eq!(frames[3].start_address().as_u64(), 0x4000);
+        assert!(alloc.allocate().is_none());
+
+        alloc.free(frames[2]);
+        assert_eq!(alloc.allocate(), Some(frames[2]));
+    }
+}
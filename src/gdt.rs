@@ -0,0 +1,105 @@
+// src/gdt.rs
+// ====================
+// GDT + TSS - Segmentos do kernel e do ring 3, pilhas IST e RSP0
+// ====================
+//
+// A ordem das entradas é a que o SYSRET exige (ver syscall::init):
+// kernel code, kernel data, user data, user code. O SYSRET carrega
+// SS = base + 8 e CS = base + 16 a partir do mesmo campo do MSR STAR.
+
+use x86_64::VirtAddr;
+use x86_64::structures::tss::TaskStateSegment;
+use x86_64::structures::gdt::{GlobalDescriptorTable, Descriptor, SegmentSelector};
+use x86_64::instructions::segmentation::{Segment, CS, SS, DS, ES};
+use x86_64::instructions::tables::load_tss;
+use x86_64::PrivilegeLevel;
+use lazy_static::lazy_static;
+
+// Índices na Interrupt Stack Table (0..7)
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+pub const NMI_IST_INDEX: u16 = 1;
+pub const MACHINE_CHECK_IST_INDEX: u16 = 2;
+
+const IST_STACK_SIZE: usize = 4096 * 5;
+
+// Pilhas "conhecidamente boas": usadas mesmo se a pilha do kernel estourar
+static mut DOUBLE_FAULT_STACK: [u8; IST_STACK_SIZE] = [0; IST_STACK_SIZE];
+static mut NMI_STACK: [u8; IST_STACK_SIZE] = [0; IST_STACK_SIZE];
+static mut MACHINE_CHECK_STACK: [u8; IST_STACK_SIZE] = [0; IST_STACK_SIZE];
+
+// A pilha cresce para baixo: a entrada da IST aponta para o topo
+fn stack_top(stack: *const [u8; IST_STACK_SIZE]) -> VirtAddr {
+    VirtAddr::from_ptr(stack) + IST_STACK_SIZE
+}
+
+// Mutável: RSP0 (pilha usada ao entrar no kernel vindo do ring 3) muda a
+// cada troca de thread. As entradas da IST são preenchidas em `init`.
+static mut TSS: TaskStateSegment = TaskStateSegment::new();
+
+struct Selectors {
+    kernel_code: SegmentSelector,
+    kernel_data: SegmentSelector,
+    user_data: SegmentSelector,
+    user_code: SegmentSelector,
+    tss: SegmentSelector,
+}
+
+lazy_static! {
+    static ref GDT: (GlobalDescriptorTable, Selectors) = {
+        let mut gdt = GlobalDescriptorTable::new();
+        let kernel_code = gdt.add_entry(Descriptor::kernel_code_segment());
+        let kernel_data = gdt.add_entry(Descriptor::kernel_data_segment());
+        let user_data = gdt.add_entry(Descriptor::user_data_segment());
+        let user_code = gdt.add_entry(Descriptor::user_code_segment());
+        let tss = gdt.add_entry(Descriptor::tss_segment(unsafe { &*core::ptr::addr_of!(TSS) }));
+        (gdt, Selectors { kernel_code, kernel_data, user_data, user_code, tss })
+    };
+}
+
+/// Carrega a GDT própria do kernel e o TSS. Deve rodar antes de `init_idt`,
+/// já que as entradas com IST dependem do TSS carregado.
+pub fn init() {
+    unsafe {
+        let tss = &mut *core::ptr::addr_of_mut!(TSS);
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] =
+            stack_top(core::ptr::addr_of!(DOUBLE_FAULT_STACK));
+        tss.interrupt_stack_table[NMI_IST_INDEX as usize] =
+            stack_top(core::ptr::addr_of!(NMI_STACK));
+        tss.interrupt_stack_table[MACHINE_CHECK_IST_INDEX as usize] =
+            stack_top(core::ptr::addr_of!(MACHINE_CHECK_STACK));
+    }
+    GDT.0.load();
+    unsafe {
+        CS::set_reg(GDT.1.kernel_code);
+        // Os seletores antigos do bootloader apontam para a GDT dele;
+        // SS inválido faria o próximo iretq gerar #GP.
+        SS::set_reg(GDT.1.kernel_data);
+        DS::set_reg(GDT.1.kernel_data);
+        ES::set_reg(GDT.1.kernel_data);
+        load_tss(GDT.1.tss);
+    }
+}
+
+pub fn kernel_code_selector() -> SegmentSelector {
+    GDT.1.kernel_code
+}
+
+pub fn kernel_data_selector() -> SegmentSelector {
+    GDT.1.kernel_data
+}
+
+/// Seletores do ring 3, já com RPL 3.
+pub fn user_code_selector() -> SegmentSelector {
+    SegmentSelector::new(GDT.1.user_code.index(), PrivilegeLevel::Ring3)
+}
+
+pub fn user_data_selector() -> SegmentSelector {
+    SegmentSelector::new(GDT.1.user_data.index(), PrivilegeLevel::Ring3)
+}
+
+/// Pilha em que a CPU entra ao receber uma interrupção ou exceção no ring 3
+/// (TSS.RSP0). O escalonador aponta para o topo da pilha da thread atual.
+pub fn set_kernel_stack(top: VirtAddr) {
+    unsafe { (*core::ptr::addr_of_mut!(TSS)).privilege_stack_table[0] = top };
+    crate::syscall::set_kernel_stack(top);
+}
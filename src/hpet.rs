@@ -0,0 +1,92 @@
+// src/hpet.rs
+// ====================
+// HPET - Contador de alta resolução (tabela ACPI "HPET", via MMIO)
+// ====================
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use x86_64::PhysAddr;
+use crate::acpi;
+use crate::memory;
+
+// Registradores (offsets no bloco MMIO de 1 KiB)
+const REG_CAPABILITIES: u64 = 0x000;
+const REG_CONFIG: u64 = 0x010;
+const REG_MAIN_COUNTER: u64 = 0x0F0;
+
+const CAP_COUNTER_64BIT: u64 = 1 << 13;
+const CONFIG_ENABLE: u64 = 1 << 0;
+
+/// Período máximo permitido pela especificação: 100 ns.
+const MAX_PERIOD_FS: u64 = 100_000_000;
+const FS_PER_NS: u128 = 1_000_000;
+
+static BASE: AtomicU64 = AtomicU64::new(0);
+static PERIOD_FS: AtomicU64 = AtomicU64::new(0);
+static COUNTER_64BIT: AtomicBool = AtomicBool::new(false);
+// Último valor estendido do contador de 32 bits (detecta a volta)
+static LAST_COUNT: AtomicU64 = AtomicU64::new(0);
+
+fn read(reg: u64) -> u64 {
+    unsafe { ptr::read_volatile((BASE.load(Ordering::Relaxed) + reg) as *const u64) }
+}
+
+fn write(reg: u64, value: u64) {
+    unsafe { ptr::write_volatile((BASE.load(Ordering::Relaxed) + reg) as *mut u64, value) }
+}
+
+/// Mapeia o HPET descrito pelo ACPI e liga o contador principal.
+pub fn init() -> Result<(), &'static str> {
+    let info = acpi::hpet().ok_or("tabela HPET não encontrada")?;
+    if info.base.address_space != acpi::GAS_SYSTEM_MEMORY {
+        return Err("HPET fora do espaço de memória");
+    }
+    let base = memory::map_mmio(PhysAddr::new(info.base.address), 1024)
+        .map_err(|_| "falha ao mapear o HPET")?;
+    BASE.store(base.as_u64(), Ordering::SeqCst);
+
+    let caps = read(REG_CAPABILITIES);
+    let period = caps >> 32;
+    if period == 0 || period > MAX_PERIOD_FS {
+        BASE.store(0, Ordering::SeqCst);
+        return Err("período do HPET inválido");
+    }
+    PERIOD_FS.store(period, Ordering::SeqCst);
+    COUNTER_64BIT.store(caps & CAP_COUNTER_64BIT != 0, Ordering::SeqCst);
+
+    // Sem o modo de roteamento legado: o PIT/APIC continua com o IRQ 0
+    write(REG_CONFIG, read(REG_CONFIG) | CONFIG_ENABLE);
+    crate::klog!("HPET: {:#x}, período {} fs ({} MHz), {} bits",
+        info.base.address, period, 1_000_000_000 / period,
+        if caps & CAP_COUNTER_64BIT != 0 { 64 } else { 32 });
+    Ok(())
+}
+
+pub fn is_available() -> bool {
+    PERIOD_FS.load(Ordering::Relaxed) != 0
+}
+
+/// Período de um tick do contador, em femtossegundos.
+pub fn period_fs() -> u64 {
+    PERIOD_FS.load(Ordering::Relaxed)
+}
+
+/// Contador principal, estendido para 64 bits se o hardware for de 32.
+pub fn counter() -> u64 {
+    if COUNTER_64BIT.load(Ordering::Relaxed) {
+        return read(REG_MAIN_COUNTER);
+    }
+    let low = read(REG_MAIN_COUNTER) & 0xFFFF_FFFF;
+    let last = LAST_COUNT.load(Ordering::Relaxed);
+    let mut count = (last & !0xFFFF_FFFF) | low;
+    if count < last {
+        count += 1 << 32;
+    }
+    // Só avança: outra leitura concorrente pode já ter visto um valor maior
+    LAST_COUNT.fetch_max(count, Ordering::Relaxed).max(count)
+}
+
+/// Nanossegundos desde que o contador foi ligado (0 sem HPET).
+pub fn nanos() -> u64 {
+    (counter() as u128 * period_fs() as u128 / FS_PER_NS) as u64
+}
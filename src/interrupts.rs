@@ -1,21 +1,33 @@
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+use x86_64::{PrivilegeLevel, VirtAddr};
+use x86_64::registers::control::Cr2;
 use pic8259::ChainedPics;
 use x86_64::instructions::port::Port;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+pub const SYSCALL_VECTOR: u8 = 0x80;
 
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard = PIC_1_OFFSET + 1,
+    Com2 = PIC_1_OFFSET + 3,
+    Com1 = PIC_1_OFFSET + 4,
+    Rtc = PIC_2_OFFSET,
+    Mouse = PIC_2_OFFSET + 4,
 }
 
 static mut IDT: InterruptDescriptorTable = InterruptDescriptorTable::new();
 
+// false = PIC 8259 legado; true = LAPIC/I/O APIC (ver init_apic)
+static USE_APIC: AtomicBool = AtomicBool::new(false);
+
 lazy_static! {
     static ref PICS: Mutex<ChainedPics> = {
         Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) })
@@ -23,43 +35,277 @@ lazy_static! {
 }
 
 pub fn init_idt() {
+    let idt: &'static mut InterruptDescriptorTable = unsafe { &mut *core::ptr::addr_of_mut!(IDT) };
+    idt.divide_error.set_handler_fn(divide_error_handler);
+    idt.debug.set_handler_fn(debug_handler);
+    unsafe {
+        idt.non_maskable_interrupt.set_handler_fn(nmi_handler)
+            .set_stack_index(crate::gdt::NMI_IST_INDEX);
+    }
+    idt.breakpoint.set_handler_fn(breakpoint_handler);
+    idt.overflow.set_handler_fn(overflow_handler);
+    idt.bound_range_exceeded.set_handler_fn(bound_range_handler);
+    idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+    idt.device_not_available.set_handler_fn(device_not_available_handler);
+    unsafe {
+        idt.double_fault.set_handler_fn(double_fault_handler)
+            .set_stack_index(crate::gdt::DOUBLE_FAULT_IST_INDEX);
+    }
+    idt.invalid_tss.set_handler_fn(invalid_tss_handler);
+    idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+    idt.stack_segment_fault.set_handler_fn(stack_segment_handler);
+    idt.general_protection_fault.set_handler_fn(general_protection_handler);
+    idt.page_fault.set_handler_fn(page_fault_handler);
+    idt.x87_floating_point.set_handler_fn(x87_floating_point_handler);
+    idt.alignment_check.set_handler_fn(alignment_check_handler);
     unsafe {
-        IDT[InterruptIndex::Timer as usize].set_handler_fn(timer_handler);
-        IDT[InterruptIndex::Keyboard as usize].set_handler_fn(keyboard_handler);
-        IDT.load();
+        idt.machine_check.set_handler_fn(machine_check_handler)
+            .set_stack_index(crate::gdt::MACHINE_CHECK_IST_INDEX);
+    }
+    idt.simd_floating_point.set_handler_fn(simd_floating_point_handler);
+    idt.virtualization.set_handler_fn(virtualization_handler);
+    idt.cp_protection_exception.set_handler_fn(control_protection_handler);
+    idt.hv_injection_exception.set_handler_fn(hv_injection_handler);
+    idt.vmm_communication_exception.set_handler_fn(vmm_communication_handler);
+    idt.security_exception.set_handler_fn(security_handler);
+    idt[InterruptIndex::Timer as usize].set_handler_fn(timer_handler);
+    idt[InterruptIndex::Keyboard as usize].set_handler_fn(keyboard_handler);
+    idt[InterruptIndex::Com2 as usize].set_handler_fn(com2_handler);
+    idt[InterruptIndex::Com1 as usize].set_handler_fn(com1_handler);
+    idt[InterruptIndex::Rtc as usize].set_handler_fn(rtc_handler);
+    idt[InterruptIndex::Mouse as usize].set_handler_fn(mouse_handler);
+    idt[crate::apic::ERROR_VECTOR as usize].set_handler_fn(apic_error_handler);
+    idt[crate::apic::SPURIOUS_VECTOR as usize].set_handler_fn(spurious_handler);
+    // Syscall legada: chamável do ring 3
+    unsafe {
+        idt[SYSCALL_VECTOR as usize].set_handler_addr(crate::syscall::int80_entry())
+            .set_privilege_level(PrivilegeLevel::Ring3);
+    }
+    idt.load();
+}
+
+// --- Exceções da CPU (vetores 0-31) ---
+// Vetores 9, 15, 22-27 e 31 são reservados pela arquitetura (o x86_64 nem expõe
+// essas entradas); todos os outros caem aqui, imprimem o dump e param a CPU.
+// #DB e #BP são traps: só reportam e retornam.
+
+/// Escreve uma linha de diagnóstico na serial e no VGA.
+fn dump_line(args: core::fmt::Arguments) {
+    crate::serial::print(args);
+    crate::serial::print(format_args!("\n"));
+    let mut writer = crate::vga::get_writer().lock();
+    let _ = writer.write_fmt(args);
+    let _ = writer.write_str("\n");
+}
+
+fn dump_exception(
+    vector: u8,
+    name: &str,
+    error_code: Option<u64>,
+    stack_frame: &InterruptStackFrame,
+) {
+    // O shell segura o WRITER enquanto roda; se a exceção veio de dentro dele,
+    // o lock nunca seria liberado.
+    unsafe { crate::vga::force_unlock(); }
+
+    dump_line(format_args!("*** EXCEÇÃO {} (vetor {}) ***", name, vector));
+    if let Some(code) = error_code {
+        dump_line(format_args!("  error code: {:#x}", code));
+    }
+    dump_line(format_args!("  RIP:    {:#018x}", stack_frame.instruction_pointer.as_u64()));
+    dump_line(format_args!("  CS:     {:#06x}", stack_frame.code_segment));
+    dump_line(format_args!("  RFLAGS: {:#018x}", stack_frame.cpu_flags));
+    dump_line(format_args!("  RSP:    {:#018x}", stack_frame.stack_pointer.as_u64()));
+    dump_line(format_args!("  SS:     {:#06x}", stack_frame.stack_segment));
+}
+
+// Exceção causada por código do ring 3: derruba só o processo culpado.
+// `detail` entra na mesma linha do relatório (ex.: CR2 do #PF). Devolve
+// `true` se o handler deve só retornar (o iretq já leva à saída).
+fn kill_if_user(name: &str, stack_frame: &mut InterruptStackFrame, detail: Option<core::fmt::Arguments>) -> bool {
+    if stack_frame.code_segment & 3 != 3 {
+        return false;
+    }
+    let rip = stack_frame.instruction_pointer.as_u64();
+    match detail {
+        Some(detail) => dump_line(format_args!("{} no ring 3 (RIP {:#x}, {}), thread encerrada", name, rip, detail)),
+        None => dump_line(format_args!("{} no ring 3 (RIP {:#x}), thread encerrada", name, rip)),
     }
+    crate::process::mark_killed(crate::process::SIGSEGV);
+    exit_on_return(stack_frame);
+    true
 }
 
-extern "x86-interrupt" fn timer_handler(_stack_frame: InterruptStackFrame) {
-    // Correção: Unsafe pro notify (função unsafe)
+// `process::exit` pega locks, acorda quem espera e troca de thread: não roda
+// dentro de handler. Em vez de voltar ao ring 3, o iretq cai em
+// `exit_trampoline` no ring 0, com interrupções ligadas, usando a pilha de
+// kernel da própria thread (o frame fica no topo dela, vindo do RSP0 da TSS).
+fn exit_on_return(stack_frame: &mut InterruptStackFrame) {
+    let frame_end = stack_frame as *mut InterruptStackFrame as u64
+        + core::mem::size_of::<InterruptStackFrame>() as u64;
+    // RSP % 16 == 8 na entrada, como depois de um `call`
+    let stack_top = (frame_end & !0xF) - 8;
     unsafe {
-        PICS.lock().notify_end_of_interrupt(InterruptIndex::Timer as u8);
+        stack_frame.as_mut().update(|frame| {
+            frame.instruction_pointer = VirtAddr::new(exit_trampoline as *const () as u64);
+            frame.code_segment = u64::from(crate::gdt::kernel_code_selector().0);
+            frame.cpu_flags = 0x202; // IF + bit 1 reservado
+            frame.stack_pointer = VirtAddr::new(stack_top);
+            frame.stack_segment = u64::from(crate::gdt::kernel_data_selector().0);
+        });
+    }
+}
+
+extern "C" fn exit_trampoline() -> ! {
+    crate::process::exit_killed()
+}
+
+fn halt_forever() -> ! {
+    x86_64::instructions::interrupts::disable();
+    dump_line(format_args!("Sistema parado."));
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+macro_rules! fatal_exception {
+    ($fn_name:ident, $vector:expr, $name:expr) => {
+        extern "x86-interrupt" fn $fn_name(mut stack_frame: InterruptStackFrame) {
+            if kill_if_user($name, &mut stack_frame, None) {
+                return;
+            }
+            dump_exception($vector, $name, None, &stack_frame);
+            halt_forever();
+        }
+    };
+    ($fn_name:ident, $vector:expr, $name:expr, error_code) => {
+        extern "x86-interrupt" fn $fn_name(mut stack_frame: InterruptStackFrame, error_code: u64) {
+            if kill_if_user($name, &mut stack_frame, None) {
+                return;
+            }
+            dump_exception($vector, $name, Some(error_code), &stack_frame);
+            halt_forever();
+        }
+    };
+}
+
+fatal_exception!(divide_error_handler, 0, "#DE Divide Error");
+fatal_exception!(overflow_handler, 4, "#OF Overflow");
+fatal_exception!(bound_range_handler, 5, "#BR Bound Range Exceeded");
+fatal_exception!(invalid_opcode_handler, 6, "#UD Invalid Opcode");
+fatal_exception!(invalid_tss_handler, 10, "#TS Invalid TSS", error_code);
+fatal_exception!(segment_not_present_handler, 11, "#NP Segment Not Present", error_code);
+fatal_exception!(stack_segment_handler, 12, "#SS Stack-Segment Fault", error_code);
+fatal_exception!(general_protection_handler, 13, "#GP General Protection Fault", error_code);
+fatal_exception!(x87_floating_point_handler, 16, "#MF x87 Floating-Point");
+fatal_exception!(alignment_check_handler, 17, "#AC Alignment Check", error_code);
+fatal_exception!(simd_floating_point_handler, 19, "#XM SIMD Floating-Point");
+fatal_exception!(virtualization_handler, 20, "#VE Virtualization");
+fatal_exception!(control_protection_handler, 21, "#CP Control Protection", error_code);
+fatal_exception!(hv_injection_handler, 28, "#HV Hypervisor Injection");
+fatal_exception!(vmm_communication_handler, 29, "#VC VMM Communication", error_code);
+fatal_exception!(security_handler, 30, "#SX Security Exception", error_code);
+
+// NMI vem do hardware (erro de memória, watchdog), não do programa que
+// estava rodando: nada de `kill_if_user`, sempre dump e parada
+extern "x86-interrupt" fn nmi_handler(stack_frame: InterruptStackFrame) {
+    dump_exception(2, "NMI Non-Maskable Interrupt", None, &stack_frame);
+    halt_forever();
+}
+
+extern "x86-interrupt" fn debug_handler(stack_frame: InterruptStackFrame) {
+    dump_exception(1, "#DB Debug", None, &stack_frame);
+}
+
+extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    dump_exception(3, "#BP Breakpoint", None, &stack_frame);
+}
+
+// CR0.TS ligado pela troca preguiçosa da FPU: carrega o estado do dono atual
+extern "x86-interrupt" fn device_not_available_handler(_stack_frame: InterruptStackFrame) {
+    crate::fpu::handle_device_not_available();
+}
+
+extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame, error_code: u64) -> ! {
+    dump_exception(8, "#DF Double Fault", Some(error_code), &stack_frame);
+    halt_forever();
+}
+
+extern "x86-interrupt" fn page_fault_handler(mut stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
+    let cr2 = Cr2::read().as_u64();
+    if kill_if_user("#PF Page Fault", &mut stack_frame, Some(format_args!("CR2 {:#x} {:?}", cr2, error_code))) {
+        return;
+    }
+    dump_exception(14, "#PF Page Fault", Some(error_code.bits()), &stack_frame);
+    dump_line(format_args!("  CR2:    {:#018x} ({:?})", cr2, error_code));
+    halt_forever();
+}
+
+extern "x86-interrupt" fn machine_check_handler(stack_frame: InterruptStackFrame) -> ! {
+    dump_exception(18, "#MC Machine Check", None, &stack_frame);
+    halt_forever();
+}
+
+extern "x86-interrupt" fn timer_handler(mut stack_frame: InterruptStackFrame) {
+    crate::timer::tick();
+
+    end_of_interrupt(InterruptIndex::Timer);
+    // Pode trocar de thread: esta volta pelo iretq quando for escalonada
+    crate::scheduler::on_tick();
+    // Processo em laço no ring 3 também precisa atender o kill: a saída
+    // acontece depois do iretq, fora do contexto de IRQ
+    if stack_frame.code_segment & 3 == 3 && crate::process::is_killed() {
+        exit_on_return(&mut stack_frame);
     }
 }
 
 extern "x86-interrupt" fn keyboard_handler(_stack_frame: InterruptStackFrame) {
     let mut port = Port::<u8>::new(0x60);
     let scancode = unsafe { port.read() };
+    crate::keyboard::add_scancode(scancode);
 
-    // Debug print
-    crate::serial_print("Scancode: 0x");
-    print_hex(scancode);
-    crate::serial_println!("");
+    end_of_interrupt(InterruptIndex::Keyboard);
+}
 
-    // Add to buffer
-    crate::keyboard::add_scancode(scancode);
+extern "x86-interrupt" fn mouse_handler(_stack_frame: InterruptStackFrame) {
+    let mut port = Port::<u8>::new(0x60);
+    let byte = unsafe { port.read() };
+    crate::mouse::add_byte(byte);
+    end_of_interrupt(InterruptIndex::Mouse);
+}
 
-    // Correção: Unsafe pro notify
-    unsafe {
-        PICS.lock().notify_end_of_interrupt(InterruptIndex::Keyboard as u8);
-    }
+// COM1/COM3 na IRQ 4, COM2/COM4 na IRQ 3
+extern "x86-interrupt" fn com1_handler(_stack_frame: InterruptStackFrame) {
+    crate::serial::handle_interrupt(4);
+    end_of_interrupt(InterruptIndex::Com1);
 }
 
-fn print_hex(byte: u8) {
-    let nibbles = [byte >> 4, byte & 0xF];
-    for nib in nibbles {
-        let c = if nib < 10 { b'0' + nib } else { b'A' + (nib - 10) };
-        crate::serial_putc(c);
+extern "x86-interrupt" fn com2_handler(_stack_frame: InterruptStackFrame) {
+    crate::serial::handle_interrupt(3);
+    end_of_interrupt(InterruptIndex::Com2);
+}
+
+extern "x86-interrupt" fn rtc_handler(_stack_frame: InterruptStackFrame) {
+    crate::rtc::handle_interrupt();
+    end_of_interrupt(InterruptIndex::Rtc);
+}
+
+extern "x86-interrupt" fn apic_error_handler(_stack_frame: InterruptStackFrame) {
+    crate::serial_println!("APIC: interrupção de erro");
+    crate::apic::eoi();
+}
+
+// Spurious do LAPIC: não recebe EOI
+extern "x86-interrupt" fn spurious_handler(_stack_frame: InterruptStackFrame) {}
+
+/// EOI no controlador ativo.
+pub fn end_of_interrupt(index: InterruptIndex) {
+    if USE_APIC.load(Ordering::Relaxed) {
+        crate::apic::eoi();
+    } else {
+        unsafe {
+            PICS.lock().notify_end_of_interrupt(index as u8);
+        }
     }
 }
 
@@ -69,3 +315,55 @@ pub fn init_pics() {
         PICS.lock().initialize();
     }
 }
+
+/// Desmascara uma linha de IRQ (0-15) nos PICs; as do escravo também
+/// precisam da cascata (IRQ 2) liberada no mestre. Com APIC, a IRQ é
+/// roteada no I/O APIC para o mesmo vetor (PIC_1_OFFSET + irq).
+pub fn unmask_irq(irq: u8) {
+    if USE_APIC.load(Ordering::Relaxed) {
+        crate::apic::route_irq(irq, PIC_1_OFFSET + irq);
+        return;
+    }
+    let mut pics = PICS.lock();
+    unsafe {
+        let [mut master, mut slave] = pics.read_masks();
+        if irq < 8 {
+            master &= !(1 << irq);
+        } else {
+            slave &= !(1 << (irq - 8));
+            master &= !(1 << 2);
+        }
+        pics.write_masks(master, slave);
+    }
+}
+
+/// Troca o par de 8259 pelo LAPIC + I/O APIC. O timer do LAPIC é calibrado
+/// contra o PIT (que precisa estar rodando com interrupções ligadas) e passa
+/// a gerar o tick no vetor do Timer; as IRQs já liberadas no PIC são
+/// roteadas no I/O APIC. Em caso de erro o PIC continua no comando.
+pub fn init_apic() -> Result<(), &'static str> {
+    let madt = crate::acpi::madt().ok_or("MADT não encontrado")?;
+    crate::apic::init(&madt)?;
+
+    const CALIBRATION_TICKS: u64 = 50;
+    let counts_per_tick = crate::apic::calibrate_timer(CALIBRATION_TICKS);
+    if counts_per_tick == 0 {
+        return Err("calibração do timer do LAPIC falhou");
+    }
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let [master, slave] = unsafe { PICS.lock().read_masks() };
+        unsafe { PICS.lock().disable(); }
+        USE_APIC.store(true, Ordering::SeqCst);
+
+        // IRQ 0 (PIT) vira o timer do LAPIC; IRQ 2 é só a cascata
+        let masks = (slave as u16) << 8 | master as u16;
+        for irq in (1..16).filter(|&irq| irq != 2 && masks & (1 << irq) == 0) {
+            crate::apic::route_irq(irq, PIC_1_OFFSET + irq);
+        }
+        crate::apic::start_timer(InterruptIndex::Timer as u8, counts_per_tick);
+    });
+    crate::klog!("APIC: LAPIC id {}, {} I/O APIC(s), timer {} ciclos/tick",
+        crate::apic::lapic_id(), madt.io_apics.len(), counts_per_tick);
+    Ok(())
+}
@@ -1,56 +1,804 @@
-use x86_64::instructions::port::Port;
-use core::sync::atomic::{AtomicUsize, Ordering};
-
-// Tabela ASCII com exatos 128 elementos (mapeados + zeros padded corretamente)
-static SC_ASCII: [u8; 128] = [
-    0,  27, '1' as u8, '2' as u8, '3' as u8, '4' as u8, '5' as u8, '6' as u8, '7' as u8, '8' as u8, '9' as u8, '0' as u8, '-' as u8, '=' as u8, 8,  // 0-14 (15 elems)
-    9, 'q' as u8, 'w' as u8, 'e' as u8, 'r' as u8, 't' as u8, 'y' as u8, 'u' as u8, 'i' as u8, 'o' as u8, 'p' as u8, '[' as u8, ']' as u8, 10, 0,  // 15-29 (15 elems, total 30)
-    'a' as u8, 's' as u8, 'd' as u8, 'f' as u8, 'g' as u8, 'h' as u8, 'j' as u8, 'k' as u8, 'l' as u8, ';' as u8, '\'' as u8, '`' as u8, 0, 92,  // 30-43 (14 elems, total 44)
-    'z' as u8, 'x' as u8, 'c' as u8, 'v' as u8, 'b' as u8, 'n' as u8, 'm' as u8, ',' as u8, '.' as u8, '/' as u8, 0, '*' as u8, 0, ' ' as u8, 0,  // 44-58 (15 elems, total 59)
-    // Padding: 69 zeros pra fechar 128 (59 + 69 = 128)
-    0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,  // 32 zeros (total 91)
-    0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,  // 32 zeros (total 123)
-    0,0,0,0,0  // +2 zeros finais (total 125? Espera, ajustei: na verdade, com contagem precisa, são 69)
-];
-
-// Buffer circular
-const BUFFER_SIZE: usize = 128;
-static mut KEYBOARD_BUFFER: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
-static HEAD: AtomicUsize = AtomicUsize::new(0);
-static TAIL: AtomicUsize = AtomicUsize::new(0);
+// src/keyboard.rs
+// ====================
+// TECLADO PS/2 - Decodificador dos scancode sets 1 e 2 (prefixo 0xE0, Pause
+// e soltura de teclas), modificadores, LEDs e filas de bytes e de eventos
+// ====================
+//
+// O `init` passa o controlador pelo `ps2::init`, reseta o teclado e escolhe
+// o set: por padrão o teclado fala o set 2 e o 8042 traduz para o set 1;
+// com `set scancodes=2` no tri-shellrc a tradução sai e o set 2 chega cru.
+//
+// O IRQ 1 entrega um byte por vez em `add_scancode`. O decodificador junta
+// a sequência em (tecla, pressionada?), os modificadores viram estado e o
+// evento resultante vai para duas filas:
+// - eventos (`next_event`/`wait_event`): toda tecla, inclusive setas, F1..F12
+//   e soltura;
+// - bytes (`get_key`/`wait_key`): só o que tem caractere, para o shell.
+//
+// Os LEDs seguem Caps/Num/Scroll Lock com o comando 0xED do teclado; os ACKs
+// chegam pelo próprio IRQ 1 e são consumidos antes do decodificador.
+
+use crate::keymap::{self, Layout, Symbol};
+use crate::ps2::{self, Channel};
+use crate::ring::Ring;
+use crate::sync::{IrqSpinlock, WaitQueue};
+
+/// Tecla física, independente de layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCode {
+    Escape, Backspace, Tab, Enter, Space,
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M,
+    N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Minus, Equals, LeftBracket, RightBracket, Semicolon, Quote, Backquote,
+    Backslash, Comma, Period, Slash,
+    /// Tecla extra dos teclados ISO, ao lado do Shift esquerdo
+    IntlBackslash,
+    /// Tecla extra do ABNT2, ao lado do Shift direito
+    IntlRo,
+    LeftShift, RightShift, LeftCtrl, RightCtrl, LeftAlt, RightAlt,
+    LeftMeta, RightMeta, Menu,
+    CapsLock, NumLock, ScrollLock,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Up, Down, Left, Right, Home, End, PageUp, PageDown, Insert, Delete,
+    PrintScreen, Pause,
+    Keypad0, Keypad1, Keypad2, Keypad3, Keypad4,
+    Keypad5, Keypad6, Keypad7, Keypad8, Keypad9,
+    KeypadPeriod, KeypadEnter, KeypadPlus, KeypadMinus, KeypadStar, KeypadSlash,
+    /// Ponto do teclado numérico do ABNT2
+    KeypadComma,
+}
+
+// Quantidade de variantes de `KeyCode` (cabe no bitmap de teclas presas)
+const KEY_COUNT: usize = KeyCode::KeypadComma as usize + 1;
+
+/// Scancodes do set 1 sem prefixo (só o código de make, bit 7 limpo).
+fn set1_key(code: u8) -> Option<KeyCode> {
+    use KeyCode::*;
+    const ROWS: [(u8, &[KeyCode]); 4] = [
+        (0x01, &[Escape, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, Minus, Equals, Backspace,
+                 Tab, Q, W, E, R, T, Y, U, I, O, P, LeftBracket, RightBracket, Enter, LeftCtrl,
+                 A, S, D, F, G, H, J, K, L, Semicolon, Quote, Backquote, LeftShift, Backslash,
+                 Z, X, C, V, B, N, M, Comma, Period, Slash, RightShift, KeypadStar, LeftAlt, Space, CapsLock,
+                 F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, NumLock, ScrollLock,
+                 Keypad7, Keypad8, Keypad9, KeypadMinus, Keypad4, Keypad5, Keypad6, KeypadPlus,
+                 Keypad1, Keypad2, Keypad3, Keypad0, KeypadPeriod]),
+        (0x56, &[IntlBackslash, F11, F12]),
+        (0x73, &[IntlRo]),
+        (0x7E, &[KeypadComma]),
+    ];
+    ROWS.iter().find_map(|&(first, keys)| {
+        code.checked_sub(first).and_then(|i| keys.get(i as usize)).copied()
+    })
+}
+
+/// Scancodes depois do prefixo 0xE0. Os "shifts falsos" (E0 2A, E0 36) que
+/// alguns teclados mandam em volta de PrintScreen e das setas ficam de fora.
+fn set1_extended_key(code: u8) -> Option<KeyCode> {
+    use KeyCode::*;
+    Some(match code {
+        0x1C => KeypadEnter,
+        0x1D => RightCtrl,
+        0x35 => KeypadSlash,
+        0x37 => PrintScreen,
+        0x38 => RightAlt,
+        0x47 => Home,
+        0x48 => Up,
+        0x49 => PageUp,
+        0x4B => Left,
+        0x4D => Right,
+        0x4F => End,
+        0x50 => Down,
+        0x51 => PageDown,
+        0x52 => Insert,
+        0x53 => Delete,
+        0x5B => LeftMeta,
+        0x5C => RightMeta,
+        0x5D => Menu,
+        _ => return None,
+    })
+}
+
+/// Scancodes do set 2 sem prefixo (o código de make, depois do 0xF0 na
+/// soltura).
+fn set2_key(code: u8) -> Option<KeyCode> {
+    use KeyCode::*;
+    Some(match code {
+        0x01 => F9,
+        0x03 => F5,
+        0x04 => F3,
+        0x05 => F1,
+        0x06 => F2,
+        0x07 => F12,
+        0x09 => F10,
+        0x0A => F8,
+        0x0B => F6,
+        0x0C => F4,
+        0x0D => Tab,
+        0x0E => Backquote,
+        0x11 => LeftAlt,
+        0x12 => LeftShift,
+        0x14 => LeftCtrl,
+        0x15 => Q,
+        0x16 => Key1,
+        0x1A => Z,
+        0x1B => S,
+        0x1C => A,
+        0x1D => W,
+        0x1E => Key2,
+        0x21 => C,
+        0x22 => X,
+        0x23 => D,
+        0x24 => E,
+        0x25 => Key4,
+        0x26 => Key3,
+        0x29 => Space,
+        0x2A => V,
+        0x2B => F,
+        0x2C => T,
+        0x2D => R,
+        0x2E => Key5,
+        0x31 => N,
+        0x32 => B,
+        0x33 => H,
+        0x34 => G,
+        0x35 => Y,
+        0x36 => Key6,
+        0x3A => M,
+        0x3B => J,
+        0x3C => U,
+        0x3D => Key7,
+        0x3E => Key8,
+        0x41 => Comma,
+        0x42 => K,
+        0x43 => I,
+        0x44 => O,
+        0x45 => Key0,
+        0x46 => Key9,
+        0x49 => Period,
+        0x4A => Slash,
+        0x4B => L,
+        0x4C => Semicolon,
+        0x4D => P,
+        0x4E => Minus,
+        0x51 => IntlRo,
+        0x52 => Quote,
+        0x54 => LeftBracket,
+        0x55 => Equals,
+        0x58 => CapsLock,
+        0x59 => RightShift,
+        0x5A => Enter,
+        0x5B => RightBracket,
+        0x5D => Backslash,
+        0x61 => IntlBackslash,
+        0x66 => Backspace,
+        0x69 => Keypad1,
+        0x6B => Keypad4,
+        0x6C => Keypad7,
+        0x6D => KeypadComma,
+        0x70 => Keypad0,
+        0x71 => KeypadPeriod,
+        0x72 => Keypad2,
+        0x73 => Keypad5,
+        0x74 => Keypad6,
+        0x75 => Keypad8,
+        0x76 => Escape,
+        0x77 => NumLock,
+        0x78 => F11,
+        0x79 => KeypadPlus,
+        0x7A => Keypad3,
+        0x7B => KeypadMinus,
+        0x7C => KeypadStar,
+        0x7D => Keypad9,
+        0x7E => ScrollLock,
+        0x83 => F7,
+        _ => return None,
+    })
+}
+
+/// Scancodes do set 2 depois do prefixo 0xE0 (os shifts falsos E0 12 e
+/// E0 59 ficam de fora, como no set 1).
+fn set2_extended_key(code: u8) -> Option<KeyCode> {
+    use KeyCode::*;
+    Some(match code {
+        0x11 => RightAlt,
+        0x14 => RightCtrl,
+        0x1F => LeftMeta,
+        0x27 => RightMeta,
+        0x2F => Menu,
+        0x4A => KeypadSlash,
+        0x5A => KeypadEnter,
+        0x69 => End,
+        0x6B => Left,
+        0x6C => Home,
+        0x70 => Insert,
+        0x71 => Delete,
+        0x72 => Down,
+        0x74 => Right,
+        0x75 => Up,
+        0x7A => PageDown,
+        0x7C => PrintScreen,
+        0x7D => PageUp,
+        _ => return None,
+    })
+}
+
+// --- Decodificador ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ScancodeSet {
+    Set1 = 1,
+    Set2 = 2,
+}
+
+/// Máquina de estados dos scancode sets 1 e 2.
+pub struct Decoder {
+    set: ScancodeSet,
+    /// Depois de 0xE0
+    extended: bool,
+    /// Depois de 0xF0 (soltura no set 2)
+    release: bool,
+    /// Bytes que faltam da sequência do Pause
+    skip: u8,
+}
+
+impl Decoder {
+    pub const fn new(set: ScancodeSet) -> Self {
+        Decoder { set, extended: false, release: false, skip: 0 }
+    }
+
+    /// Consome um byte; devolve (tecla, pressionada?) quando a sequência fecha.
+    pub fn feed(&mut self, byte: u8) -> Option<(KeyCode, bool)> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        match (self.set, byte) {
+            (_, 0xE0) => {
+                self.extended = true;
+                return None;
+            }
+            // Pause não tem código de soltura: a sequência inteira é um toque
+            // (E1 1D 45 E1 9D C5 no set 1, E1 14 77 E1 F0 14 F0 77 no set 2)
+            (set, 0xE1) => {
+                self.skip = if set == ScancodeSet::Set1 { 5 } else { 7 };
+                self.extended = false;
+                self.release = false;
+                return Some((KeyCode::Pause, true));
+            }
+            (ScancodeSet::Set2, 0xF0) => {
+                self.release = true;
+                return None;
+            }
+            _ => {}
+        }
+        let extended = core::mem::take(&mut self.extended);
+        let release = core::mem::take(&mut self.release);
+        let (key, pressed) = match (self.set, extended) {
+            (ScancodeSet::Set1, false) => (set1_key(byte & 0x7F), byte & 0x80 == 0),
+            (ScancodeSet::Set1, true) => (set1_extended_key(byte & 0x7F), byte & 0x80 == 0),
+            (ScancodeSet::Set2, false) => (set2_key(byte), !release),
+            (ScancodeSet::Set2, true) => (set2_extended_key(byte), !release),
+        };
+        key.map(|key| (key, pressed))
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new(ScancodeSet::Set1)
+    }
+}
+
+// --- Modificadores e eventos ---
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub left_alt: bool,
+    pub right_alt: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.left_ctrl || self.right_ctrl
+    }
+
+    pub fn alt(&self) -> bool {
+        self.left_alt || self.right_alt
+    }
+
+    /// Byte do comando 0xED: bit 0 Scroll, bit 1 Num, bit 2 Caps.
+    pub fn leds(&self) -> u8 {
+        u8::from(self.scroll_lock) | u8::from(self.num_lock) << 1 | u8::from(self.caps_lock) << 2
+    }
+
+    fn update(&mut self, key: KeyCode, pressed: bool, repeat: bool) {
+        match key {
+            KeyCode::LeftShift => self.left_shift = pressed,
+            KeyCode::RightShift => self.right_shift = pressed,
+            KeyCode::LeftCtrl => self.left_ctrl = pressed,
+            KeyCode::RightCtrl => self.right_ctrl = pressed,
+            KeyCode::LeftAlt => self.left_alt = pressed,
+            KeyCode::RightAlt => self.right_alt = pressed,
+            // As travas só trocam no primeiro make, não no autorepeat
+            KeyCode::CapsLock if pressed && !repeat => self.caps_lock = !self.caps_lock,
+            KeyCode::NumLock if pressed && !repeat => self.num_lock = !self.num_lock,
+            KeyCode::ScrollLock if pressed && !repeat => self.scroll_lock = !self.scroll_lock,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: KeyCode,
+    pub pressed: bool,
+    /// Make repetido pelo autorepeat com a tecla ainda presa
+    pub repeat: bool,
+    /// Modificadores já atualizados por este evento
+    pub modifiers: Modifiers,
+    /// Caractere no layout atual (só em pressionamentos), já composto com
+    /// uma tecla morta pendente
+    pub ch: Option<char>,
+    /// Acento de uma tecla morta que não compôs com `ch`; vem antes dele
+    pub accent: Option<char>,
+}
+
+impl KeyEvent {
+    /// O que este evento põe na fila de bytes: o texto em UTF-8 (acento
+    /// pendente + caractere), ou o código de controle (Ctrl+C = 3) com Ctrl
+    /// preso.
+    pub fn encode<'a>(&self, buf: &'a mut [u8; 8]) -> &'a [u8] {
+        if !self.pressed {
+            return &[];
+        }
+        if let Some(ch) = self.ch.filter(|ch| self.modifiers.ctrl() && ch.is_ascii_alphabetic()) {
+            buf[0] = ch.to_ascii_uppercase() as u8 & 0x1F;
+            return &buf[..1];
+        }
+        let mut len = 0;
+        for ch in self.accent.into_iter().chain(self.ch) {
+            len += ch.encode_utf8(&mut buf[len..]).len();
+        }
+        &buf[..len]
+    }
+}
+
+/// Decodificador + modificadores + teclas presas + layout: dos bytes do IRQ
+/// aos eventos, sem tocar em hardware.
+pub struct KeyboardState {
+    decoder: Decoder,
+    modifiers: Modifiers,
+    held: [u64; KEY_COUNT.div_ceil(64)],
+    layout: &'static Layout,
+    /// Acento da tecla morta esperando a próxima tecla
+    dead: Option<char>,
+}
+
+impl KeyboardState {
+    pub const fn new() -> Self {
+        KeyboardState {
+            decoder: Decoder::new(ScancodeSet::Set1),
+            modifiers: Modifiers {
+                left_shift: false,
+                right_shift: false,
+                left_ctrl: false,
+                right_ctrl: false,
+                left_alt: false,
+                right_alt: false,
+                caps_lock: false,
+                num_lock: false,
+                scroll_lock: false,
+            },
+            held: [0; KEY_COUNT.div_ceil(64)],
+            layout: &keymap::US,
+            dead: None,
+        }
+    }
+
+    /// Troca o decodificador (descarta uma sequência pela metade).
+    pub fn set_scancode_set(&mut self, set: ScancodeSet) {
+        self.decoder = Decoder::new(set);
+    }
+
+    pub fn set_layout(&mut self, layout: &'static Layout) {
+        self.layout = layout;
+        self.dead = None;
+    }
+
+    pub fn is_pressed(&self, key: KeyCode) -> bool {
+        let i = key as usize;
+        self.held[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn process(&mut self, byte: u8) -> Option<KeyEvent> {
+        let (key, pressed) = self.decoder.feed(byte)?;
+        let repeat = pressed && self.is_pressed(key);
+        let (word, bit) = (key as usize / 64, 1u64 << (key as usize % 64));
+        if pressed {
+            self.held[word] |= bit;
+        } else {
+            self.held[word] &= !bit;
+        }
+        self.modifiers.update(key, pressed, repeat);
+        let (accent, ch) = if pressed { self.translate(key) } else { (None, None) };
+        Some(KeyEvent { key, pressed, repeat, modifiers: self.modifiers, ch, accent })
+    }
+
+    /// (acento que não compôs, caractere) de um pressionamento.
+    fn translate(&mut self, key: KeyCode) -> (Option<char>, Option<char>) {
+        match self.layout.symbol(key, &self.modifiers) {
+            // Shift/AltGr entre a tecla morta e a letra não cancelam nada
+            None => (None, None),
+            Some(Symbol::Dead(accent)) => match self.dead.replace(accent) {
+                None => (None, None),
+                // A mesma tecla morta duas vezes dá o acento isolado
+                Some(pending) if pending == accent => {
+                    self.dead = None;
+                    (None, Some(accent))
+                }
+                Some(pending) => (None, Some(pending)),
+            },
+            Some(Symbol::Char(ch)) => match self.dead.take() {
+                None => (None, Some(ch)),
+                Some(accent) if ch == ' ' => (None, Some(accent)),
+                // Enter, Backspace, Esc... só descartam o acento
+                Some(_) if ch.is_control() => (None, Some(ch)),
+                Some(accent) => match keymap::compose(accent, ch) {
+                    Some(composed) => (None, Some(composed)),
+                    None => (Some(accent), Some(ch)),
+                },
+            },
+        }
+    }
+}
+
+impl Default for KeyboardState {
+    fn default() -> Self {
+        KeyboardState::new()
+    }
+}
+
+// --- LEDs ---
+
+const CMD_SET_LEDS: u8 = 0xED;
+const CMD_SET_SCANCODE_SET: u8 = 0xF0;
+const CMD_ENABLE_SCANNING: u8 = 0xF4;
+const CMD_RESET: u8 = 0xFF;
+const SELF_TEST_PASSED: u8 = 0xAA;
+
+/// Comando de LED em andamento, esperando o ACK do teclado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LedCommand {
+    Idle,
+    /// 0xED enviado; o valor vai depois do ACK
+    Command(u8),
+    /// Valor enviado
+    Value(u8),
+}
+
+struct Leds {
+    command: LedCommand,
+    wanted: u8,
+}
+
+impl Leds {
+    fn set(&mut self, leds: u8) {
+        self.wanted = leds;
+        if self.command == LedCommand::Idle {
+            write_data(CMD_SET_LEDS);
+            self.command = LedCommand::Command(leds);
+        }
+    }
+
+    /// Consome ACK/RESEND do comando em andamento; `false` para scancodes.
+    fn on_byte(&mut self, byte: u8) -> bool {
+        match (self.command, byte) {
+            (LedCommand::Command(leds), ps2::ACK) => {
+                write_data(leds);
+                self.command = LedCommand::Value(leds);
+            }
+            (LedCommand::Command(_), ps2::RESEND) => write_data(CMD_SET_LEDS),
+            (LedCommand::Value(leds), ps2::ACK) => {
+                self.command = LedCommand::Idle;
+                // Mudou de novo enquanto o comando andava
+                if self.wanted != leds {
+                    self.set(self.wanted);
+                }
+            }
+            (LedCommand::Value(leds), ps2::RESEND) => write_data(leds),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Escreve no teclado sem esperar o ACK, que chega pela IRQ.
+fn write_data(byte: u8) {
+    if let Err(err) = ps2::write_device(Channel::First, byte) {
+        crate::serial_println!("Keyboard: byte {:#04x}: {}", byte, err);
+    }
+}
+
+// --- Filas ---
+
+struct Driver {
+    state: KeyboardState,
+    leds: Leds,
+    bytes: Ring<u8, 128>,
+    events: Ring<KeyEvent, 64>,
+}
+
+static DRIVER: IrqSpinlock<Driver> = IrqSpinlock::new(Driver {
+    state: KeyboardState::new(),
+    leds: Leds { command: LedCommand::Idle, wanted: 0 },
+    bytes: Ring::new(),
+    events: Ring::new(),
+});
+
+// Threads esperando tecla (acordadas pelo IRQ 1)
+static KEY_WAITERS: WaitQueue = WaitQueue::new();
 
 // Init
 pub fn init() {
-    let mut status_port = Port::new(0x64);
-    unsafe {
-        status_port.write(0xae as u8);
+    let wanted = match crate::config::get("scancodes").as_deref() {
+        Some("2") => ScancodeSet::Set2,
+        _ => ScancodeSet::Set1,
+    };
+    let set = match bring_up(wanted) {
+        Ok(set) => set,
+        Err(err) => {
+            crate::klog!("Keyboard: {}; seguindo com o teclado como o firmware deixou", err);
+            ps2::enable_first_port_fallback();
+            ScancodeSet::Set1
+        }
+    };
+    DRIVER.lock().state.set_scancode_set(set);
+    // `set keymap=abnt2` no tri-shellrc
+    if let Some(name) = crate::config::get("keymap") {
+        match keymap::find(&name) {
+            Some(layout) => set_layout(layout),
+            None => crate::klog!("Keyboard: layout '{}' desconhecido, mantendo US", name),
+        }
     }
-    crate::serial_println!("Keyboard driver init: Simple map table loaded (no shift yet)");
+    crate::serial_println!("Keyboard driver init: scancode set {}, layout {}, LEDs via 0xED",
+        set as u8, layout().name);
 }
 
-// Add scancode
+/// Controlador, reset do teclado, set de scancodes, LEDs apagados e a IRQ
+/// ligada; tudo por polling antes da IRQ. Devolve o set em uso.
+fn bring_up(wanted: ScancodeSet) -> Result<ScancodeSet, &'static str> {
+    ps2::init()?;
+    ps2::send(Channel::First, CMD_RESET)?;
+    // O autoteste (BAT) do teclado pode levar centenas de ms
+    match ps2::read_from(Channel::First, ps2::TIMEOUT_MS * 20) {
+        Some(SELF_TEST_PASSED) => {}
+        Some(_) => return Err("autoteste do teclado falhou"),
+        None => return Err("teclado não terminou o reset"),
+    }
+    // O teclado fica no set 2 nos dois casos; no set 1 quem converte é o 8042
+    let set = match ps2::send(Channel::First, CMD_SET_SCANCODE_SET)
+        .and_then(|_| ps2::send(Channel::First, 2))
+    {
+        Ok(()) => wanted,
+        Err(err) => {
+            crate::klog!("Keyboard: set 2 recusado ({}), usando o set 1 traduzido", err);
+            ScancodeSet::Set1
+        }
+    };
+    ps2::set_translation(set == ScancodeSet::Set1)?;
+    ps2::send(Channel::First, CMD_SET_LEDS)?;
+    ps2::send(Channel::First, 0)?;
+    ps2::send(Channel::First, CMD_ENABLE_SCANNING)?;
+    ps2::enable_irq(Channel::First)?;
+    Ok(set)
+}
+
+pub fn set_layout(layout: &'static Layout) {
+    DRIVER.lock().state.set_layout(layout);
+}
+
+pub fn layout() -> &'static Layout {
+    DRIVER.lock().state.layout
+}
+
+// Ctrl+C: interrompe o processo em primeiro plano
+const ETX: u8 = 0x03;
+
+/// Chamado pelo IRQ 1 com cada byte lido da porta 0x60.
 pub fn add_scancode(scancode: u8) {
-    if scancode > 127 { return; }
+    let mut driver = DRIVER.lock();
+    if driver.leds.on_byte(scancode) {
+        return;
+    }
+    let Some(event) = driver.state.process(scancode) else { return };
+    if event.pressed && !event.repeat
+        && matches!(event.key, KeyCode::CapsLock | KeyCode::NumLock | KeyCode::ScrollLock)
+    {
+        driver.leds.set(event.modifiers.leds());
+    }
+    let mut buf = [0; 8];
+    let text = event.encode(&mut buf);
+    // Um caractere UTF-8 entra inteiro ou não entra
+    if driver.bytes.free() >= text.len() {
+        for &byte in text {
+            driver.bytes.push(byte);
+        }
+    }
+    driver.events.push_overwrite(event);
+    drop(driver);
+    if text == [ETX] {
+        crate::process::interrupt_foreground();
+    }
+    KEY_WAITERS.wake_all();
+}
 
-    let ch = if (scancode as usize) < SC_ASCII.len() { SC_ASCII[scancode as usize] } else { 0 };
+/// Injeta texto na fila de bytes como se tivesse sido digitado (colar do
+/// mouse). O que não couber é descartado.
+pub fn paste(text: &str) {
+    let mut driver = DRIVER.lock();
+    let mut end = text.len().min(driver.bytes.free());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    for &byte in &text.as_bytes()[..end] {
+        driver.bytes.push(byte);
+    }
+    drop(driver);
+    KEY_WAITERS.wake_all();
+}
 
-    if ch > 0 {
-        let next_head = (HEAD.load(Ordering::Relaxed) + 1) % BUFFER_SIZE;
-        if next_head != TAIL.load(Ordering::Relaxed) {
-            unsafe { KEYBOARD_BUFFER[HEAD.load(Ordering::Relaxed)] = ch; }
-            HEAD.store(next_head, Ordering::Relaxed);
+/// Bytes de outra fonte de entrada (console serial): entram na mesma fila
+/// que o shell lê. O que não couber é descartado.
+pub fn push_input(bytes: &[u8]) {
+    let mut driver = DRIVER.lock();
+    for &byte in bytes {
+        if !driver.bytes.push(byte) {
+            break;
         }
     }
+    drop(driver);
+    if bytes.contains(&ETX) {
+        crate::process::interrupt_foreground();
+    }
+    KEY_WAITERS.wake_all();
 }
 
 // Get key
 pub fn get_key() -> Option<u8> {
-    let tail = TAIL.load(Ordering::Relaxed);
-    if tail == HEAD.load(Ordering::Relaxed) {
-        None
-    } else {
-        let c = unsafe { KEYBOARD_BUFFER[tail] };
-        TAIL.store((tail + 1) % BUFFER_SIZE, Ordering::Relaxed);
-        Some(c)
+    DRIVER.lock().bytes.pop()
+}
+
+/// Bloqueia a thread até chegar uma tecla.
+pub fn wait_key() -> u8 {
+    wait_key_unless(|| false).unwrap()
+}
+
+/// Como `wait_key`, mas desiste (None) quando `stop()` ficar verdadeiro;
+/// a condição é reavaliada a cada despertar da thread.
+pub fn wait_key_unless(stop: impl Fn() -> bool) -> Option<u8> {
+    let mut key = None;
+    KEY_WAITERS.wait_until(|| {
+        key = get_key();
+        key.is_some() || stop()
+    });
+    key
+}
+
+/// Próximo evento (pressionamento ou soltura), sem bloquear.
+pub fn next_event() -> Option<KeyEvent> {
+    DRIVER.lock().events.pop()
+}
+
+/// Bloqueia a thread até o próximo evento de tecla.
+pub fn wait_event() -> KeyEvent {
+    let mut event = None;
+    KEY_WAITERS.wait_until(|| {
+        event = next_event();
+        event.is_some()
+    });
+    event.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(state: &mut KeyboardState, bytes: &[u8]) -> alloc::vec::Vec<KeyEvent> {
+        bytes.iter().filter_map(|&b| state.process(b)).collect()
+    }
+
+    fn typed(state: &mut KeyboardState, bytes: &[u8]) -> alloc::string::String {
+        let mut text = alloc::vec::Vec::new();
+        for event in feed_all(state, bytes) {
+            text.extend_from_slice(event.encode(&mut [0; 8]));
+        }
+        alloc::string::String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn decodes_extended_release_and_pause() {
+        let mut decoder = Decoder::new(ScancodeSet::Set1);
+        assert_eq!(decoder.feed(0x1E), Some((KeyCode::A, true)));
+        assert_eq!(decoder.feed(0x9E), Some((KeyCode::A, false)));
+        assert_eq!(decoder.feed(0xE0), None);
+        assert_eq!(decoder.feed(0x48), Some((KeyCode::Up, true)));
+        // Shift falso em volta do PrintScreen
+        assert_eq!(decoder.feed(0xE0), None);
+        assert_eq!(decoder.feed(0x2A), None);
+        assert_eq!(decoder.feed(0xE0), None);
+        assert_eq!(decoder.feed(0x37), Some((KeyCode::PrintScreen, true)));
+        let pause: alloc::vec::Vec<_> = [0xE1, 0x1D, 0x45, 0xE1, 0x9D, 0xC5, 0x1C]
+            .iter().filter_map(|&b| decoder.feed(b)).collect();
+        assert_eq!(pause, [(KeyCode::Pause, true), (KeyCode::Enter, true)]);
+        assert_eq!(set1_key(0x53), Some(KeyCode::KeypadPeriod));
+        assert_eq!(set1_key(0x58), Some(KeyCode::F12));
+        assert_eq!(set1_key(0x54), None);
+    }
+
+    #[test]
+    fn decodes_set2() {
+        let mut decoder = Decoder::new(ScancodeSet::Set2);
+        let keys: alloc::vec::Vec<_> = [
+            0x1C, 0xF0, 0x1C,             // A
+            0xE0, 0x75, 0xE0, 0xF0, 0x75, // seta para cima
+            0xE0, 0x12, 0xE0, 0x7C,       // PrintScreen com shift falso
+            0xE1, 0x14, 0x77, 0xE1, 0xF0, 0x14, 0xF0, 0x77,
+            0x83, 0x51,                   // F7, /? do ABNT2
+        ].iter().filter_map(|&b| decoder.feed(b)).collect();
+        assert_eq!(keys, [
+            (KeyCode::A, true), (KeyCode::A, false),
+            (KeyCode::Up, true), (KeyCode::Up, false),
+            (KeyCode::PrintScreen, true),
+            (KeyCode::Pause, true),
+            (KeyCode::F7, true), (KeyCode::IntlRo, true),
+        ]);
+    }
+
+    #[test]
+    fn shift_caps_and_ctrl() {
+        let mut state = KeyboardState::new();
+        // Shift + a, Shift + \ , solta o Shift
+        assert_eq!(typed(&mut state, &[0x2A, 0x1E, 0x2B, 0xAA, 0x1E]), "A|a");
+        // Caps Lock com autorepeat troca uma vez só; Shift inverte
+        feed_all(&mut state, &[0x3A, 0x3A, 0xBA]);
+        assert!(state.modifiers.caps_lock);
+        assert_eq!(state.modifiers.leds(), 0b100);
+        assert_eq!(typed(&mut state, &[0x1E, 0x2A, 0x1E, 0x02, 0xAA]), "Aa!");
+        // Ctrl + C
+        assert_eq!(typed(&mut state, &[0x1D, 0x2E, 0x9D]), "\x03");
+    }
+
+    #[test]
+    fn keypad_follows_num_lock() {
+        let mut state = KeyboardState::new();
+        assert_eq!(state.process(0x47).unwrap().ch, None);
+        feed_all(&mut state, &[0x45, 0xC5]);
+        assert_eq!(state.process(0x47).unwrap().ch, Some('7'));
+        assert_eq!(feed_all(&mut state, &[0xE0, 0x35])[0].ch, Some('/'));
+        assert!(state.is_pressed(KeyCode::KeypadSlash));
+    }
+
+    #[test]
+    fn abnt2_dead_keys() {
+        let mut state = KeyboardState::new();
+        state.set_layout(&keymap::ABNT2);
+        // ´ + e, Shift + ~ (^) + o, ~ + espaço, ´ + x, ç
+        assert_eq!(typed(&mut state, &[0x1A, 0x12, 0x2A, 0x28, 0xAA, 0x18, 0x28, 0x39]), "éô~");
+        assert_eq!(typed(&mut state, &[0x1A, 0x2D, 0x27, 0x73]), "´xç/");
+        // ´ ´ dá o acento; ´ + Backspace só descarta
+        assert_eq!(typed(&mut state, &[0x1A, 0x1A, 0x1A, 0x0E]), "´\x08");
     }
 }
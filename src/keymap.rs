@@ -0,0 +1,194 @@
+// src/keymap.rs
+// ====================
+// LAYOUTS DE TECLADO - Tecla física + modificadores -> caractere, com
+// US-QWERTY e ABNT2 (teclas mortas para acentos)
+// ====================
+//
+// O layout só traduz; quem guarda a tecla morta pendente e chama `compose`
+// é o `keyboard::KeyboardState`. Uma tecla morta é representada pelo acento
+// isolado (´ ` ~ ^ ¨), que também é o que sai quando ela não compõe.
+
+use crate::keyboard::{KeyCode, Modifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+    Char(char),
+    /// Tecla morta: espera a próxima tecla para compor o acento
+    Dead(char),
+}
+
+pub struct Layout {
+    pub name: &'static str,
+    pub description: &'static str,
+    lookup: fn(KeyCode, &Modifiers) -> Option<Symbol>,
+}
+
+impl Layout {
+    pub fn symbol(&self, key: KeyCode, modifiers: &Modifiers) -> Option<Symbol> {
+        (self.lookup)(key, modifiers)
+    }
+}
+
+pub static US: Layout = Layout { name: "us", description: "US-QWERTY", lookup: us };
+pub static ABNT2: Layout = Layout { name: "abnt2", description: "Brasileiro ABNT2", lookup: abnt2 };
+
+pub static LAYOUTS: [&Layout; 2] = [&US, &ABNT2];
+
+/// Layout pelo nome (sem diferenciar maiúsculas).
+pub fn find(name: &str) -> Option<&'static Layout> {
+    LAYOUTS.iter().copied().find(|layout| layout.name.eq_ignore_ascii_case(name))
+}
+
+// Acento, letras base e letras acentuadas, na mesma ordem
+const COMPOSE: [(char, &str, &str); 5] = [
+    ('´', "aeiouycAEIOUYC", "áéíóúýçÁÉÍÓÚÝÇ"),
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('~', "aonAON", "ãõñÃÕÑ"),
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('¨', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+];
+
+/// Letra `base` com o acento de uma tecla morta, se a combinação existe.
+pub fn compose(accent: char, base: char) -> Option<char> {
+    let (_, bases, composed) = COMPOSE.iter().find(|(dead, _, _)| *dead == accent)?;
+    let i = bases.chars().position(|ch| ch == base)?;
+    composed.chars().nth(i)
+}
+
+// --- Layouts ---
+
+/// Letras seguem Shift xor Caps Lock.
+fn cased(lower: char, upper: char, modifiers: &Modifiers) -> char {
+    if modifiers.shift() != modifiers.caps_lock { upper } else { lower }
+}
+
+fn letter(key: KeyCode, modifiers: &Modifiers) -> Option<char> {
+    use KeyCode::*;
+    const LETTERS: [KeyCode; 26] = [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z];
+    let i = LETTERS.iter().position(|&letter| letter == key)?;
+    let ch = (b'a' + i as u8) as char;
+    Some(cased(ch, ch.to_ascii_uppercase(), modifiers))
+}
+
+/// Teclado numérico: dígitos só com Num Lock (sem ele são as setas).
+fn keypad_digit(key: KeyCode, modifiers: &Modifiers) -> Option<char> {
+    use KeyCode::*;
+    const KEYPAD: [KeyCode; 10] = [Keypad0, Keypad1, Keypad2, Keypad3, Keypad4, Keypad5, Keypad6, Keypad7, Keypad8, Keypad9];
+    let i = KEYPAD.iter().position(|&pad| pad == key)?;
+    modifiers.num_lock.then(|| (b'0' + i as u8) as char)
+}
+
+fn us(key: KeyCode, modifiers: &Modifiers) -> Option<Symbol> {
+    use KeyCode::*;
+    if let Some(ch) = letter(key, modifiers).or_else(|| keypad_digit(key, modifiers)) {
+        return Some(Symbol::Char(ch));
+    }
+    let (normal, shifted) = match key {
+        Key1 => ('1', '!'),
+        Key2 => ('2', '@'),
+        Key3 => ('3', '#'),
+        Key4 => ('4', '$'),
+        Key5 => ('5', '%'),
+        Key6 => ('6', '^'),
+        Key7 => ('7', '&'),
+        Key8 => ('8', '*'),
+        Key9 => ('9', '('),
+        Key0 => ('0', ')'),
+        Minus => ('-', '_'),
+        Equals => ('=', '+'),
+        LeftBracket => ('[', '{'),
+        RightBracket => (']', '}'),
+        Semicolon => (';', ':'),
+        Quote => ('\'', '"'),
+        Backquote => ('`', '~'),
+        Backslash | IntlBackslash => ('\\', '|'),
+        Comma => (',', '<'),
+        Period => ('.', '>'),
+        Slash => ('/', '?'),
+        KeypadPeriod if modifiers.num_lock => ('.', '.'),
+        Space => (' ', ' '),
+        Tab => ('\t', '\t'),
+        Enter | KeypadEnter => ('\n', '\n'),
+        Backspace => ('\x08', '\x08'),
+        Escape => ('\x1B', '\x1B'),
+        Delete => ('\x7F', '\x7F'),
+        KeypadPlus => ('+', '+'),
+        KeypadMinus => ('-', '-'),
+        KeypadStar => ('*', '*'),
+        KeypadSlash => ('/', '/'),
+        _ => return None,
+    };
+    Some(Symbol::Char(if modifiers.shift() { shifted } else { normal }))
+}
+
+/// ABNT2: Ç no lugar do `;`, acentos agudo/grave e til/circunflexo como
+/// teclas mortas, `/?` extra em 0x73 e AltGr (Alt direito) para ¹²³£¢¬§ªº°.
+fn abnt2(key: KeyCode, modifiers: &Modifiers) -> Option<Symbol> {
+    use KeyCode::*;
+    use Symbol::{Char, Dead};
+    if modifiers.right_alt {
+        let ch = match key {
+            Key1 => '¹',
+            Key2 => '²',
+            Key3 => '³',
+            Key4 => '£',
+            Key5 => '¢',
+            Key6 => '¬',
+            Equals => '§',
+            RightBracket => 'ª',
+            Backslash => 'º',
+            Q => '/',
+            W => '?',
+            E => '°',
+            _ => return None,
+        };
+        return Some(Char(ch));
+    }
+    let (normal, shifted) = match key {
+        Semicolon => return Some(Char(cased('ç', 'Ç', modifiers))),
+        Backquote => (Char('\''), Char('"')),
+        Key6 => (Char('6'), Dead('¨')),
+        LeftBracket => (Dead('´'), Dead('`')),
+        RightBracket => (Char('['), Char('{')),
+        Quote => (Dead('~'), Dead('^')),
+        Backslash => (Char(']'), Char('}')),
+        Slash => (Char(';'), Char(':')),
+        IntlRo => (Char('/'), Char('?')),
+        KeypadPeriod if modifiers.num_lock => (Char(','), Char(',')),
+        KeypadComma => (Char('.'), Char('.')),
+        // O resto (letras, números, -_ =+ ,< .> \|) é igual ao US
+        _ => return us(key, modifiers),
+    };
+    Some(if modifiers.shift() { shifted } else { normal })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose_accents() {
+        assert_eq!(compose('´', 'e'), Some('é'));
+        assert_eq!(compose('~', 'A'), Some('Ã'));
+        assert_eq!(compose('^', 'o'), Some('ô'));
+        assert_eq!(compose('´', 'c'), Some('ç'));
+        assert_eq!(compose('~', 'x'), None);
+        assert_eq!(compose('*', 'a'), None);
+    }
+
+    #[test]
+    fn abnt2_keys() {
+        let plain = Modifiers::default();
+        let shift = Modifiers { left_shift: true, ..Modifiers::default() };
+        let altgr = Modifiers { right_alt: true, ..Modifiers::default() };
+        assert_eq!(ABNT2.symbol(KeyCode::Semicolon, &plain), Some(Symbol::Char('ç')));
+        assert_eq!(ABNT2.symbol(KeyCode::Semicolon, &shift), Some(Symbol::Char('Ç')));
+        assert_eq!(ABNT2.symbol(KeyCode::IntlRo, &shift), Some(Symbol::Char('?')));
+        assert_eq!(ABNT2.symbol(KeyCode::Quote, &plain), Some(Symbol::Dead('~')));
+        assert_eq!(ABNT2.symbol(KeyCode::Key2, &shift), Some(Symbol::Char('@')));
+        assert_eq!(ABNT2.symbol(KeyCode::Q, &altgr), Some(Symbol::Char('/')));
+        assert_eq!(US.symbol(KeyCode::Quote, &shift), Some(Symbol::Char('"')));
+        assert!(core::ptr::eq(find("ABNT2").unwrap(), &ABNT2));
+        assert!(find("dvorak").is_none());
+    }
+}
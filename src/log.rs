@@ -0,0 +1,61 @@
+// src/log.rs
+// ====================
+// LOG DO KERNEL - Linhas com timestamp na serial + buffer circular (dmesg)
+// ====================
+
+use core::fmt;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::sync::IrqSpinlock;
+use crate::rtc;
+
+const LOG_LINES: usize = 64;
+const LOG_FILE: &str = "/var/log/kern.log";
+const FLUSH_INTERVAL_MS: u64 = 5000;
+
+static LOG: IrqSpinlock<VecDeque<String>> = IrqSpinlock::new(VecDeque::new());
+
+pub fn log(args: fmt::Arguments) {
+    let ms = rtc::unix_time_ms();
+    let time = rtc::DateTime::from_unix(ms / 1000);
+    let line = format!("[{:02}:{:02}:{:02}.{:03}] {}", time.hour, time.minute, time.second, ms % 1000, args);
+    crate::serial_println!("{}", line);
+
+    let mut log = LOG.lock();
+    if log.len() == LOG_LINES {
+        log.pop_front();
+    }
+    log.push_back(line);
+}
+
+/// Cópia das últimas linhas, da mais antiga para a mais nova.
+pub fn lines() -> Vec<String> {
+    LOG.lock().iter().cloned().collect()
+}
+
+/// Grava o buffer do dmesg em /var/log/kern.log (antes do unmount).
+pub fn flush() -> Result<(), &'static str> {
+    let mut text = String::new();
+    for line in lines() {
+        text.push_str(&line);
+        text.push('\n');
+    }
+    crate::virtual_fs::write_file(LOG_FILE, text.as_bytes())
+}
+
+/// Corpo da thread `klogd`: grava o log periodicamente até o FS ser desmontado.
+pub fn klogd() {
+    while crate::virtual_fs::is_mounted() {
+        crate::timer::sleep_ms(FLUSH_INTERVAL_MS);
+        let _ = flush();
+    }
+}
+
+#[macro_export]
+macro_rules! klog {
+    ($($arg:tt)*) => {
+        $crate::log::log(format_args!($($arg)*))
+    };
+}
@@ -1,96 +1,77 @@
 #![no_std]
 #![no_main]
 #![feature(abi_x86_interrupt)]
-
+#![feature(alloc_error_handler)]
+
+extern crate alloc;
+
+mod acpi;
+mod allocator;
+mod apic;
+mod clock;
+mod config;
+mod cpuid;
+mod elf;
+mod fpu;
+mod frame_allocator;
+mod gdt;
+mod hpet;
 mod interrupts;
 mod keyboard;
+mod keymap;
+mod log;
+mod memory;
+mod mouse;
+mod power;
+mod process;
+mod ps2;
 mod tri_compress;
 mod virtual_fs;
 mod vga;
+mod ring;
+mod rtc;
+mod scheduler;
+mod serial;
 mod shell;
+mod sync;
+mod syscall;
+mod slab;
+mod timer;
+mod timer_wheel;
+mod usermode;
 
 use core::panic::PanicInfo;
 use core::fmt::Write; // Adicionado para write_fmt
 use bootloader::{BootInfo, entry_point};
-use x86_64::instructions;
+use x86_64::{instructions, VirtAddr};
 
 // --- Panic Handler ---
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    loop {
-        instructions::hlt();
-    }
-}
-
-// --- SerialPort (para debug) ---
-struct SerialPort;
-impl core::fmt::Write for SerialPort {
-    fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for byte in s.bytes() {
-            unsafe {
-                let port = 0x3f8 as *mut u8;
-                let status = 0x3fd as *const u8;
-                while (*status & 0x20) == 0 {}
-                *port = byte;
-            }
-        }
-        Ok(())
-    }
-}
-
-static mut SERIAL: SerialPort = SerialPort;
-
-fn print_serial(args: core::fmt::Arguments) {
-    unsafe { SERIAL.write_fmt(args).unwrap(); }
-}
-
-pub fn serial_print(s: &str) {
-    print_serial(format_args!("{}", s));
-}
-
-pub fn serial_println(s: &str) {
-    serial_print(s);
-    print_serial(format_args!("\n"));
-}
-
-#[macro_export]
-macro_rules! serial_println {
-    ($($arg:tt)*) => {
-        $crate::print_serial(format_args!($($arg)*));
-        $crate::print_serial(format_args!("\n"));
-    };
-}
-
-pub fn serial_putc(byte: u8) {
+fn panic(info: &PanicInfo) -> ! {
+    instructions::interrupts::disable();
+    // Quem entrou em pânico pode estar segurando o WRITER
     unsafe {
-        let port = 0x3f8 as *mut u8;
-        let status = 0x3fd as *const u8;
-        while (*status & 0x20) == 0 {}
-        *port = byte;
+        vga::force_unlock();
+        serial::force_unlock();
     }
-}
-
-// --- Serial Init ---
-fn serial_init() {
-    unsafe {
-        let port = 0x3f8 as *mut u8;
-        *port = 0x03;
-        *(port.offset(1)) = 0x00;
-        *(port.offset(3)) = 0x03;
-        *(port.offset(2)) = 0xc7;
-        *(port.offset(4)) = 0x0b;
+    serial_println!("*** KERNEL PANIC: {} ***", info);
+    println!("*** KERNEL PANIC: {} ***", info);
+    loop {
+        instructions::hlt();
     }
 }
 
 // --- Entry Point ---
 entry_point!(_start);
 
-fn _start(_boot_info: &'static BootInfo) -> ! {
-    serial_init();
+fn _start(boot_info: &'static BootInfo) -> ! {
+    serial::init();
 
     // Inicializar VGA
     vga::init_vga(vga::Color::LightCyan, vga::Color::Black);
 
+    serial_println!("CPU: {}", cpuid::info());
+
     // Teste TRI
     let original: [u8; 32] = *b"TRI-Kernel Boot!!!\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
     let compressed = tri_compress::compress(&original);
@@ -103,25 +84,74 @@ fn _start(_boot_info: &'static BootInfo) -> ! {
     serial_println!("Boot OK! TRI Ratio: {}%. Decomp: {}", ratio, decomp_ok);
     println!("Boot OK! TRI Ratio: {}%. Decomp: {}", ratio, decomp_ok);  // VGA
 
+    // Inicializar Memória Física
+    frame_allocator::init(&boot_info.memory_map);
+    let mem = frame_allocator::stats();
+    serial_println!("Frames: {} KiB livres de {} KiB ({} KiB reservados)",
+        mem.free_bytes / 1024, mem.total_bytes / 1024, mem.reserved_bytes / 1024);
+    println!("Frames: {} KiB livres de {} KiB ({} KiB reservados)",
+        mem.free_bytes / 1024, mem.total_bytes / 1024, mem.reserved_bytes / 1024);  // VGA
+    memory::init(VirtAddr::new(boot_info.physical_memory_offset));
+    serial_println!("Memória física mapeada em {:#x}", boot_info.physical_memory_offset);
+    allocator::init_heap().expect("falha ao mapear o heap");
+    serial_println!("Heap: {} KiB em {:#x}", allocator::HEAP_SIZE / 1024, allocator::HEAP_START);
+    println!("Heap: {} KiB em {:#x}", allocator::HEAP_SIZE / 1024, allocator::HEAP_START);  // VGA
+
+    // ACPI: só precisa do mapeamento físico e do heap
+    if let Err(err) = acpi::init() {
+        serial_println!("ACPI: {:?}", err);
+    }
+    if let Some(fadt) = acpi::fadt() {
+        rtc::set_century_register(fadt.century_register);
+    }
+    if let Err(err) = hpet::init() {
+        serial_println!("HPET: {}", err);
+    }
+    clock::init();
+    if let Err(err) = fpu::init() {
+        serial_println!("FPU: {}", err);
+    }
+
     // Inicializar Interrupções
     serial_println!("Inicializando IDT e IRQs...");
     println!("Inicializando IDT e IRQs...");  // VGA
+    gdt::init();
     interrupts::init_idt();
+    syscall::init();
     interrupts::init_pics();
+    timer::init();
     instructions::interrupts::enable();
-    serial_println!("Interrupções habilitadas (Timer + Teclado)");
-    println!("Interrupções habilitadas (Timer + Teclado)");  // VGA
+    serial_println!("Interrupções habilitadas (Timer {} Hz + Teclado)", timer::TIMER_HZ);
+    println!("Interrupções habilitadas (Timer {} Hz + Teclado)", timer::TIMER_HZ);  // VGA
+
+    // Relógio de parede
+    rtc::init();
+    rtc::enable_periodic();
+    interrupts::unmask_irq(8);
+    klog!("RTC: {} UTC (IRQ 8 a {} Hz)", rtc::now(), rtc::PERIODIC_HZ);
+    println!("RTC: {} UTC", rtc::now());  // VGA
+
+    // FS Virtual (o tri-shellrc também guarda opções de boot)
+    virtual_fs::init();
+
+    // Controlador de interrupções: APIC por padrão, PIC 8259 como fallback
+    if config::get("irqchip").as_deref() == Some("pic") {
+        klog!("IRQ: PIC 8259 (irqchip=pic)");
+    } else if let Err(err) = interrupts::init_apic() {
+        klog!("IRQ: APIC indisponível ({}), usando PIC 8259", err);
+    }
 
     // Inicializar Teclado
     keyboard::init();
     serial_println!("Keyboard init OK");
     println!("Keyboard init OK");  // VGA
+    mouse::init();
+    serial::enable_interrupts();
 
-    // Inicializar FS Virtual
     serial_println!("Virtual FS montado: /bin e /etc");
     println!("Virtual FS montado: /bin e /etc");  // VGA
     if let Some(config) = virtual_fs::read_file("/etc/tri-shellrc") {
-        let config_str = core::str::from_utf8(config).unwrap_or("Erro UTF8");
+        let config_str = core::str::from_utf8(&config).unwrap_or("Erro UTF8");
         serial_println!("Config carregada: {}", config_str);
         println!("Config carregada: {}", config_str);  // VGA
     } else {
@@ -129,13 +159,21 @@ fn _start(_boot_info: &'static BootInfo) -> ! {
         println!("Erro: tri-shellrc não encontrado!");  // VGA
     }
 
+    // Threads: o fluxo do boot vira a thread do shell
+    scheduler::init("shell");
+    process::init("shell");
+    for (name, body) in [("timerd", timer_wheel::timerd as fn()), ("klogd", log::klogd as fn())] {
+        match scheduler::spawn(name, body) {
+            Ok(id) => scheduler::detach(id),
+            Err(err) => { serial_println!("{}: {}", name, err); }
+        }
+    }
+    klog!("Escalonador: round-robin, fatia de 10 ms");
+
     // Executar Shell
     serial_println!("Init: Executando /bin/shell (novo shell_loop)...");
     println!("Init: Executando /bin/shell (novo shell_loop)...");  // VGA
-    {
-        let mut writer_guard = vga::get_writer().lock();
-        crate::shell::shell_loop(&mut *writer_guard);
-    }
+    crate::shell::shell_loop(&mut vga::Console);
 
     // Caso o shell retorne (não deveria), pausar CPU
     loop {
@@ -0,0 +1,251 @@
+// src/memory.rs
+// ====================
+// MEMÓRIA VIRTUAL - Tabelas de página sobre o mapeamento físico do bootloader
+// ====================
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::{PhysAddr, VirtAddr};
+use x86_64::registers::control::{Cr3, Cr3Flags};
+use x86_64::structures::paging::{
+    Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, PhysFrame, Size4KiB, Translate,
+};
+use x86_64::structures::paging::mapper::{MapToError, TranslateResult, UnmapError};
+use spin::Mutex;
+use crate::frame_allocator::{self, GlobalFrameAllocator};
+
+// Janela virtual para MMIO de dispositivos (APIC, HPET, ...)
+const MMIO_START: u64 = 0x5555_0000_0000;
+const MMIO_SIZE: u64 = 0x1_0000_0000; // 4 GiB de espaço de endereçamento
+
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+static MMIO_NEXT: AtomicU64 = AtomicU64::new(MMIO_START);
+static MAPPER: Mutex<Option<OffsetPageTable<'static>>> = Mutex::new(None);
+// Tabela de nível 4 do boot, ativa em toda thread sem espaço próprio
+static KERNEL_LEVEL_4: AtomicU64 = AtomicU64::new(0);
+
+/// Inicializa o mapeador sobre a tabela de nível 4 ativa (a do bootloader).
+pub fn init(physical_memory_offset: VirtAddr) {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset.as_u64(), Ordering::SeqCst);
+    let (level_4_frame, _) = Cr3::read();
+    KERNEL_LEVEL_4.store(level_4_frame.start_address().as_u64(), Ordering::SeqCst);
+    let level_4_table = unsafe { table_at(level_4_frame) };
+    *MAPPER.lock() = Some(unsafe { OffsetPageTable::new(level_4_table, physical_memory_offset) });
+}
+
+pub fn physical_memory_offset() -> VirtAddr {
+    VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::SeqCst))
+}
+
+/// Endereço virtual de um endereço físico dentro do mapeamento completo.
+pub fn phys_to_virt(addr: PhysAddr) -> VirtAddr {
+    physical_memory_offset() + addr.as_u64()
+}
+
+unsafe fn table_at(frame: PhysFrame) -> &'static mut PageTable {
+    &mut *phys_to_virt(frame.start_address()).as_mut_ptr::<PageTable>()
+}
+
+fn with_mapper<R>(f: impl FnOnce(&mut OffsetPageTable<'static>) -> R) -> R {
+    let mut guard = MAPPER.lock();
+    let mapper = guard.as_mut().expect("memory::init não foi chamado");
+    f(mapper)
+}
+
+/// Endereço físico de `addr` nos mapeamentos do kernel (None se não mapeado).
+pub fn translate(addr: VirtAddr) -> Option<PhysAddr> {
+    with_mapper(|mapper| mapper.translate_addr(addr))
+}
+
+/// Mapeia `page` -> `frame` no espaço de endereçamento do kernel.
+pub fn map_page(page: Page, frame: PhysFrame, flags: PageTableFlags) -> Result<(), MapToError<Size4KiB>> {
+    with_mapper(|mapper| {
+        unsafe { mapper.map_to(page, frame, flags, &mut GlobalFrameAllocator)?.flush(); }
+        Ok(())
+    })
+}
+
+/// Remove o mapeamento e devolve o frame (que continua pertencendo ao chamador).
+pub fn unmap_page(page: Page) -> Result<PhysFrame, UnmapError> {
+    with_mapper(|mapper| {
+        let (frame, flush) = mapper.unmap(page)?;
+        flush.flush();
+        Ok(frame)
+    })
+}
+
+/// Mapeia `[start, start + size)` com frames novos do alocador físico.
+pub fn map_range(start: VirtAddr, size: u64, flags: PageTableFlags) -> Result<(), MapToError<Size4KiB>> {
+    if size == 0 {
+        return Ok(());
+    }
+    let first = Page::containing_address(start);
+    let last = Page::containing_address(start + size - 1u64);
+    for page in Page::range_inclusive(first, last) {
+        let frame = frame_allocator::allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+        if let Err(err) = map_page(page, frame, flags) {
+            // O frame não entrou na tabela: volta para o alocador
+            frame_allocator::free_frame(frame);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Mapeia uma região física de dispositivo (sem cache) na janela de MMIO.
+/// Com `size` zero nada é mapeado nem reservado; o endereço devolvido não
+/// deve ser acessado.
+pub fn map_mmio(phys: PhysAddr, size: u64) -> Result<VirtAddr, MapToError<Size4KiB>> {
+    if size == 0 {
+        return Ok(VirtAddr::new(MMIO_NEXT.load(Ordering::SeqCst)));
+    }
+    let first_frame = PhysFrame::<Size4KiB>::containing_address(phys);
+    let last_frame = PhysFrame::<Size4KiB>::containing_address(phys + size - 1u64);
+    let pages = (last_frame.start_address() - first_frame.start_address()) / 4096 + 1;
+
+    let base = MMIO_NEXT.fetch_add(pages * 4096, Ordering::SeqCst);
+    assert!(base + pages * 4096 <= MMIO_START + MMIO_SIZE, "janela de MMIO esgotada");
+
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE
+        | PageTableFlags::NO_CACHE | PageTableFlags::WRITE_THROUGH;
+    for (i, frame) in PhysFrame::range_inclusive(first_frame, last_frame).enumerate() {
+        let page = Page::containing_address(VirtAddr::new(base + i as u64 * 4096));
+        map_page(page, frame, flags)?;
+    }
+    Ok(VirtAddr::new(base + (phys.as_u64() - first_frame.start_address().as_u64())))
+}
+
+fn kernel_level_4_frame() -> PhysFrame {
+    PhysFrame::containing_address(PhysAddr::new(KERNEL_LEVEL_4.load(Ordering::SeqCst)))
+}
+
+/// Volta para a tabela do kernel, se outro espaço estiver ativo.
+pub fn activate_kernel_space() {
+    let kernel = kernel_level_4_frame();
+    if Cr3::read().0 != kernel {
+        unsafe { Cr3::write(kernel, Cr3Flags::empty()) };
+    }
+}
+
+/// Confere na tabela ativa se `[start, start + len)` está inteiro mapeado
+/// com USER_ACCESSIBLE (e WRITABLE, se `writable`). Páginas do kernel nunca
+/// têm USER_ACCESSIBLE, então isso também barra ponteiros para o kernel.
+pub fn is_user_range(start: VirtAddr, len: u64, writable: bool) -> bool {
+    if len == 0 {
+        return true;
+    }
+    let Some(end) = start.as_u64().checked_add(len - 1) else {
+        return false;
+    };
+    let mut required = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+    if writable {
+        required |= PageTableFlags::WRITABLE;
+    }
+    let (level_4_frame, _) = Cr3::read();
+    let table = unsafe { OffsetPageTable::new(table_at(level_4_frame), physical_memory_offset()) };
+    let first = Page::<Size4KiB>::containing_address(start);
+    let last = Page::<Size4KiB>::containing_address(VirtAddr::new(end));
+    Page::range_inclusive(first, last).all(|page| {
+        matches!(table.translate(page.start_address()),
+            TranslateResult::Mapped { flags, .. } if flags.contains(required))
+    })
+}
+
+// --- Espaços de endereçamento ---
+
+/// Tabela de nível 4 própria, que compartilha as entradas do kernel
+/// (copiadas na criação) e tem o resto livre para mapeamentos privados.
+/// Frames mapeados fora das entradas do kernel pertencem ao AddressSpace
+/// e são liberados no `Drop`.
+pub struct AddressSpace {
+    level_4_frame: PhysFrame,
+    kernel_entries: [bool; 512],
+}
+
+impl AddressSpace {
+    pub fn new() -> Result<Self, MapToError<Size4KiB>> {
+        let frame = frame_allocator::allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+        let table = unsafe { table_at(frame) };
+        table.zero();
+
+        // Copia da tabela do kernel, não da ativa: ela pode ser de um usuário
+        let kernel = unsafe { table_at(kernel_level_4_frame()) };
+        let mut kernel_entries = [false; 512];
+        for (i, entry) in kernel.iter().enumerate() {
+            if !entry.is_unused() {
+                table[i] = entry.clone();
+                kernel_entries[i] = true;
+            }
+        }
+        Ok(AddressSpace { level_4_frame: frame, kernel_entries })
+    }
+
+    pub fn mapper(&mut self) -> OffsetPageTable<'_> {
+        unsafe { OffsetPageTable::new(table_at(self.level_4_frame), physical_memory_offset()) }
+    }
+
+    /// Mapeia uma página privada. Entradas intermediárias herdam
+    /// USER_ACCESSIBLE de `flags` para que o ring 3 consiga atravessá-las.
+    pub fn map_page(&mut self, page: Page, frame: PhysFrame, flags: PageTableFlags) -> Result<(), MapToError<Size4KiB>> {
+        assert!(!self.kernel_entries[usize::from(page.p4_index())],
+            "página {:#x} colide com o kernel", page.start_address().as_u64());
+        let parent_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE
+            | (flags & PageTableFlags::USER_ACCESSIBLE);
+        let mut mapper = self.mapper();
+        unsafe {
+            mapper.map_to_with_table_flags(page, frame, flags, parent_flags, &mut GlobalFrameAllocator)?.flush();
+        }
+        Ok(())
+    }
+
+    pub fn translate(&mut self, addr: VirtAddr) -> Option<PhysAddr> {
+        self.mapper().translate_addr(addr)
+    }
+
+    /// `addr` cai numa entrada de nível 4 compartilhada com o kernel.
+    pub fn is_kernel_slot(&self, addr: VirtAddr) -> bool {
+        self.kernel_entries[usize::from(addr.p4_index())]
+    }
+
+    /// Troca o CR3 para este espaço.
+    ///
+    /// # Safety
+    /// O espaço precisa continuar vivo enquanto estiver ativo.
+    pub unsafe fn activate(&self) {
+        if Cr3::read().0 != self.level_4_frame {
+            Cr3::write(self.level_4_frame, Cr3Flags::empty());
+        }
+    }
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        let (active_frame, _) = Cr3::read();
+        assert!(active_frame != self.level_4_frame, "drop de AddressSpace ativo");
+
+        let level_4 = unsafe { table_at(self.level_4_frame) };
+        for (i, entry) in level_4.iter().enumerate() {
+            if !self.kernel_entries[i] && !entry.is_unused() {
+                free_table(entry.frame().unwrap(), 3);
+            }
+        }
+        frame_allocator::free_frame(self.level_4_frame);
+    }
+}
+
+// Libera recursivamente uma tabela de nível `level` (3..1) e tudo abaixo dela
+fn free_table(frame: PhysFrame, level: u8) {
+    let table = unsafe { table_at(frame) };
+    for entry in table.iter() {
+        if entry.is_unused() {
+            continue;
+        }
+        if level == 1 || entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            if let Ok(leaf) = entry.frame() {
+                frame_allocator::free_frame(leaf);
+            }
+        } else {
+            free_table(entry.frame().unwrap(), level - 1);
+        }
+    }
+    frame_allocator::free_frame(frame);
+}
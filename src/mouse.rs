@@ -0,0 +1,302 @@
+// src/mouse.rs
+// ====================
+// MOUSE PS/2 - Segunda porta do 8042 (IRQ 12), pacotes de 3 bytes e do
+// IntelliMouse (4 bytes, com roda), fila de eventos e cursor no console
+// ====================
+//
+// Cada pacote vira eventos de movimento, botão e roda na fila
+// (`next_event`/`wait_event`) e também move o cursor de bloco do VGA:
+// - botão esquerdo arrastado seleciona texto da tela e, ao soltar, copia;
+// - botão direito ou do meio cola o que foi copiado na entrada do teclado.
+
+use alloc::string::String;
+use crate::ps2::{self, Channel};
+use crate::ring::Ring;
+use crate::sync::{IrqSpinlock, WaitQueue};
+
+pub const IRQ: u8 = 12;
+
+const CMD_SET_SAMPLE_RATE: u8 = 0xF3;
+const CMD_GET_ID: u8 = 0xF2;
+const CMD_ENABLE_REPORTING: u8 = 0xF4;
+const CMD_SET_DEFAULTS: u8 = 0xF6;
+const CMD_RESET: u8 = 0xFF;
+const SELF_TEST_PASSED: u8 = 0xAA;
+
+// IDs do 0xF2: 3 = IntelliMouse (roda), 4 = IntelliMouse Explorer (roda +
+// 5 botões, com a roda nos mesmos 4 bits baixos do 4º byte)
+const ID_INTELLIMOUSE: u8 = 3;
+const ID_EXPLORER: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+}
+
+impl Button {
+    const ALL: [Button; 3] = [Button::Left, Button::Right, Button::Middle];
+
+    /// Bit do botão no 1º byte do pacote.
+    fn mask(self) -> u8 {
+        match self {
+            Button::Left => 0x01,
+            Button::Right => 0x02,
+            Button::Middle => 0x04,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEvent {
+    /// Deslocamento em contagens do mouse (`dy` positivo = para cima)
+    Move { dx: i16, dy: i16 },
+    Button { button: Button, pressed: bool },
+    /// Roda: positivo = para baixo (em direção ao usuário)
+    Scroll(i8),
+}
+
+// --- Pacotes ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    /// Botões presos (máscaras de `Button`)
+    pub buttons: u8,
+    pub dx: i16,
+    pub dy: i16,
+    pub wheel: i8,
+}
+
+/// Junta os bytes da IRQ 12 em pacotes de 3 ou 4 bytes.
+pub struct PacketDecoder {
+    bytes: [u8; 4],
+    len: usize,
+    size: usize,
+}
+
+impl PacketDecoder {
+    pub const fn new(wheel: bool) -> Self {
+        PacketDecoder { bytes: [0; 4], len: 0, size: if wheel { 4 } else { 3 } }
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<Packet> {
+        // O 1º byte sempre tem o bit 3 ligado: sem ele o fluxo está fora de
+        // sincronia e o byte é descartado até achar um início válido
+        if self.len == 0 && byte & 0x08 == 0 {
+            return None;
+        }
+        self.bytes[self.len] = byte;
+        self.len += 1;
+        if self.len < self.size {
+            return None;
+        }
+        self.len = 0;
+        let [flags, x, y, z] = self.bytes;
+        // Overflow em X ou Y: o deslocamento não vale
+        if flags & 0xC0 != 0 {
+            return None;
+        }
+        // Bits 4 e 5 são o 9º bit (sinal) de X e Y
+        let dx = i16::from(x) - (i16::from(flags) << 4 & 0x100);
+        let dy = i16::from(y) - (i16::from(flags) << 3 & 0x100);
+        // Roda: 4 bits com sinal
+        let wheel = if self.size == 4 { ((z << 4) as i8) >> 4 } else { 0 };
+        Some(Packet { buttons: flags & 0x07, dx, dy, wheel })
+    }
+}
+
+// --- Console ---
+
+// Posição em contagens: 8 por coluna e 16 por linha, como os pixels de uma
+// célula de texto
+const COUNTS_PER_COL: i32 = 8;
+const COUNTS_PER_ROW: i32 = 16;
+
+/// Cursor, seleção e área de transferência do console VGA.
+struct Console {
+    x: i32,
+    y: i32,
+    /// Célula onde o botão esquerdo desceu (seleção em andamento)
+    anchor: Option<usize>,
+    clipboard: String,
+}
+
+impl Console {
+    fn cell(&self) -> (usize, usize) {
+        ((self.x / COUNTS_PER_COL) as usize, (self.y / COUNTS_PER_ROW) as usize)
+    }
+
+    fn handle(&mut self, packet: &Packet, previous: u8) {
+        self.x = (self.x + i32::from(packet.dx)).clamp(0, 80 * COUNTS_PER_COL - 1);
+        self.y = (self.y - i32::from(packet.dy)).clamp(0, 25 * COUNTS_PER_ROW - 1);
+        let (col, row) = self.cell();
+        let index = row * 80 + col;
+        let pressed = packet.buttons & !previous;
+        let released = previous & !packet.buttons;
+
+        let mut writer = crate::vga::WRITER.lock();
+        writer.set_mouse_cursor(Some((col, row)));
+        if pressed & Button::Left.mask() != 0 {
+            self.anchor = Some(index);
+            writer.set_selection(None);
+        } else if let Some(anchor) = self.anchor.filter(|&anchor| anchor != index) {
+            writer.set_selection(Some((anchor, index)));
+        }
+        if released & Button::Left.mask() != 0 {
+            // Um clique sem arrastar não copia nada
+            if let Some(anchor) = self.anchor.take().filter(|&anchor| anchor != index) {
+                self.clipboard = writer.text(anchor.min(index), anchor.max(index));
+            }
+        }
+        drop(writer);
+
+        if pressed & (Button::Right.mask() | Button::Middle.mask()) != 0 && !self.clipboard.is_empty() {
+            crate::keyboard::paste(&self.clipboard);
+        }
+    }
+}
+
+// --- Driver ---
+
+struct Driver {
+    decoder: PacketDecoder,
+    buttons: u8,
+    events: Ring<MouseEvent, 64>,
+    console: Console,
+}
+
+static DRIVER: IrqSpinlock<Driver> = IrqSpinlock::new(Driver {
+    decoder: PacketDecoder::new(false),
+    buttons: 0,
+    events: Ring::new(),
+    console: Console {
+        x: 40 * COUNTS_PER_COL,
+        y: 12 * COUNTS_PER_ROW,
+        anchor: None,
+        clipboard: String::new(),
+    },
+});
+
+// Threads esperando evento (acordadas pela IRQ 12)
+static MOUSE_WAITERS: WaitQueue = WaitQueue::new();
+
+pub fn init() {
+    if !ps2::has_second_port() {
+        crate::klog!("Mouse: 8042 sem segunda porta");
+        return;
+    }
+    match bring_up() {
+        Ok(wheel) => {
+            let mut driver = DRIVER.lock();
+            driver.decoder = PacketDecoder::new(wheel);
+            crate::vga::WRITER.lock().set_mouse_cursor(Some(driver.console.cell()));
+            drop(driver);
+            crate::interrupts::unmask_irq(IRQ);
+            crate::klog!("Mouse: PS/2 {} na IRQ {}", if wheel { "IntelliMouse (roda)" } else { "3 botões" }, IRQ);
+        }
+        Err(err) => crate::klog!("Mouse: {}", err),
+    }
+}
+
+/// Reset, detecção do IntelliMouse e IRQ ligada, por polling. Devolve se
+/// o mouse manda pacotes de 4 bytes.
+fn bring_up() -> Result<bool, &'static str> {
+    ps2::enable_port(Channel::Second)?;
+    ps2::send(Channel::Second, CMD_RESET)?;
+    match ps2::read_from(Channel::Second, ps2::TIMEOUT_MS * 20) {
+        Some(SELF_TEST_PASSED) => {}
+        Some(_) => return Err("autoteste do mouse falhou"),
+        None => return Err("mouse não terminou o reset"),
+    }
+    // ID que segue o autoteste (0x00)
+    let _ = ps2::read_from(Channel::Second, ps2::TIMEOUT_MS);
+    ps2::send(Channel::Second, CMD_SET_DEFAULTS)?;
+
+    // Sequência mágica do IntelliMouse: taxas 200, 100, 80 e o ID vira 3
+    for rate in [200, 100, 80] {
+        ps2::send(Channel::Second, CMD_SET_SAMPLE_RATE)?;
+        ps2::send(Channel::Second, rate)?;
+    }
+    ps2::send(Channel::Second, CMD_GET_ID)?;
+    let id = ps2::read_from(Channel::Second, ps2::TIMEOUT_MS).ok_or("mouse não devolveu o ID")?;
+    ps2::send(Channel::Second, CMD_SET_SAMPLE_RATE)?;
+    ps2::send(Channel::Second, 100)?;
+
+    ps2::send(Channel::Second, CMD_ENABLE_REPORTING)?;
+    ps2::enable_irq(Channel::Second)?;
+    Ok(matches!(id, ID_INTELLIMOUSE | ID_EXPLORER))
+}
+
+/// Chamado pela IRQ 12 com cada byte lido da porta 0x60.
+pub fn add_byte(byte: u8) {
+    let mut guard = DRIVER.lock();
+    let driver = &mut *guard;
+    let Some(packet) = driver.decoder.feed(byte) else { return };
+    let previous = core::mem::replace(&mut driver.buttons, packet.buttons);
+
+    if packet.dx != 0 || packet.dy != 0 {
+        driver.events.push_overwrite(MouseEvent::Move { dx: packet.dx, dy: packet.dy });
+    }
+    for button in Button::ALL {
+        if (packet.buttons ^ previous) & button.mask() != 0 {
+            let pressed = packet.buttons & button.mask() != 0;
+            driver.events.push_overwrite(MouseEvent::Button { button, pressed });
+        }
+    }
+    if packet.wheel != 0 {
+        driver.events.push_overwrite(MouseEvent::Scroll(packet.wheel));
+    }
+    driver.console.handle(&packet, previous);
+    drop(guard);
+    MOUSE_WAITERS.wake_all();
+}
+
+/// Próximo evento, sem bloquear.
+pub fn next_event() -> Option<MouseEvent> {
+    DRIVER.lock().events.pop()
+}
+
+/// Bloqueia a thread até o próximo evento do mouse.
+pub fn wait_event() -> MouseEvent {
+    let mut event = None;
+    MOUSE_WAITERS.wait_until(|| {
+        event = next_event();
+        event.is_some()
+    });
+    event.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_standard_packets() {
+        let mut decoder = PacketDecoder::new(false);
+        // Esquerdo preso, dx = +5, dy = -3 (sinal de Y no bit 5)
+        assert_eq!(decoder.feed(0x29), None);
+        assert_eq!(decoder.feed(0x05), None);
+        assert_eq!(decoder.feed(0xFD), Some(Packet { buttons: 1, dx: 5, dy: -3, wheel: 0 }));
+        // Fora de sincronia: bytes sem o bit 3 são pulados
+        assert_eq!(decoder.feed(0x00), None);
+        assert_eq!(decoder.feed(0x18), None);
+        assert_eq!(decoder.feed(0xFF), None);
+        assert_eq!(decoder.feed(0x01), Some(Packet { buttons: 0, dx: -1, dy: 1, wheel: 0 }));
+        // Overflow descarta o pacote
+        for byte in [0x48, 0x10, 0x10] {
+            assert_eq!(decoder.feed(byte), None);
+        }
+    }
+
+    #[test]
+    fn decodes_wheel_packets() {
+        let mut decoder = PacketDecoder::new(true);
+        let packets: alloc::vec::Vec<_> = [0x0C, 0, 0, 0x01, 0x08, 0, 0, 0x0F]
+            .iter().filter_map(|&b| decoder.feed(b)).collect();
+        assert_eq!(packets, [
+            Packet { buttons: 4, dx: 0, dy: 0, wheel: 1 },
+            Packet { buttons: 0, dx: 0, dy: 0, wheel: -1 },
+        ]);
+    }
+}
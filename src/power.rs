@@ -0,0 +1,222 @@
+// src/power.rs
+// ====================
+// ENERGIA - poweroff (ACPI S5), reboot e desligamento ordenado
+// ====================
+
+use core::hint::spin_loop;
+use core::ptr;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+use x86_64::instructions::tables::lidt;
+use x86_64::structures::DescriptorTablePointer;
+use x86_64::{PhysAddr, VirtAddr};
+use crate::acpi::{self, Fadt, GenericAddress};
+use crate::klog;
+
+// PM1x_CNT
+const SCI_EN: u16 = 1 << 0;
+const SLP_TYP_SHIFT: u16 = 10;
+const SLP_EN: u16 = 1 << 13;
+
+// Opcodes AML usados no pacote \_S5_
+const AML_NAME_OP: u8 = 0x08;
+const AML_ROOT_PREFIX: u8 = b'\\';
+const AML_PACKAGE_OP: u8 = 0x12;
+const AML_ZERO_OP: u8 = 0x00;
+const AML_ONE_OP: u8 = 0x01;
+const AML_BYTE_PREFIX: u8 = 0x0A;
+
+// Atalhos de emulador: QEMU (-M q35/piix novo), Bochs/QEMU antigo, VirtualBox
+const EMULATOR_POWEROFF: [(u16, u16); 3] = [(0x604, 0x2000), (0xB004, 0x2000), (0x4004, 0x3400)];
+
+const KBC_STATUS: u16 = 0x64;
+const KBC_INPUT_FULL: u8 = 1 << 1;
+const KBC_PULSE_RESET: u8 = 0xFE;
+
+// Espera ocupada curta entre tentativas (interrupções já desligadas)
+const SETTLE_SPINS: u32 = 10_000_000;
+
+fn settle() {
+    for _ in 0..SETTLE_SPINS {
+        spin_loop();
+    }
+}
+
+/// Desligamento ordenado: grava o log, desmonta o FS e desliga as IRQs.
+fn shutdown(action: &str) {
+    klog!("Shutdown: {}", action);
+    if let Err(err) = crate::log::flush() {
+        klog!("Shutdown: log não gravado ({})", err);
+    }
+    crate::virtual_fs::unmount();
+    interrupts::disable();
+}
+
+/// Para a CPU depois do desligamento ordenado (sem desligar a máquina).
+pub fn halt() -> ! {
+    shutdown("halt");
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+// --- poweroff ---
+
+fn aml_integer(bytes: &[u8], i: usize) -> Option<(u8, usize)> {
+    match *bytes.get(i)? {
+        AML_ZERO_OP => Some((0, i + 1)),
+        AML_ONE_OP => Some((1, i + 1)),
+        AML_BYTE_PREFIX => Some((*bytes.get(i + 1)?, i + 2)),
+        _ => None,
+    }
+}
+
+/// Valores SLP_TYPa/SLP_TYPb do objeto `Name(\_S5_, Package(){a, b, ...})`
+/// no DSDT. Só entende a forma simples que todo firmware usa.
+fn parse_s5(dsdt: &[u8]) -> Option<(u8, u8)> {
+    dsdt.windows(4).enumerate().filter(|(_, w)| w == b"_S5_").find_map(|(pos, _)| {
+        let named = match pos {
+            0 => false,
+            1 => dsdt[0] == AML_NAME_OP,
+            _ => dsdt[pos - 1] == AML_NAME_OP
+                || (dsdt[pos - 1] == AML_ROOT_PREFIX && dsdt[pos - 2] == AML_NAME_OP),
+        };
+        let mut i = pos + 4;
+        if !named || *dsdt.get(i)? != AML_PACKAGE_OP {
+            return None;
+        }
+        // PkgLength: bits 6-7 do primeiro byte dizem quantos bytes seguem
+        i += 1;
+        i += ((*dsdt.get(i)? >> 6) & 0b11) as usize + 1;
+        i += 1; // NumElements
+        let (typ_a, i) = aml_integer(dsdt, i)?;
+        let (typ_b, _) = aml_integer(dsdt, i)?;
+        Some((typ_a, typ_b))
+    })
+}
+
+// Passa o chipset para o modo ACPI via SMI_CMD, se ainda estiver em modo legado
+fn enable_acpi(fadt: &Fadt) {
+    let mut pm1a = Port::<u16>::new(fadt.pm1a_control as u16);
+    if unsafe { pm1a.read() } & SCI_EN != 0 || fadt.smi_command == 0 || fadt.acpi_enable == 0 {
+        return;
+    }
+    unsafe { Port::<u8>::new(fadt.smi_command as u16).write(fadt.acpi_enable); }
+    for _ in 0..SETTLE_SPINS {
+        if unsafe { pm1a.read() } & SCI_EN != 0 {
+            return;
+        }
+        spin_loop();
+    }
+    klog!("Poweroff: SCI_EN não ligou após ACPI_ENABLE");
+}
+
+fn acpi_poweroff() -> Result<(), &'static str> {
+    let fadt = acpi::fadt().ok_or("FADT não encontrado")?;
+    if fadt.pm1a_control == 0 {
+        return Err("FADT sem PM1a_CNT");
+    }
+    let dsdt = acpi::find_table(b"DSDT").ok_or("DSDT não encontrado")?;
+    let (typ_a, typ_b) = parse_s5(acpi::table_bytes(dsdt)).ok_or("DSDT sem \\_S5_")?;
+
+    enable_acpi(&fadt);
+    unsafe {
+        let mut pm1a = Port::<u16>::new(fadt.pm1a_control as u16);
+        let value = pm1a.read() & !(0b111 << SLP_TYP_SHIFT);
+        pm1a.write(value | (typ_a as u16) << SLP_TYP_SHIFT | SLP_EN);
+        if fadt.pm1b_control != 0 {
+            let mut pm1b = Port::<u16>::new(fadt.pm1b_control as u16);
+            let value = pm1b.read() & !(0b111 << SLP_TYP_SHIFT);
+            pm1b.write(value | (typ_b as u16) << SLP_TYP_SHIFT | SLP_EN);
+        }
+    }
+    settle();
+    Err("S5 não desligou a máquina")
+}
+
+/// Desliga a máquina: ACPI S5 pelo PM1a/PM1b, depois as portas de emulador.
+pub fn poweroff() -> ! {
+    shutdown("poweroff");
+    if let Err(err) = acpi_poweroff() {
+        klog!("Poweroff: ACPI falhou ({}), tentando portas de emulador", err);
+    }
+    for &(port, value) in EMULATOR_POWEROFF.iter() {
+        unsafe { Port::<u16>::new(port).write(value); }
+        settle();
+    }
+    klog!("Poweroff: nada funcionou, parando a CPU");
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+// --- reboot ---
+
+fn acpi_reset(reg: GenericAddress, value: u8) {
+    match reg.address_space {
+        acpi::GAS_SYSTEM_IO => unsafe { Port::<u8>::new(reg.address as u16).write(value) },
+        acpi::GAS_SYSTEM_MEMORY => {
+            if let Ok(addr) = crate::memory::map_mmio(PhysAddr::new(reg.address), 1) {
+                unsafe { ptr::write_volatile(addr.as_mut_ptr::<u8>(), value) }
+            }
+        }
+        // Espaço de configuração PCI: ainda sem driver
+        space => klog!("Reboot: reset ACPI no espaço {} não suportado", space),
+    }
+}
+
+// Pulso na linha de reset da CPU pelo controlador de teclado
+fn kbc_reset() {
+    let mut kbc = Port::<u8>::new(KBC_STATUS);
+    for _ in 0..SETTLE_SPINS {
+        if unsafe { kbc.read() } & KBC_INPUT_FULL == 0 {
+            break;
+        }
+        spin_loop();
+    }
+    unsafe { kbc.write(KBC_PULSE_RESET) }
+}
+
+// IDT vazia + exceção: #BP -> #DF -> triple fault, e a CPU reinicia
+fn triple_fault() -> ! {
+    let empty = DescriptorTablePointer { limit: 0, base: VirtAddr::new(0) };
+    unsafe {
+        lidt(&empty);
+        core::arch::asm!("int3", options(noreturn));
+    }
+}
+
+/// Reinicia: registrador de reset do ACPI, 8042 e, por último, triple fault.
+pub fn reboot() -> ! {
+    shutdown("reboot");
+    if let Some((reg, value)) = acpi::fadt().and_then(|fadt| fadt.reset) {
+        acpi_reset(reg, value);
+        settle();
+        klog!("Reboot: reset ACPI não funcionou");
+    }
+    kbc_reset();
+    settle();
+    klog!("Reboot: 8042 não funcionou, forçando triple fault");
+    triple_fault()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s5_qemu() {
+        // Trecho do DSDT do QEMU: Name (_S5, Package (0x04) { Zero, Zero, Zero, Zero })
+        let aml = [0x10, 0x08, AML_NAME_OP, b'_', b'S', b'5', b'_', AML_PACKAGE_OP, 0x06, 0x04, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(parse_s5(&aml), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_parse_s5_byte_prefix_and_root() {
+        let aml = [AML_NAME_OP, AML_ROOT_PREFIX, b'_', b'S', b'5', b'_', AML_PACKAGE_OP, 0x08, 0x02,
+            AML_BYTE_PREFIX, 0x07, AML_BYTE_PREFIX, 0x05];
+        assert_eq!(parse_s5(&aml), Some((7, 5)));
+        // Referência ao _S5_ sem NameOp (ex.: dentro de um método) é ignorada
+        assert_eq!(parse_s5(b"\x70_S5_\x12\x06\x04\x00\x00"), None);
+    }
+}
@@ -0,0 +1,443 @@
+// src/process.rs
+// ====================
+// PROCESSOS - PID, pai/filhos, descritores, cwd, ambiente, zumbis e wait
+// ====================
+//
+// Cada processo de usuário tem uma thread (ring 3) e um espaço de
+// endereçamento. O PID 1 é o shell, que roda na thread do boot. Um processo
+// que termina vira zumbi até o pai buscar o status com `wait`; órfãos são
+// adotados pelo PID 1, e os que já eram zumbis são recolhidos na hora.
+//
+// A tabela é um `IrqSpinlock`: o handler do timer consulta o pedido de
+// `kill` quando interrompe o ring 3.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::scheduler::{self, ThreadId};
+use crate::sync::{IrqSpinlock, WaitQueue};
+
+pub type Pid = u64;
+
+pub const INIT_PID: Pid = 1;
+
+pub const SIGKILL: u8 = 9;
+pub const SIGSEGV: u8 = 11;
+
+const MAX_FDS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Exited(i32),
+    Signaled(u8),
+}
+
+impl ExitStatus {
+    /// Codificação do `wait4`: código << 8 ou o número do sinal.
+    pub fn wait_status(self) -> i32 {
+        match self {
+            ExitStatus::Exited(code) => (code & 0xFF) << 8,
+            ExitStatus::Signaled(signal) => i32::from(signal & 0x7F),
+        }
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExitStatus::Exited(code) => write!(f, "saiu com código {}", code),
+            ExitStatus::Signaled(signal) => write!(f, "morto pelo sinal {}", signal),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitError {
+    /// Nenhum filho que combine com o pedido
+    NoChild,
+    /// Quem esperava recebeu `kill`
+    Interrupted,
+}
+
+impl fmt::Display for WaitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WaitError::NoChild => write!(f, "nenhum filho com esse PID"),
+            WaitError::Interrupted => write!(f, "interrompido"),
+        }
+    }
+}
+
+pub enum FileDescriptor {
+    Console,
+    File { data: Vec<u8>, offset: usize },
+}
+
+struct Process {
+    name: String,
+    parent: Pid,
+    children: Vec<Pid>,
+    // None para o PID 1, que é a thread do boot e nunca termina
+    thread: Option<ThreadId>,
+    // Mantém o espaço vivo enquanto o processo existir (a thread tem outra cópia)
+    _address_space: Option<Arc<crate::memory::AddressSpace>>,
+    fds: Vec<Option<FileDescriptor>>,
+    cwd: String,
+    env: Vec<String>,
+    // Some: zumbi esperando o wait do pai
+    status: Option<ExitStatus>,
+    // Sinal pendente (`kill` ou exceção no ring 3), atendido antes de voltar
+    // ao ring 3
+    killed: Option<u8>,
+}
+
+struct ProcessTable {
+    processes: BTreeMap<Pid, Process>,
+    by_thread: BTreeMap<ThreadId, Pid>,
+    next_pid: Pid,
+}
+
+static PROCESSES: IrqSpinlock<ProcessTable> = IrqSpinlock::new(ProcessTable {
+    processes: BTreeMap::new(),
+    by_thread: BTreeMap::new(),
+    next_pid: INIT_PID,
+});
+
+// Acordada a cada processo que termina (ou recebe kill)
+static EXITS: WaitQueue = WaitQueue::new();
+
+// Processo que o shell espera em primeiro plano (0 = nenhum): é o que o
+// Ctrl+C mata
+static FOREGROUND: AtomicU64 = AtomicU64::new(0);
+
+fn console_fds() -> Vec<Option<FileDescriptor>> {
+    let mut fds = Vec::new();
+    fds.resize_with(3, || Some(FileDescriptor::Console));
+    fds
+}
+
+/// Registra o fluxo atual (a thread do shell) como PID 1.
+pub fn init(name: &str) {
+    let thread = scheduler::current();
+    let mut table = PROCESSES.lock();
+    let pid = table.next_pid;
+    table.next_pid += 1;
+    table.processes.insert(pid, Process {
+        name: String::from(name),
+        parent: pid,
+        children: Vec::new(),
+        thread: None,
+        _address_space: None,
+        fds: console_fds(),
+        cwd: String::from("/"),
+        env: crate::config::exports(),
+        status: None,
+        killed: None,
+    });
+    table.by_thread.insert(thread, pid);
+}
+
+/// PID do processo dono da thread atual (None em threads do kernel).
+pub fn current_pid() -> Option<Pid> {
+    let thread = scheduler::current();
+    PROCESSES.lock().by_thread.get(&thread).copied()
+}
+
+pub fn pid_of_thread(thread: ThreadId) -> Option<Pid> {
+    PROCESSES.lock().by_thread.get(&thread).copied()
+}
+
+pub fn parent_of(pid: Pid) -> Option<Pid> {
+    PROCESSES.lock().processes.get(&pid).map(|p| p.parent)
+}
+
+/// Normaliza `path` relativo a `cwd` (".", ".." e barras repetidas).
+pub fn resolve(cwd: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    let base = if path.starts_with('/') { "" } else { cwd };
+    for part in base.split('/').chain(path.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => { parts.pop(); }
+            part => parts.push(part),
+        }
+    }
+    let mut resolved = String::new();
+    for part in parts {
+        resolved.push('/');
+        resolved.push_str(part);
+    }
+    if resolved.is_empty() {
+        resolved.push('/');
+    }
+    resolved
+}
+
+/// Carrega `path` (relativo ao cwd do pai) e cria um filho de `parent`,
+/// herdando cwd e ambiente. `argv[0]` é o caminho resolvido.
+pub fn spawn(parent: Pid, path: &str, args: &[&str]) -> Result<Pid, &'static str> {
+    let (cwd, env) = {
+        let table = PROCESSES.lock();
+        let parent = table.processes.get(&parent).ok_or("pai inexistente")?;
+        (parent.cwd.clone(), parent.env.clone())
+    };
+    let path = resolve(&cwd, path);
+    let data = crate::virtual_fs::read_file(&path).ok_or("arquivo não encontrado")?;
+    let mut argv = alloc::vec![path.as_str()];
+    argv.extend_from_slice(args);
+    let envp: Vec<&str> = env.iter().map(String::as_str).collect();
+    let (space, entry, rsp) = crate::elf::load(&data, &argv, &envp)?;
+
+    let space = Arc::new(space);
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    let thread = scheduler::spawn_user(name, space.clone(), entry, rsp)?;
+    let pid = {
+        let mut table = PROCESSES.lock();
+        let pid = table.next_pid;
+        table.next_pid += 1;
+        table.processes.insert(pid, Process {
+            name: String::from(name),
+            parent,
+            children: Vec::new(),
+            thread: Some(thread),
+            _address_space: Some(space),
+            fds: console_fds(),
+            cwd,
+            env,
+            status: None,
+            killed: None,
+        });
+        table.by_thread.insert(thread, pid);
+        if let Some(parent) = table.processes.get_mut(&parent) {
+            parent.children.push(pid);
+        }
+        pid
+    };
+    // A thread nasceu bloqueada: só roda com o processo já registrado
+    scheduler::unblock(thread);
+    Ok(pid)
+}
+
+/// Termina o processo atual: fecha os descritores, entrega os filhos ao
+/// PID 1 e vira zumbi até o `wait` do pai.
+pub fn exit(status: ExitStatus) -> ! {
+    let thread = scheduler::current();
+    let mut reaped = Vec::new();
+    {
+        let mut table = PROCESSES.lock();
+        let Some(pid) = table.by_thread.remove(&thread) else {
+            drop(table);
+            scheduler::exit();
+        };
+        let process = table.processes.get_mut(&pid).unwrap();
+        process.status = Some(status);
+        process.fds.clear();
+        let children = core::mem::take(&mut process.children);
+        crate::klog!("processo {} ({}) {}", pid, process.name, status);
+        for child in children {
+            reaped.extend(table.adopt(child));
+        }
+    }
+    // Fora do lock: soltar um espaço de endereçamento pega outros locks
+    drop(reaped);
+    EXITS.wake_all();
+    scheduler::exit()
+}
+
+impl ProcessTable {
+    // Órfão vai para o PID 1; se já for zumbi, é recolhido na hora
+    fn adopt(&mut self, child: Pid) -> Option<Process> {
+        let process = self.processes.get_mut(&child)?;
+        process.parent = INIT_PID;
+        if process.status.is_some() {
+            return self.processes.remove(&child);
+        }
+        if let Some(init) = self.processes.get_mut(&INIT_PID) {
+            init.children.push(child);
+        }
+        None
+    }
+}
+
+/// Espera um filho de `parent` terminar (`pid` = None: qualquer um) e
+/// recolhe o zumbi. Com `nohang`, volta `Ok(None)` se ninguém terminou ainda.
+pub fn wait(parent: Pid, pid: Option<Pid>, nohang: bool) -> Result<Option<(Pid, ExitStatus)>, WaitError> {
+    let mut result = Err(WaitError::NoChild);
+    let mut reaped = None;
+    EXITS.wait_until(|| {
+        let mut table = PROCESSES.lock();
+        let Some(me) = table.processes.get(&parent) else {
+            return true;
+        };
+        if me.killed.is_some() {
+            result = Err(WaitError::Interrupted);
+            return true;
+        }
+        let candidates: Vec<Pid> = me.children.iter().copied()
+            .filter(|&child| pid.is_none_or(|wanted| wanted == child))
+            .collect();
+        if candidates.is_empty() {
+            result = Err(WaitError::NoChild);
+            return true;
+        }
+        let zombie = candidates.into_iter()
+            .find_map(|child| table.processes[&child].status.map(|status| (child, status)));
+        match zombie {
+            Some((child, status)) => {
+                reaped = table.processes.remove(&child);
+                table.processes.get_mut(&parent).unwrap().children.retain(|&c| c != child);
+                result = Ok(Some((child, status)));
+                true
+            }
+            None if nohang => {
+                result = Ok(None);
+                true
+            }
+            None => false,
+        }
+    });
+    drop(reaped);
+    result
+}
+
+/// Marca o processo para morrer: ele termina com SIGKILL na próxima vez que
+/// voltaria ao ring 3 (fim de syscall ou tick do timer).
+pub fn kill(pid: Pid) -> Result<(), &'static str> {
+    if pid == INIT_PID {
+        return Err("o PID 1 não pode ser morto");
+    }
+    let thread = {
+        let mut table = PROCESSES.lock();
+        let process = table.processes.get_mut(&pid).ok_or("processo inexistente")?;
+        if process.status.is_some() {
+            return Err("processo já terminou");
+        }
+        process.killed.get_or_insert(SIGKILL);
+        process.thread
+    };
+    // Tira a thread de um sono ou espera para ela ver o pedido
+    if let Some(thread) = thread {
+        scheduler::unblock(thread);
+    }
+    EXITS.wake_all();
+    Ok(())
+}
+
+/// Marca (ou limpa) o processo em primeiro plano.
+pub fn set_foreground(pid: Option<Pid>) {
+    FOREGROUND.store(pid.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Ctrl+C no console: `kill` no processo em primeiro plano, se houver.
+/// Chamado pelas ISRs de entrada.
+pub fn interrupt_foreground() {
+    let pid = FOREGROUND.load(Ordering::Relaxed);
+    if pid != 0 {
+        let _ = kill(pid);
+    }
+}
+
+/// Sinal pendente do processo atual.
+fn pending_signal() -> Option<u8> {
+    let thread = scheduler::current();
+    let table = PROCESSES.lock();
+    table.by_thread.get(&thread)
+        .and_then(|pid| table.processes.get(pid))
+        .and_then(|process| process.killed)
+}
+
+/// O processo atual recebeu `kill`?
+pub fn is_killed() -> bool {
+    pending_signal().is_some()
+}
+
+/// Exceção no ring 3: o processo atual morre com `signal` (um `kill` que
+/// já estava pendente prevalece).
+pub fn mark_killed(signal: u8) {
+    let thread = scheduler::current();
+    let mut table = PROCESSES.lock();
+    if let Some(pid) = table.by_thread.get(&thread).copied() {
+        table.processes.get_mut(&pid).unwrap().killed.get_or_insert(signal);
+    }
+}
+
+/// Chamado antes de voltar ao ring 3, em contexto de thread: atende um
+/// sinal pendente.
+pub fn check_killed() {
+    if let Some(signal) = pending_signal() {
+        exit(ExitStatus::Signaled(signal));
+    }
+}
+
+/// Fim de um processo marcado, para quando não há como voltar ao ring 3
+/// (ver `interrupts::exit_on_return`).
+pub fn exit_killed() -> ! {
+    exit(ExitStatus::Signaled(pending_signal().unwrap_or(SIGKILL)))
+}
+
+// --- Descritores, cwd e ambiente do processo atual ---
+
+/// Roda `f` sobre a tabela de descritores do processo atual (None fora de
+/// um processo). O lock desliga as interrupções: nada de dormir em `f`.
+pub fn with_fds<R>(f: impl FnOnce(&mut Vec<Option<FileDescriptor>>) -> R) -> Option<R> {
+    let thread = scheduler::current();
+    let mut table = PROCESSES.lock();
+    let pid = *table.by_thread.get(&thread)?;
+    table.processes.get_mut(&pid).map(|process| f(&mut process.fds))
+}
+
+/// Menor descritor livre, ou None se a tabela estiver cheia.
+pub fn install_fd(fds: &mut Vec<Option<FileDescriptor>>, descriptor: FileDescriptor) -> Option<usize> {
+    match fds.iter().position(Option::is_none) {
+        Some(fd) => {
+            fds[fd] = Some(descriptor);
+            Some(fd)
+        }
+        None if fds.len() < MAX_FDS => {
+            fds.push(Some(descriptor));
+            Some(fds.len() - 1)
+        }
+        None => None,
+    }
+}
+
+pub fn cwd(pid: Pid) -> Option<String> {
+    PROCESSES.lock().processes.get(&pid).map(|p| p.cwd.clone())
+}
+
+/// Troca o diretório atual; `path` pode ser relativo.
+pub fn chdir(pid: Pid, path: &str) -> Result<(), &'static str> {
+    let cwd = cwd(pid).ok_or("processo inexistente")?;
+    let target = resolve(&cwd, path);
+    if !crate::virtual_fs::is_dir(&target) {
+        return Err("diretório não existe");
+    }
+    if let Some(process) = PROCESSES.lock().processes.get_mut(&pid) {
+        process.cwd = target;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_handles_relative_and_dots() {
+        assert_eq!(resolve("/", "bin/hello"), "/bin/hello");
+        assert_eq!(resolve("/etc", "../bin//hello"), "/bin/hello");
+        assert_eq!(resolve("/etc", "/bin/./hello"), "/bin/hello");
+        assert_eq!(resolve("/", "../.."), "/");
+        assert_eq!(resolve("/bin", "."), "/bin");
+    }
+
+    #[test]
+    fn wait_status_encoding() {
+        assert_eq!(ExitStatus::Exited(3).wait_status(), 0x300);
+        assert_eq!(ExitStatus::Exited(-1).wait_status(), 0xFF00);
+        assert_eq!(ExitStatus::Signaled(SIGKILL).wait_status(), 9);
+    }
+}
@@ -0,0 +1,263 @@
+// src/ps2.rs
+// ====================
+// CONTROLADOR PS/2 (8042) - Inicialização, byte de configuração, autotestes
+// e comandos para os dispositivos das duas portas
+// ====================
+//
+// Sequência do `init` (OSDev / IBM PS/2 Technical Reference):
+// 1. desliga as duas portas e esvazia o buffer de saída
+// 2. tira IRQs e tradução do byte de configuração (tudo por polling)
+// 3. autoteste do controlador (0xAA -> 0x55) e regrava a configuração, que
+//    alguns chipsets zeram no teste
+// 4. descobre se há segunda porta e testa as interfaces (0xAB/0xA9 -> 0x00)
+// 5. religa o clock da primeira porta
+// As IRQs ficam desligadas: quem dirige cada dispositivo liga a sua com
+// `enable_irq` depois de configurá-lo.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::port::Port;
+
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64;
+const COMMAND_PORT: u16 = 0x64;
+
+// Bits do status
+const STATUS_OUTPUT_FULL: u8 = 0x01;
+const STATUS_INPUT_FULL: u8 = 0x02;
+// O byte no buffer de saída veio da segunda porta
+const STATUS_AUX_DATA: u8 = 0x20;
+
+// Comandos do controlador
+const CMD_READ_CONFIG: u8 = 0x20;
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_DISABLE_SECOND: u8 = 0xA7;
+const CMD_ENABLE_SECOND: u8 = 0xA8;
+const CMD_TEST_SECOND: u8 = 0xA9;
+const CMD_SELF_TEST: u8 = 0xAA;
+const CMD_TEST_FIRST: u8 = 0xAB;
+const CMD_DISABLE_FIRST: u8 = 0xAD;
+const CMD_ENABLE_FIRST: u8 = 0xAE;
+const CMD_WRITE_SECOND: u8 = 0xD4;
+
+// Bits do byte de configuração
+const CONFIG_IRQ_FIRST: u8 = 0x01;
+const CONFIG_IRQ_SECOND: u8 = 0x02;
+const CONFIG_CLOCK_SECOND_OFF: u8 = 0x20;
+const CONFIG_TRANSLATION: u8 = 0x40;
+
+const SELF_TEST_OK: u8 = 0x55;
+
+// Respostas dos dispositivos
+pub const ACK: u8 = 0xFA;
+pub const RESEND: u8 = 0xFE;
+
+/// Espera padrão por uma resposta do controlador ou do dispositivo.
+pub const TIMEOUT_MS: u64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    First,
+    Second,
+}
+
+static SECOND_PORT: AtomicBool = AtomicBool::new(false);
+
+/// Inicializa o controlador. `Err` quando ele ou a primeira porta falham;
+/// a segunda porta só é anotada (`has_second_port`) se passar no teste.
+pub fn init() -> Result<(), &'static str> {
+    command(CMD_DISABLE_FIRST)?;
+    command(CMD_DISABLE_SECOND)?;
+    flush();
+
+    let config = update_config(CONFIG_IRQ_FIRST | CONFIG_IRQ_SECOND | CONFIG_TRANSLATION, 0)?;
+
+    command(CMD_SELF_TEST)?;
+    match read(TIMEOUT_MS * 10) {
+        Some(SELF_TEST_OK) => {}
+        Some(_) => return Err("autoteste do 8042 falhou"),
+        None => return Err("8042 não respondeu ao autoteste"),
+    }
+    write_config(config)?;
+
+    // Com a segunda porta ligada o bit do clock dela tem que limpar
+    command(CMD_ENABLE_SECOND)?;
+    let dual = read_config()? & CONFIG_CLOCK_SECOND_OFF == 0;
+    command(CMD_DISABLE_SECOND)?;
+
+    if let Some(err) = test_port(CMD_TEST_FIRST)? {
+        return Err(err);
+    }
+    let second = dual && match test_port(CMD_TEST_SECOND)? {
+        None => true,
+        Some(err) => {
+            crate::klog!("PS/2: segunda porta: {}", err);
+            false
+        }
+    };
+    SECOND_PORT.store(second, Ordering::Relaxed);
+
+    enable_port(Channel::First)?;
+    crate::klog!("PS/2: 8042 OK, {}", if second { "duas portas" } else { "uma porta" });
+    Ok(())
+}
+
+pub fn has_second_port() -> bool {
+    SECOND_PORT.load(Ordering::Relaxed)
+}
+
+/// `None` se a interface passou; senão a causa (códigos do 0xAB/0xA9).
+fn test_port(cmd: u8) -> Result<Option<&'static str>, &'static str> {
+    command(cmd)?;
+    Ok(match read(TIMEOUT_MS).ok_or("8042 não respondeu ao teste de porta")? {
+        0x00 => None,
+        0x01 => Some("linha de clock presa em baixo"),
+        0x02 => Some("linha de clock presa em alto"),
+        0x03 => Some("linha de dados presa em baixo"),
+        0x04 => Some("linha de dados presa em alto"),
+        _ => Some("resposta inválida ao teste de porta"),
+    })
+}
+
+/// Caminho de emergência quando o `init` falha: liga a primeira porta com
+/// IRQ e tradução, como o firmware costuma deixar.
+pub fn enable_first_port_fallback() {
+    let _ = command(CMD_ENABLE_FIRST);
+    let _ = update_config(0, CONFIG_IRQ_FIRST | CONFIG_TRANSLATION);
+}
+
+/// Religa o clock da porta (o dispositivo volta a falar com o 8042).
+pub fn enable_port(channel: Channel) -> Result<(), &'static str> {
+    command(match channel {
+        Channel::First => CMD_ENABLE_FIRST,
+        Channel::Second => CMD_ENABLE_SECOND,
+    })
+}
+
+/// Liga ou desliga a tradução para o scancode set 1.
+pub fn set_translation(on: bool) -> Result<(), &'static str> {
+    let result = if on { update_config(0, CONFIG_TRANSLATION) } else { update_config(CONFIG_TRANSLATION, 0) };
+    result.map(|_| ())
+}
+
+pub fn enable_irq(channel: Channel) -> Result<(), &'static str> {
+    let bit = match channel {
+        Channel::First => CONFIG_IRQ_FIRST,
+        Channel::Second => CONFIG_IRQ_SECOND,
+    };
+    update_config(0, bit).map(|_| ())
+}
+
+/// Envia `byte` ao dispositivo da porta e espera o ACK (repete no RESEND).
+/// Só por polling: a IRQ da porta tem que estar desligada.
+pub fn send(channel: Channel, byte: u8) -> Result<(), &'static str> {
+    for _ in 0..3 {
+        write_device(channel, byte)?;
+        match read_from(channel, TIMEOUT_MS) {
+            Some(ACK) => return Ok(()),
+            Some(RESEND) => continue,
+            Some(_) => return Err("resposta inesperada do dispositivo"),
+            None => return Err("dispositivo não respondeu"),
+        }
+    }
+    Err("dispositivo pediu reenvio demais")
+}
+
+/// Escreve no dispositivo sem esperar resposta (para quem trata o ACK na
+/// IRQ, como os LEDs do teclado).
+pub fn write_device(channel: Channel, byte: u8) -> Result<(), &'static str> {
+    if channel == Channel::Second {
+        command(CMD_WRITE_SECOND)?;
+    }
+    write_data(byte)
+}
+
+/// Lê um byte do buffer de saída, esperando até `timeout_ms`.
+fn read(timeout_ms: u64) -> Option<u8> {
+    wait_status(timeout_ms, |status| status & STATUS_OUTPUT_FULL != 0)
+        .then(|| unsafe { Port::<u8>::new(DATA_PORT).read() })
+}
+
+/// Como `read`, mas só aceita bytes vindos da porta `channel`; os da outra
+/// porta ficam para a IRQ dela.
+pub fn read_from(channel: Channel, timeout_ms: u64) -> Option<u8> {
+    let aux = channel == Channel::Second;
+    wait_status(timeout_ms, |status| {
+        status & STATUS_OUTPUT_FULL != 0 && (status & STATUS_AUX_DATA != 0) == aux
+    })
+    .then(|| unsafe { Port::<u8>::new(DATA_PORT).read() })
+}
+
+// --- Acesso ao controlador ---
+
+fn status() -> u8 {
+    unsafe { Port::<u8>::new(STATUS_PORT).read() }
+}
+
+/// Espera `ready(status)` até `timeout_ms`. Com o relógio no PIT e as IRQs
+/// desligadas o tempo não anda, então o número de leituras também tem teto
+/// (cada `in` leva bem mais que 50 ns).
+fn wait_status(timeout_ms: u64, ready: impl Fn(u8) -> bool) -> bool {
+    let deadline = crate::clock::nanos() + timeout_ms * 1_000_000;
+    for _ in 0..timeout_ms * 20_000 {
+        if ready(status()) {
+            return true;
+        }
+        if crate::clock::nanos() >= deadline {
+            break;
+        }
+        core::hint::spin_loop();
+    }
+    ready(status())
+}
+
+/// Espera o buffer de entrada esvaziar (o 8042 pode levar alguns ms).
+fn wait_input_empty() -> Result<(), &'static str> {
+    if wait_status(TIMEOUT_MS, |status| status & STATUS_INPUT_FULL == 0) {
+        Ok(())
+    } else {
+        Err("8042 não esvaziou o buffer de entrada")
+    }
+}
+
+fn command(cmd: u8) -> Result<(), &'static str> {
+    wait_input_empty()?;
+    unsafe { Port::<u8>::new(COMMAND_PORT).write(cmd) };
+    Ok(())
+}
+
+fn write_data(byte: u8) -> Result<(), &'static str> {
+    wait_input_empty()?;
+    unsafe { Port::<u8>::new(DATA_PORT).write(byte) };
+    Ok(())
+}
+
+/// Descarta o que ficou no buffer de saída (teclas apertadas no boot).
+fn flush() {
+    for _ in 0..64 {
+        if status() & STATUS_OUTPUT_FULL == 0 {
+            return;
+        }
+        unsafe { Port::<u8>::new(DATA_PORT).read() };
+    }
+}
+
+fn read_config() -> Result<u8, &'static str> {
+    command(CMD_READ_CONFIG)?;
+    read(TIMEOUT_MS).ok_or("8042 não devolveu o byte de configuração")
+}
+
+fn write_config(config: u8) -> Result<(), &'static str> {
+    command(CMD_WRITE_CONFIG)?;
+    write_data(config)
+}
+
+/// Lê-modifica-grava o byte de configuração, com as IRQs da CPU desligadas
+/// para o handler do teclado não consumir a resposta no meio. Devolve o
+/// valor gravado.
+fn update_config(clear: u8, set: u8) -> Result<u8, &'static str> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let config = read_config()? & !clear | set;
+        write_config(config)?;
+        Ok(config)
+    })
+}
@@ -0,0 +1,62 @@
+// src/ring.rs
+// ====================
+// FILA CIRCULAR - Buffer de tamanho fixo para filas alimentadas por ISRs
+// ====================
+//
+// Sem alocação: cabe em `static` e pode ser usada dentro de um
+// `IrqSpinlock` tocado por handlers de interrupção.
+
+pub struct Ring<T: Copy, const N: usize> {
+    items: [Option<T>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> Ring<T, N> {
+    pub const fn new() -> Self {
+        Ring { items: [None; N], head: 0, len: 0 }
+    }
+
+    /// `false` (e o item descartado) com a fila cheia.
+    pub fn push(&mut self, item: T) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.items[(self.head + self.len) % N] = Some(item);
+        self.len += 1;
+        true
+    }
+
+    /// Com a fila cheia descarta o mais antigo: sem leitor, ela guarda só
+    /// os itens mais recentes.
+    pub fn push_overwrite(&mut self, item: T) {
+        if !self.push(item) {
+            self.pop();
+            self.push(item);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let item = self.items[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        item
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn free(&self) -> usize {
+        N - self.len
+    }
+}
+
+impl<T: Copy, const N: usize> Default for Ring<T, N> {
+    fn default() -> Self {
+        Ring::new()
+    }
+}
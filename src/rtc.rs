@@ -0,0 +1,270 @@
+// src/rtc.rs
+// ====================
+// RTC CMOS - Relógio de parede (data/hora) + IRQ 8 periódico
+// ====================
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use x86_64::instructions::port::Port;
+use x86_64::instructions::interrupts;
+use crate::timer;
+
+const CMOS_INDEX: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_CENTURY_DEFAULT: u8 = 0x32; // Padrão do PC; o FADT pode indicar outro
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+const REG_STATUS_C: u8 = 0x0C;
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 0x80;
+const STATUS_B_24H: u8 = 0x02;
+const STATUS_B_BINARY: u8 = 0x04;
+const STATUS_B_PERIODIC: u8 = 0x40;
+const HOUR_PM: u8 = 0x80;
+
+/// Taxa do IRQ 8: 32768 >> (RATE - 1) = 1024 Hz.
+const PERIODIC_RATE: u8 = 6;
+pub const PERIODIC_HZ: u64 = 32768 >> (PERIODIC_RATE - 1);
+
+// Instante do boot em ms Unix, amarrado ao tick do PIT em que foi lido
+static BOOT_EPOCH_MS: AtomicU64 = AtomicU64::new(0);
+static BOOT_TICK: AtomicU64 = AtomicU64::new(0);
+static PERIODIC_TICKS: AtomicU64 = AtomicU64::new(0);
+// 0 = sem registrador de século
+static CENTURY_REGISTER: AtomicU8 = AtomicU8::new(REG_CENTURY_DEFAULT);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Segundos desde 1970-01-01 00:00:00 UTC.
+    pub fn to_unix(self) -> u64 {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        days as u64 * 86400 + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64
+    }
+
+    pub fn from_unix(secs: u64) -> DateTime {
+        let (year, month, day) = civil_from_days((secs / 86400) as i64);
+        let rem = secs % 86400;
+        DateTime {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+// Algoritmos de calendário civil de Howard Hinnant (proléptico gregoriano)
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (if m <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, m, d)
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value & 0x0F) + (value >> 4) * 10
+}
+
+// Índice + dado sem interrupção no meio: o handler do IRQ 8 também mexe no índice
+fn read_register(reg: u8) -> u8 {
+    let mut index = Port::<u8>::new(CMOS_INDEX);
+    let mut data = Port::<u8>::new(CMOS_DATA);
+    interrupts::without_interrupts(|| unsafe {
+        index.write(reg);
+        data.read()
+    })
+}
+
+fn write_register(reg: u8, value: u8) {
+    let mut index = Port::<u8>::new(CMOS_INDEX);
+    let mut data = Port::<u8>::new(CMOS_DATA);
+    interrupts::without_interrupts(|| unsafe {
+        index.write(reg);
+        data.write(value);
+    })
+}
+
+fn update_in_progress() -> bool {
+    read_register(REG_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0
+}
+
+// Registradores crus, lidos fora de uma atualização do RTC
+fn read_raw() -> [u8; 7] {
+    while update_in_progress() {}
+    [
+        read_register(REG_SECONDS),
+        read_register(REG_MINUTES),
+        read_register(REG_HOURS),
+        read_register(REG_DAY),
+        read_register(REG_MONTH),
+        read_register(REG_YEAR),
+        match CENTURY_REGISTER.load(Ordering::Relaxed) {
+            0 => 0,
+            reg => read_register(reg),
+        },
+    ]
+}
+
+/// Lê o RTC direto do hardware. Repete a leitura até duas seguidas baterem,
+/// para não pegar uma virada de segundo no meio.
+pub fn read_rtc() -> DateTime {
+    let mut raw = read_raw();
+    loop {
+        let again = read_raw();
+        if again == raw {
+            break;
+        }
+        raw = again;
+    }
+    decode(raw, read_register(REG_STATUS_B))
+}
+
+fn decode(raw: [u8; 7], status_b: u8) -> DateTime {
+    let [mut second, mut minute, mut hour, mut day, mut month, mut year, mut century] = raw;
+    let pm = hour & HOUR_PM != 0;
+    hour &= !HOUR_PM;
+
+    if status_b & STATUS_B_BINARY == 0 {
+        second = bcd_to_binary(second);
+        minute = bcd_to_binary(minute);
+        hour = bcd_to_binary(hour);
+        day = bcd_to_binary(day);
+        month = bcd_to_binary(month);
+        year = bcd_to_binary(year);
+        century = bcd_to_binary(century);
+    }
+
+    // 12h: 12 AM = 0h, 12 PM = 12h
+    if status_b & STATUS_B_24H == 0 {
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    // Sem registrador de século confiável, assume 20xx
+    let century = if (19..=21).contains(&century) { century as u16 } else { 20 };
+    DateTime { year: century * 100 + year as u16, month, day, hour, minute, second }
+}
+
+/// Registrador CMOS do século indicado pelo FADT (0 = não existe).
+pub fn set_century_register(reg: u8) {
+    CENTURY_REGISTER.store(reg, Ordering::Relaxed);
+}
+
+/// Sincroniza com a virada de segundo do RTC (até 1 s de espera) e guarda
+/// a referência para `unix_time_ms`. Precisa do PIT já rodando.
+pub fn init() {
+    let start = read_rtc();
+    let mut now = start;
+    while now == start {
+        now = read_rtc();
+    }
+    BOOT_EPOCH_MS.store(now.to_unix() * 1000, Ordering::SeqCst);
+    BOOT_TICK.store(timer::ticks(), Ordering::SeqCst);
+}
+
+/// Liga o IRQ 8 periódico (segunda fonte de tempo, independente do PIT).
+pub fn enable_periodic() {
+    interrupts::without_interrupts(|| {
+        let rate = read_register(REG_STATUS_A) & 0xF0;
+        write_register(REG_STATUS_A, rate | PERIODIC_RATE);
+        let status_b = read_register(REG_STATUS_B);
+        write_register(REG_STATUS_B, status_b | STATUS_B_PERIODIC);
+        // Limpa qualquer interrupção pendente para o RTC voltar a disparar
+        read_register(REG_STATUS_C);
+    });
+}
+
+/// Chamado pelo handler do IRQ 8. Ler o registrador C é obrigatório,
+/// senão o RTC não gera o próximo IRQ.
+pub fn handle_interrupt() {
+    read_register(REG_STATUS_C);
+    PERIODIC_TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn periodic_ticks() -> u64 {
+    PERIODIC_TICKS.load(Ordering::Relaxed)
+}
+
+/// Tempo de parede em ms Unix: leitura do RTC no boot + ticks desde então.
+pub fn unix_time_ms() -> u64 {
+    let elapsed = timer::ticks() - BOOT_TICK.load(Ordering::Relaxed);
+    BOOT_EPOCH_MS.load(Ordering::Relaxed) + elapsed * 1000 / timer::TIMER_HZ
+}
+
+/// Timestamp em segundos Unix (metadados de arquivo, logs).
+pub fn timestamp() -> u64 {
+    unix_time_ms() / 1000
+}
+
+pub fn now() -> DateTime {
+    DateTime::from_unix(timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_roundtrip() {
+        let dt = DateTime { year: 2025, month: 10, day: 19, hour: 13, minute: 45, second: 7 };
+        assert_eq!(dt.to_unix(), 1_760_881_507);
+        assert_eq!(DateTime::from_unix(dt.to_unix()), dt);
+        assert_eq!(DateTime::from_unix(0).year, 1970);
+    }
+
+    #[test]
+    fn test_decode_bcd_12h() {
+        // 11:59:30 PM em BCD, 12 horas, 31/12/99, século 20
+        let raw = [0x30, 0x59, 0x11 | HOUR_PM, 0x31, 0x12, 0x99, 0x20];
+        let dt = decode(raw, 0);
+        assert_eq!(dt, DateTime { year: 2099, month: 12, day: 31, hour: 23, minute: 59, second: 30 });
+    }
+
+    #[test]
+    fn test_decode_binary_24h_midnight() {
+        let raw = [0, 0, 0, 1, 1, 26, 20];
+        let dt = decode(raw, STATUS_B_BINARY | STATUS_B_24H);
+        assert_eq!(dt, DateTime { year: 2026, month: 1, day: 1, hour: 0, minute: 0, second: 0 });
+    }
+}
@@ -0,0 +1,554 @@
+// src/scheduler.rs
+// ====================
+// THREADS DO KERNEL - Pilhas, troca de contexto e escalonador round-robin
+// ====================
+//
+// Uma CPU só. O lock do escalonador é sempre tomado com as interrupções
+// desligadas, então o IRQ do timer nunca o encontra ocupado. A preempção
+// acontece no fim do handler do timer (depois do EOI): a thread interrompida
+// dorme dentro do handler e volta pelo `iretq` quando for escalonada de novo.
+
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicBool, Ordering};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::{Mutex, MutexGuard};
+use x86_64::instructions::interrupts;
+use x86_64::structures::paging::{Page, PageTableFlags};
+use x86_64::VirtAddr;
+use crate::fpu::FpuState;
+use crate::memory::AddressSpace;
+use crate::{frame_allocator, gdt, memory, timer, usermode};
+
+pub type ThreadId = u64;
+
+/// Pilhas de 64 KiB, cada uma com uma página de guarda não mapeada abaixo:
+/// estouro vira #PF (e #DF na IST) em vez de corromper o heap.
+const STACK_SIZE: u64 = 64 * 1024;
+const STACK_REGION: u64 = 0x6666_0000_0000;
+const STACK_SLOT_SIZE: u64 = STACK_SIZE + 4096;
+
+/// Fatia de tempo antes da preempção (10 ms com o timer a 1 kHz).
+const TIME_SLICE_TICKS: u64 = 10;
+
+// RFLAGS inicial: só o bit 1 (reservado); IF desligado até thread_start
+const INITIAL_RFLAGS: u64 = 0x2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    Ready,
+    Running,
+    Blocked,
+    Sleeping,
+    Dead,
+}
+
+// --- Pilhas ---
+
+struct KernelStack {
+    slot: u64,
+}
+
+struct StackSlots {
+    next: u64,
+    free: Vec<u64>,
+}
+
+static STACK_SLOTS: Mutex<StackSlots> = Mutex::new(StackSlots { next: 0, free: Vec::new() });
+
+impl KernelStack {
+    fn allocate() -> Result<KernelStack, &'static str> {
+        let slot = {
+            let mut slots = STACK_SLOTS.lock();
+            slots.free.pop().unwrap_or_else(|| {
+                slots.next += 1;
+                slots.next - 1
+            })
+        };
+        let stack = KernelStack { slot };
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+        if memory::map_range(stack.bottom(), STACK_SIZE, flags).is_err() {
+            // O Drop desfaz o que chegou a ser mapeado
+            return Err("sem memória para a pilha");
+        }
+        Ok(stack)
+    }
+
+    fn bottom(&self) -> VirtAddr {
+        // A página de guarda fica no início do slot
+        VirtAddr::new(STACK_REGION + self.slot * STACK_SLOT_SIZE + 4096)
+    }
+
+    fn top(&self) -> VirtAddr {
+        self.bottom() + STACK_SIZE
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        let first = Page::containing_address(self.bottom());
+        let last = Page::containing_address(self.top() - 1u64);
+        for page in Page::range_inclusive(first, last) {
+            if let Ok(frame) = memory::unmap_page(page) {
+                frame_allocator::free_frame(frame);
+            }
+        }
+        STACK_SLOTS.lock().free.push(self.slot);
+    }
+}
+
+// --- Threads ---
+
+struct Thread {
+    id: ThreadId,
+    name: String,
+    state: ThreadState,
+    rsp: u64,
+    // None para a thread do boot, que roda na pilha do bootloader
+    stack: Option<KernelStack>,
+    entry: Option<Box<dyn FnOnce() + Send>>,
+    // Threads de usuário: espaço do processo e (entrada, pilha) no ring 3
+    address_space: Option<Arc<AddressSpace>>,
+    user_entry: Option<(VirtAddr, VirtAddr)>,
+    fpu: Box<FpuState>,
+    cpu_ticks: u64,
+    wake_at: u64,
+    joiners: Vec<ThreadId>,
+    detached: bool,
+}
+
+impl Thread {
+    fn new(id: ThreadId, name: &str, stack: Option<KernelStack>) -> Box<Thread> {
+        Box::new(Thread {
+            id,
+            name: String::from(name),
+            state: ThreadState::Ready,
+            rsp: 0,
+            stack,
+            entry: None,
+            address_space: None,
+            user_entry: None,
+            fpu: Box::new(FpuState::new()),
+            cpu_ticks: 0,
+            wake_at: 0,
+            joiners: Vec::new(),
+            detached: false,
+        })
+    }
+}
+
+/// Linha do `ps`.
+#[derive(Debug, Clone)]
+pub struct ThreadInfo {
+    pub id: ThreadId,
+    pub name: String,
+    pub state: ThreadState,
+    pub cpu_ms: u64,
+}
+
+struct Scheduler {
+    // Box: o endereço de `rsp` e do FpuState não pode mudar com o BTreeMap
+    threads: BTreeMap<ThreadId, Box<Thread>>,
+    ready: VecDeque<ThreadId>,
+    sleepers: Vec<ThreadId>,
+    current: ThreadId,
+    idle: ThreadId,
+    next_id: ThreadId,
+    slice_left: u64,
+}
+
+static SCHEDULER: Mutex<Scheduler> = Mutex::new(Scheduler {
+    threads: BTreeMap::new(),
+    ready: VecDeque::new(),
+    sleepers: Vec::new(),
+    current: 0,
+    idle: 0,
+    next_id: 0,
+    slice_left: TIME_SLICE_TICKS,
+});
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+impl Scheduler {
+    fn thread(&mut self, id: ThreadId) -> &mut Thread {
+        self.threads.get_mut(&id).expect("thread inexistente")
+    }
+
+    fn make_ready(&mut self, id: ThreadId) {
+        let thread = self.thread(id);
+        if matches!(thread.state, ThreadState::Blocked | ThreadState::Sleeping) {
+            thread.state = ThreadState::Ready;
+            self.ready.push_back(id);
+        }
+    }
+}
+
+// --- Troca de contexto ---
+
+// Salva os registradores preservados pela ABI + RFLAGS na pilha atual,
+// guarda o RSP em *old_rsp (rdi) e retoma a pilha new_rsp (rsi).
+global_asm!(r#"
+.global tri_switch_context
+tri_switch_context:
+    push rbp
+    push rbx
+    push r12
+    push r13
+    push r14
+    push r15
+    pushfq
+    mov [rdi], rsp
+    mov rsp, rsi
+    popfq
+    pop r15
+    pop r14
+    pop r13
+    pop r12
+    pop rbx
+    pop rbp
+    ret
+"#);
+
+extern "C" {
+    fn tri_switch_context(old_rsp: *mut u64, new_rsp: u64);
+}
+
+// Pilha inicial no formato que tri_switch_context desempilha
+fn prepare_stack(top: VirtAddr) -> u64 {
+    let frame: [u64; 9] = [
+        INITIAL_RFLAGS,
+        0, 0, 0, 0, 0, 0, // r15, r14, r13, r12, rbx, rbp
+        thread_start as *const () as u64,
+        0, // endereço de retorno falso: RSP ≡ 8 (mod 16) na entrada, como após um call
+    ];
+    let rsp = top.as_u64() - core::mem::size_of_val(&frame) as u64;
+    unsafe { core::ptr::copy_nonoverlapping(frame.as_ptr(), rsp as *mut u64, frame.len()) };
+    rsp
+}
+
+// Primeira execução de toda thread criada por `spawn`
+extern "C" fn thread_start() -> ! {
+    let (entry, user_entry) = interrupts::without_interrupts(|| {
+        let mut sched = SCHEDULER.lock();
+        let id = sched.current;
+        let thread = sched.thread(id);
+        (thread.entry.take(), thread.user_entry.take())
+    });
+    interrupts::enable();
+    if let Some((rip, rsp)) = user_entry {
+        usermode::enter_user(rip, rsp);
+    }
+    if let Some(entry) = entry {
+        entry();
+    }
+    exit()
+}
+
+/// Escolhe a próxima thread e troca para ela. Precisa das interrupções
+/// desligadas; o lock é solto antes da troca.
+fn schedule(mut sched: MutexGuard<Scheduler>) {
+    let prev_id = sched.current;
+    let idle = sched.idle;
+    if sched.thread(prev_id).state == ThreadState::Running {
+        sched.thread(prev_id).state = ThreadState::Ready;
+        if prev_id != idle {
+            sched.ready.push_back(prev_id);
+        }
+    }
+
+    // A fila pode ter ids velhos (thread que já morreu ou voltou a dormir)
+    let next_id = loop {
+        match sched.ready.pop_front() {
+            Some(id) if sched.threads.get(&id).is_some_and(|t| t.state == ThreadState::Ready) => break id,
+            Some(_) => continue,
+            None => break idle,
+        }
+    };
+    sched.slice_left = TIME_SLICE_TICKS;
+    sched.thread(next_id).state = ThreadState::Running;
+    if next_id == prev_id {
+        return;
+    }
+    sched.current = next_id;
+
+    let prev_rsp: *mut u64 = &mut sched.thread(prev_id).rsp;
+    let next = sched.thread(next_id);
+    let next_rsp = next.rsp;
+    let next_fpu: *mut FpuState = &mut *next.fpu;
+    let next_stack = next.stack.as_ref().map(KernelStack::top);
+    let next_space = next.address_space.as_ref().map(Arc::as_ptr);
+    drop(sched);
+
+    // Entradas no kernel vindas do ring 3 caem no topo da pilha da thread
+    if let Some(top) = next_stack {
+        gdt::set_kernel_stack(top);
+    }
+    match next_space {
+        Some(space) => unsafe { (*space).activate() },
+        None => memory::activate_kernel_space(),
+    }
+    crate::fpu::switch_to(next_fpu);
+    unsafe { tri_switch_context(prev_rsp, next_rsp) };
+}
+
+// --- API ---
+
+/// Transforma o fluxo atual (boot) na thread `name` e cria a idle.
+/// A partir daqui o timer preempta.
+pub fn init(name: &str) {
+    let idle_stack = KernelStack::allocate().expect("pilha da idle");
+    interrupts::without_interrupts(|| {
+        let mut sched = SCHEDULER.lock();
+        let mut boot = Thread::new(0, name, None);
+        boot.state = ThreadState::Running;
+        crate::fpu::switch_to(&mut *boot.fpu);
+        sched.threads.insert(0, boot);
+
+        // A idle nunca entra na fila: roda quando não há mais ninguém
+        let mut idle = Thread::new(1, "idle", None);
+        idle.rsp = prepare_stack(idle_stack.top());
+        idle.stack = Some(idle_stack);
+        idle.entry = Some(Box::new(idle_loop));
+        idle.detached = true;
+        sched.threads.insert(1, idle);
+        sched.idle = 1;
+        sched.next_id = 2;
+        sched.current = 0;
+    });
+    RUNNING.store(true, Ordering::SeqCst);
+}
+
+fn idle_loop() {
+    loop {
+        reap();
+        interrupts::enable_and_hlt();
+    }
+}
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+// Dá um id à thread nova e a põe na fila de prontas
+fn enqueue(mut thread: Box<Thread>) -> ThreadId {
+    interrupts::without_interrupts(|| {
+        let mut sched = SCHEDULER.lock();
+        let id = sched.next_id;
+        sched.next_id += 1;
+        thread.id = id;
+        let ready = thread.state == ThreadState::Ready;
+        sched.threads.insert(id, thread);
+        if ready {
+            sched.ready.push_back(id);
+        }
+        id
+    })
+}
+
+/// Cria uma thread pronta para rodar `f`.
+pub fn spawn(name: &str, f: impl FnOnce() + Send + 'static) -> Result<ThreadId, &'static str> {
+    reap();
+    let stack = KernelStack::allocate()?;
+    let rsp = prepare_stack(stack.top());
+    let mut thread = Thread::new(0, name, Some(stack));
+    thread.rsp = rsp;
+    thread.entry = Some(Box::new(f));
+
+    Ok(enqueue(thread))
+}
+
+/// Cria uma thread que entra no ring 3 em `entry`, com a pilha de usuário
+/// `stack`, dentro de `space`. Ela nasce bloqueada e desanexada: quem cria
+/// registra o processo dono e então chama `unblock`.
+pub fn spawn_user(name: &str, space: Arc<AddressSpace>, entry: VirtAddr, stack: VirtAddr) -> Result<ThreadId, &'static str> {
+    reap();
+    let kernel_stack = KernelStack::allocate()?;
+    let rsp = prepare_stack(kernel_stack.top());
+    let mut thread = Thread::new(0, name, Some(kernel_stack));
+    thread.rsp = rsp;
+    thread.address_space = Some(space);
+    thread.user_entry = Some((entry, stack));
+    thread.state = ThreadState::Blocked;
+    thread.detached = true;
+
+    Ok(enqueue(thread))
+}
+
+/// Cede a CPU para a próxima thread pronta.
+// Primitiva da API do escalonador; os laços atuais do kernel esperam em
+// WaitQueue (bloqueando) e não precisam ceder a CPU, daí o allow.
+#[allow(dead_code)]
+pub fn yield_now() {
+    if !is_running() {
+        return;
+    }
+    interrupts::without_interrupts(|| schedule(SCHEDULER.lock()));
+}
+
+pub fn current() -> ThreadId {
+    interrupts::without_interrupts(|| SCHEDULER.lock().current)
+}
+
+/// Dorme por `ticks` ticks do timer.
+pub fn sleep_ticks(ticks: u64) {
+    let wake_at = timer::ticks() + ticks;
+    interrupts::without_interrupts(|| {
+        let mut sched = SCHEDULER.lock();
+        let id = sched.current;
+        let thread = sched.thread(id);
+        thread.state = ThreadState::Sleeping;
+        thread.wake_at = wake_at;
+        sched.sleepers.push(id);
+        schedule(sched);
+    });
+}
+
+/// Bloqueia a thread atual até um `unblock`. Quem chama precisa ter se
+/// registrado em alguma fila de espera antes, com as interrupções desligadas,
+/// para não perder o despertar.
+pub fn block() {
+    interrupts::without_interrupts(|| {
+        let mut sched = SCHEDULER.lock();
+        let id = sched.current;
+        sched.thread(id).state = ThreadState::Blocked;
+        schedule(sched);
+    });
+}
+
+/// Acorda uma thread bloqueada ou dormindo. Pode ser chamada de IRQ.
+pub fn unblock(id: ThreadId) {
+    interrupts::without_interrupts(|| {
+        let mut sched = SCHEDULER.lock();
+        if sched.threads.contains_key(&id) {
+            sched.make_ready(id);
+        }
+    });
+}
+
+/// Termina a thread atual e acorda quem estiver em `join`.
+pub fn exit() -> ! {
+    interrupts::disable();
+    let mut sched = SCHEDULER.lock();
+    let id = sched.current;
+    let thread = sched.thread(id);
+    thread.state = ThreadState::Dead;
+    crate::fpu::release(&mut *thread.fpu);
+    for joiner in core::mem::take(&mut thread.joiners) {
+        sched.make_ready(joiner);
+    }
+    schedule(sched);
+    unreachable!("thread morta escalonada de novo");
+}
+
+/// Espera a thread `id` terminar. Retorna false se ela não existe, é a
+/// própria thread ou a idle (que nunca terminam), ou está detached: essa é
+/// removida sozinha ao terminar e ninguém deve esperar por ela.
+pub fn join(id: ThreadId) -> bool {
+    loop {
+        let finished = interrupts::without_interrupts(|| {
+            let mut sched = SCHEDULER.lock();
+            let me = sched.current;
+            let refused = id == me || id == sched.idle;
+            match sched.threads.get(&id).map(|t| (t.state, t.detached)) {
+                None => Some(None),
+                Some(_) if refused => Some(None),
+                Some((_, true)) => Some(None),
+                Some((ThreadState::Dead, _)) => Some(sched.threads.remove(&id)),
+                Some(_) => {
+                    sched.thread(id).joiners.push(me);
+                    sched.thread(me).state = ThreadState::Blocked;
+                    schedule(sched);
+                    None
+                }
+            }
+        });
+        // O registro (e a pilha) é solto aqui, com as interrupções ligadas
+        if let Some(record) = finished {
+            return record.is_some();
+        }
+    }
+}
+
+/// A thread é removida sozinha ao terminar, sem precisar de `join`.
+pub fn detach(id: ThreadId) {
+    interrupts::without_interrupts(|| {
+        if let Some(thread) = SCHEDULER.lock().threads.get_mut(&id) {
+            thread.detached = true;
+        }
+    });
+}
+
+// Solta pilhas e espaços de threads mortas (não dá para fazer no `schedule`:
+// unmap pega o lock do mapeador, que uma thread preemptada pode estar segurando)
+fn reap() {
+    let mut dead = Vec::new();
+    let mut stacks = Vec::new();
+    let mut spaces = Vec::new();
+    interrupts::without_interrupts(|| {
+        let mut sched = SCHEDULER.lock();
+        let current = sched.current;
+        let ids: Vec<ThreadId> = sched.threads.values()
+            .filter(|t| t.state == ThreadState::Dead && t.id != current)
+            .map(|t| t.id)
+            .collect();
+        for id in ids {
+            if sched.thread(id).detached {
+                dead.extend(sched.threads.remove(&id));
+            } else {
+                let thread = sched.thread(id);
+                stacks.extend(thread.stack.take());
+                spaces.extend(thread.address_space.take());
+            }
+        }
+    });
+    drop(dead);
+    drop(stacks);
+    drop(spaces);
+}
+
+/// Chamado pelo handler do timer, depois do EOI: contabiliza CPU, acorda
+/// quem dormia e preempta quando a fatia acaba.
+pub fn on_tick() {
+    if !is_running() {
+        return;
+    }
+    let now = timer::ticks();
+    let mut sched = SCHEDULER.lock();
+    let current = sched.current;
+    sched.thread(current).cpu_ticks += 1;
+
+    let mut i = 0;
+    while i < sched.sleepers.len() {
+        let id = sched.sleepers[i];
+        let due = sched.threads.get(&id).is_none_or(|t| t.state != ThreadState::Sleeping || t.wake_at <= now);
+        if due {
+            sched.sleepers.swap_remove(i);
+            if sched.threads.contains_key(&id) {
+                sched.make_ready(id);
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    sched.slice_left = sched.slice_left.saturating_sub(1);
+    let idle_with_work = current == sched.idle && !sched.ready.is_empty();
+    if sched.slice_left == 0 || idle_with_work {
+        schedule(sched);
+    }
+}
+
+/// Todas as threads (para o `ps`).
+pub fn threads() -> Vec<ThreadInfo> {
+    interrupts::without_interrupts(|| {
+        SCHEDULER.lock().threads.values().map(|t| ThreadInfo {
+            id: t.id,
+            name: t.name.clone(),
+            state: t.state,
+            cpu_ms: t.cpu_ticks * 1000 / timer::TIMER_HZ,
+        }).collect()
+    })
+}
@@ -0,0 +1,554 @@
+// src/serial.rs
+// ====================
+// SERIAL (UART 16550) - COM1..COM4 pelas portas de I/O, baud/paridade,
+// FIFO, IRQs 4/3 com filas de recepção e transmissão e autoteste em loopback
+// ====================
+//
+// Duas fases:
+// - `init` (primeira coisa do boot): detecta e configura as portas em
+//   115200 8N1 e faz o autoteste em loopback; tudo por polling, sem heap.
+// - `enable_interrupts` (com IDT, APIC e /etc/tri-shellrc prontos): aplica
+//   `set serial=<baud>,<dados><paridade><stop>` na COM1 e liga as IRQs.
+// A COM1 é o console: o que chega nela vai para a mesma fila de bytes do
+// teclado (o shell não distingue) e a saída do shell é espelhada nela, então
+// o kernel pode ser usado sem monitor (`qemu -serial stdio`).
+// Com as interrupções da CPU desligadas (ISRs, pânico) a escrita é síncrona:
+// a fila de transmissão é esvaziada e o resto vai direto por polling.
+
+use core::fmt;
+use x86_64::instructions::port::Port;
+use crate::ring::Ring;
+use crate::sync::IrqSpinlock;
+
+// Registradores (deslocamento da base)
+const REG_DATA: u16 = 0; // RBR/THR; com DLAB, divisor baixo
+const REG_IER: u16 = 1; // com DLAB, divisor alto
+const REG_IIR: u16 = 2; // leitura; escrita = FCR
+const REG_FCR: u16 = 2;
+const REG_LCR: u16 = 3;
+const REG_MCR: u16 = 4;
+const REG_LSR: u16 = 5;
+const REG_MSR: u16 = 6;
+const REG_SCRATCH: u16 = 7;
+
+// Bits do IER
+const IER_RX: u8 = 0x01;
+const IER_TX_EMPTY: u8 = 0x02;
+const IER_LINE_STATUS: u8 = 0x04;
+
+// FIFO ligada, filas limpas, IRQ de recepção com 14 bytes
+const FCR_ENABLE: u8 = 0xC7;
+const FIFO_SIZE: usize = 16;
+
+const LCR_DLAB: u8 = 0x80;
+
+// MCR: DTR, RTS e OUT2 (que libera a linha de IRQ para o PIC)
+const MCR_NORMAL: u8 = 0x0B;
+// RTS, OUT1, OUT2 e loopback: o que sai volta na recepção
+const MCR_LOOPBACK: u8 = 0x1E;
+
+// Bits do LSR
+const LSR_DATA_READY: u8 = 0x01;
+const LSR_THR_EMPTY: u8 = 0x20;
+
+// Clock do 16550 / 16
+const BASE_BAUD: u32 = 115_200;
+
+// Leituras do LSR antes de desistir (sem relógio no começo do boot)
+const POLL_LIMIT: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Com {
+    Com1,
+    Com2,
+    Com3,
+    Com4,
+}
+
+impl Com {
+    pub const ALL: [Com; 4] = [Com::Com1, Com::Com2, Com::Com3, Com::Com4];
+
+    pub fn base(self) -> u16 {
+        match self {
+            Com::Com1 => 0x3F8,
+            Com::Com2 => 0x2F8,
+            Com::Com3 => 0x3E8,
+            Com::Com4 => 0x2E8,
+        }
+    }
+
+    /// COM1/COM3 dividem a IRQ 4 e COM2/COM4 a IRQ 3.
+    pub fn irq(self) -> u8 {
+        match self {
+            Com::Com1 | Com::Com3 => 4,
+            Com::Com2 | Com::Com4 => 3,
+        }
+    }
+
+    /// "com1".."com4", sem diferenciar maiúsculas.
+    pub fn parse(name: &str) -> Option<Com> {
+        Com::ALL.into_iter().find(|com| {
+            let mut buf = [0; 4];
+            name.eq_ignore_ascii_case(com.name(&mut buf))
+        })
+    }
+
+    fn name(self, buf: &mut [u8; 4]) -> &str {
+        *buf = *b"COM1";
+        buf[3] += self as u8;
+        core::str::from_utf8(buf).unwrap()
+    }
+}
+
+impl fmt::Display for Com {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name(&mut [0; 4]))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+    Mark,
+    Space,
+}
+
+impl Parity {
+    const ALL: [(Parity, char); 5] = [
+        (Parity::None, 'N'),
+        (Parity::Odd, 'O'),
+        (Parity::Even, 'E'),
+        (Parity::Mark, 'M'),
+        (Parity::Space, 'S'),
+    ];
+
+    /// Bits 3-5 do LCR.
+    fn bits(self) -> u8 {
+        match self {
+            Parity::None => 0x00,
+            Parity::Odd => 0x08,
+            Parity::Even => 0x18,
+            Parity::Mark => 0x28,
+            Parity::Space => 0x38,
+        }
+    }
+
+    fn letter(self) -> char {
+        Parity::ALL.iter().find(|(parity, _)| *parity == self).unwrap().1
+    }
+}
+
+/// Formato da linha: baud, bits de dados (5-8), paridade e stop bits (1-2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineConfig {
+    pub baud: u32,
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: u8,
+}
+
+impl LineConfig {
+    pub const DEFAULT: LineConfig = LineConfig { baud: 115_200, data_bits: 8, parity: Parity::None, stop_bits: 1 };
+
+    /// "115200", "9600,7E1"... O formato ausente fica 8N1; baud que o
+    /// divisor não gera exato é recusado.
+    pub fn parse(text: &str) -> Option<LineConfig> {
+        let (baud, format) = text.trim().split_once(',').unwrap_or((text.trim(), "8N1"));
+        let mut format = format.trim().chars();
+        let data_bits = format.next()?.to_digit(10)? as u8;
+        let letter = format.next()?.to_ascii_uppercase();
+        let parity = Parity::ALL.iter().find(|(_, l)| *l == letter)?.0;
+        let stop_bits = format.next()?.to_digit(10)? as u8;
+        let config = LineConfig { baud: baud.trim().parse().ok()?, data_bits, parity, stop_bits };
+        let valid = format.next().is_none()
+            && (5..=8).contains(&data_bits)
+            && (1..=2).contains(&stop_bits)
+            && config.divisor().is_some();
+        valid.then_some(config)
+    }
+
+    fn divisor(&self) -> Option<u16> {
+        if self.baud == 0 || !BASE_BAUD.is_multiple_of(self.baud) {
+            return None;
+        }
+        u16::try_from(BASE_BAUD / self.baud).ok()
+    }
+
+    /// LCR sem o DLAB: tamanho da palavra, stop bits e paridade.
+    fn line_control(&self) -> u8 {
+        (self.data_bits - 5) | if self.stop_bits == 2 { 0x04 } else { 0 } | self.parity.bits()
+    }
+}
+
+impl fmt::Display for LineConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}{}{}", self.baud, self.data_bits, self.parity.letter(), self.stop_bits)
+    }
+}
+
+// --- UART ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Nada respondeu no registrador de rascunho
+    Absent,
+    /// Respondeu, mas o loopback não devolveu o byte: fica desligada
+    Faulty,
+    /// Funcionando por polling (antes do `enable_interrupts`)
+    Polled,
+    Interrupts,
+}
+
+struct Uart {
+    base: u16,
+    state: State,
+    config: LineConfig,
+    ier: u8,
+    rx: Ring<u8, 256>,
+    tx: Ring<u8, 1024>,
+}
+
+impl Uart {
+    const fn new(base: u16) -> Self {
+        Uart {
+            base,
+            state: State::Absent,
+            config: LineConfig::DEFAULT,
+            ier: 0,
+            rx: Ring::new(),
+            tx: Ring::new(),
+        }
+    }
+
+    fn read(&self, reg: u16) -> u8 {
+        unsafe { Port::<u8>::new(self.base + reg).read() }
+    }
+
+    fn write(&self, reg: u16, value: u8) {
+        unsafe { Port::<u8>::new(self.base + reg).write(value) }
+    }
+
+    fn usable(&self) -> bool {
+        matches!(self.state, State::Polled | State::Interrupts)
+    }
+
+    /// Detecta, programa a linha e a FIFO e faz o autoteste. Deixa a porta
+    /// em polling, com as IRQs do chip desligadas.
+    fn configure(&mut self, config: LineConfig) -> Result<(), &'static str> {
+        let divisor = config.divisor().ok_or("baud inválido")?;
+        self.write(REG_IER, 0);
+        self.ier = 0;
+
+        // Porta ISA vazia devolve 0xFF em tudo
+        self.write(REG_SCRATCH, 0x5A);
+        if self.read(REG_SCRATCH) != 0x5A {
+            self.state = State::Absent;
+            return Err("porta ausente");
+        }
+
+        self.write(REG_LCR, LCR_DLAB);
+        self.write(REG_DATA, divisor as u8);
+        self.write(REG_IER, (divisor >> 8) as u8);
+        self.write(REG_LCR, config.line_control());
+        self.write(REG_FCR, FCR_ENABLE);
+        self.config = config;
+
+        self.write(REG_MCR, MCR_LOOPBACK);
+        let echoed = self.loopback(0xAE);
+        self.write(REG_MCR, MCR_NORMAL);
+        if !echoed {
+            self.state = State::Faulty;
+            return Err("autoteste em loopback falhou");
+        }
+        self.state = State::Polled;
+        Ok(())
+    }
+
+    /// Manda `byte` com o loopback ligado e confere se ele volta.
+    fn loopback(&mut self, byte: u8) -> bool {
+        // Lixo que já estava na recepção
+        while self.read(REG_LSR) & LSR_DATA_READY != 0 {
+            self.read(REG_DATA);
+        }
+        self.write(REG_DATA, byte);
+        for _ in 0..POLL_LIMIT {
+            if self.read(REG_LSR) & LSR_DATA_READY != 0 {
+                return self.read(REG_DATA) == byte;
+            }
+            core::hint::spin_loop();
+        }
+        false
+    }
+
+    fn enable_interrupts(&mut self) {
+        // Descarta o que chegou antes e o estado pendente de linha/modem
+        while self.read(REG_LSR) & LSR_DATA_READY != 0 {
+            self.read(REG_DATA);
+        }
+        self.read(REG_IIR);
+        self.read(REG_MSR);
+        self.ier = IER_RX | IER_LINE_STATUS;
+        self.write(REG_IER, self.ier);
+        self.state = State::Interrupts;
+    }
+
+    fn send_polled(&self, byte: u8) {
+        for _ in 0..POLL_LIMIT {
+            if self.read(REG_LSR) & LSR_THR_EMPTY != 0 {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        self.write(REG_DATA, byte);
+    }
+
+    fn flush_polled(&mut self) {
+        while let Some(byte) = self.tx.pop() {
+            self.send_polled(byte);
+        }
+    }
+
+    /// Enche a FIFO de transmissão se ela esvaziou, e liga a IRQ de THR
+    /// vazio só enquanto ainda houver bytes na fila.
+    fn pump_tx(&mut self) {
+        if self.read(REG_LSR) & LSR_THR_EMPTY != 0 {
+            for _ in 0..FIFO_SIZE {
+                match self.tx.pop() {
+                    Some(byte) => self.write(REG_DATA, byte),
+                    None => break,
+                }
+            }
+        }
+        let ier = if !self.tx.is_empty() { self.ier | IER_TX_EMPTY } else { self.ier & !IER_TX_EMPTY };
+        if ier != self.ier {
+            self.ier = ier;
+            self.write(REG_IER, ier);
+        }
+    }
+
+    fn send(&mut self, bytes: &[u8], polled: bool) {
+        if !self.usable() {
+            return;
+        }
+        if polled || self.state != State::Interrupts {
+            self.flush_polled();
+            for &byte in bytes {
+                self.send_polled(byte);
+            }
+            return;
+        }
+        for &byte in bytes {
+            // Fila cheia: o mais antigo sai por polling, a ordem se mantém
+            while !self.tx.push(byte) {
+                if let Some(old) = self.tx.pop() {
+                    self.send_polled(old);
+                }
+            }
+        }
+        self.pump_tx();
+    }
+
+    /// Atende tudo o que o IIR tiver pendente.
+    fn service(&mut self) {
+        for _ in 0..FIFO_SIZE * 4 {
+            let iir = self.read(REG_IIR);
+            if iir & 0x01 != 0 {
+                return;
+            }
+            match (iir >> 1) & 0x07 {
+                // Erro de linha (overrun, paridade, framing): ler o LSR limpa
+                0b011 => { self.read(REG_LSR); }
+                // Dados ou timeout de caractere na FIFO
+                0b010 | 0b110 => {
+                    // Com a fila cheia o byte se perde, como no overrun
+                    while self.read(REG_LSR) & LSR_DATA_READY != 0 {
+                        let byte = self.read(REG_DATA);
+                        self.rx.push(byte);
+                    }
+                }
+                0b001 => self.pump_tx(),
+                _ => { self.read(REG_MSR); }
+            }
+        }
+    }
+}
+
+static PORTS: [IrqSpinlock<Uart>; 4] = [
+    IrqSpinlock::new(Uart::new(0x3F8)),
+    IrqSpinlock::new(Uart::new(0x2F8)),
+    IrqSpinlock::new(Uart::new(0x3E8)),
+    IrqSpinlock::new(Uart::new(0x2E8)),
+];
+
+/// Porta do console (entrada do shell e saída do `serial_println!`).
+pub const CONSOLE: Com = Com::Com1;
+
+fn port(com: Com) -> &'static IrqSpinlock<Uart> {
+    &PORTS[com as usize]
+}
+
+/// Detecção e autoteste das quatro portas em 115200 8N1, por polling.
+/// Roda antes do heap e do klog: o resultado sai no `enable_interrupts`.
+pub fn init() {
+    for com in Com::ALL {
+        let _ = port(com).lock().configure(LineConfig::DEFAULT);
+    }
+}
+
+/// Aplica `set serial=...` ao console e liga as IRQs das portas que
+/// passaram no autoteste.
+pub fn enable_interrupts() {
+    if let Some(text) = crate::config::get("serial") {
+        match LineConfig::parse(&text) {
+            Some(config) => {
+                if let Err(err) = configure(CONSOLE, config) {
+                    crate::klog!("Serial: {}: {}", CONSOLE, err);
+                }
+            }
+            None => crate::klog!("Serial: configuração inválida: {}", text),
+        }
+    }
+    for com in Com::ALL {
+        let mut uart = port(com).lock();
+        match uart.state {
+            State::Absent => continue,
+            State::Faulty => {
+                drop(uart);
+                crate::klog!("Serial: {} em {:#x}: autoteste em loopback falhou", com, com.base());
+                continue;
+            }
+            _ => uart.enable_interrupts(),
+        }
+        let config = uart.config;
+        drop(uart);
+        crate::interrupts::unmask_irq(com.irq());
+        crate::klog!("Serial: {} em {:#x}, IRQ {}, {}{}", com, com.base(), com.irq(), config,
+            if com == CONSOLE { " (console)" } else { "" });
+    }
+}
+
+/// Reprograma baud e formato. A porta volta a funcionar com IRQ se já
+/// estava assim.
+pub fn configure(com: Com, config: LineConfig) -> Result<(), &'static str> {
+    let mut uart = port(com).lock();
+    let interrupts = uart.state == State::Interrupts;
+    uart.flush_polled();
+    uart.configure(config)?;
+    if interrupts {
+        uart.enable_interrupts();
+    }
+    Ok(())
+}
+
+/// Estado e formato da linha, para o shell.
+pub fn status(com: Com) -> (State, LineConfig) {
+    let uart = port(com).lock();
+    (uart.state, uart.config)
+}
+
+/// Chamado pelas IRQs 4 e 3: atende as portas da linha e entrega o que o
+/// console recebeu à fila de entrada do teclado.
+pub fn handle_interrupt(irq: u8) {
+    for com in Com::ALL.into_iter().filter(|com| com.irq() == irq) {
+        let mut uart = port(com).lock();
+        if uart.state != State::Interrupts {
+            continue;
+        }
+        uart.service();
+        if com != CONSOLE {
+            continue;
+        }
+        let mut input = [0; 256];
+        let mut len = 0;
+        while let Some(byte) = uart.rx.pop() {
+            input[len] = byte;
+            len += 1;
+        }
+        drop(uart);
+        if len > 0 {
+            crate::keyboard::push_input(&input[..len]);
+        }
+    }
+}
+
+/// Transmite bytes crus (sem traduzir `\n`).
+pub fn write(com: Com, bytes: &[u8]) {
+    let polled = !x86_64::instructions::interrupts::are_enabled();
+    port(com).lock().send(bytes, polled);
+}
+
+/// Próximo byte recebido numa porta que não é o console.
+pub fn read(com: Com) -> Option<u8> {
+    port(com).lock().rx.pop()
+}
+
+/// Saída para o terminal do console: `\n` vira `\r\n`.
+pub fn console_write(bytes: &[u8]) {
+    let polled = !x86_64::instructions::interrupts::are_enabled();
+    let mut uart = port(CONSOLE).lock();
+    let mut lines = bytes.split(|&byte| byte == b'\n');
+    if let Some(first) = lines.next() {
+        uart.send(first, polled);
+    }
+    for line in lines {
+        uart.send(b"\r\n", polled);
+        uart.send(line, polled);
+    }
+}
+
+struct ConsoleWriter;
+
+impl fmt::Write for ConsoleWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        console_write(s.as_bytes());
+        Ok(())
+    }
+}
+
+pub fn print(args: fmt::Arguments) {
+    let _ = fmt::Write::write_fmt(&mut ConsoleWriter, args);
+}
+
+/// Libera o console à força. Só para caminhos fatais (pânico), onde o dono
+/// do lock nunca mais vai rodar.
+pub unsafe fn force_unlock() {
+    let console = port(CONSOLE);
+    if console.is_locked() {
+        console.force_unlock();
+    }
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial::print(format_args!("\n")));
+    ($($arg:tt)*) => ($crate::serial::print(format_args!("{}\n", format_args!($($arg)*))));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_line_config() {
+        assert_eq!(LineConfig::parse("115200"), Some(LineConfig::DEFAULT));
+        let config = LineConfig::parse("9600,7e2").unwrap();
+        assert_eq!(config, LineConfig { baud: 9600, data_bits: 7, parity: Parity::Even, stop_bits: 2 });
+        assert_eq!(config.divisor(), Some(12));
+        assert_eq!(config.line_control(), 0x02 | 0x04 | 0x18);
+        assert_eq!(LineConfig::DEFAULT.line_control(), 0x03);
+        // Baud sem divisor exato, formato inválido
+        assert_eq!(LineConfig::parse("1000"), None);
+        assert_eq!(LineConfig::parse("9600,9N1"), None);
+        assert_eq!(LineConfig::parse("9600,8X1"), None);
+        assert_eq!(LineConfig::parse("9600,8N1x"), None);
+    }
+
+    #[test]
+    fn com_names() {
+        assert_eq!(Com::parse("com3"), Some(Com::Com3));
+        assert_eq!(Com::parse("COM5"), None);
+        assert_eq!(alloc::format!("{} {}", Com::Com2, LineConfig::DEFAULT), "COM2 115200 8N1");
+    }
+}
@@ -1,6 +1,8 @@
 use core::fmt;
 use core::fmt::Write; // Mantido para compatibilidade com macros
-use core::str;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
 
 // Trait simples pra Writer (abstrai output: serial ou VGA)
 pub trait Writer {
@@ -16,7 +18,7 @@ impl Writer for DummyWriter {
 }
 
 // Implementa fmt::Write para suportar macros como print!
-impl fmt::Write for dyn Writer {
+impl fmt::Write for dyn Writer + '_ {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         self.write_string(s);
         Ok(())
@@ -36,85 +38,126 @@ const HISTORY_SIZE: usize = 5; // Armazena até 5 comandos no histórico
 pub fn shell_loop(writer: &mut dyn Writer) {
     use crate::keyboard; // Módulo keyboard
 
-    let mut buffer: [u8; CMD_BUF_SIZE] = [0; CMD_BUF_SIZE];
-    let mut idx: usize = 0;
+    let mut buffer = String::with_capacity(CMD_BUF_SIZE);
+    // Bytes de um caractere UTF-8 (ç, ã...) ainda incompleto
+    let mut pending: Vec<u8> = Vec::with_capacity(4);
 
-    // Histórico de comandos
-    static mut HISTORY: [[u8; CMD_BUF_SIZE]; HISTORY_SIZE] = [[0; CMD_BUF_SIZE]; HISTORY_SIZE];
-    static mut HISTORY_COUNT: usize = 0;
-    static mut HISTORY_POS: usize = 0;
+    // Histórico de comandos (mais antigo na frente)
+    let mut history: VecDeque<String> = VecDeque::with_capacity(HISTORY_SIZE);
 
     print(writer, PROMPT);
 
     loop {
-        if let Some(byte) = keyboard::get_key() {
-            match byte {
-                b'\n' | b'\r' => { // Enter
-                    buffer[idx] = 0; // Null-terminate
-                    print(writer, "\n");
+        // Dorme até o IRQ do teclado trazer uma tecla
+        let byte = keyboard::wait_key();
+        match byte {
+            b'\n' | b'\r' => { // Enter
+                print(writer, "\n");
 
-                    // Salvar comando no histórico (se não for vazio)
-                    if idx > 0 {
-                        unsafe {
-                            HISTORY[HISTORY_POS] = buffer;
-                            HISTORY_POS = (HISTORY_POS + 1) % HISTORY_SIZE;
-                            if HISTORY_COUNT < HISTORY_SIZE {
-                                HISTORY_COUNT += 1;
-                            }
-                        }
+                // Salvar comando no histórico (se não for vazio)
+                if !buffer.is_empty() {
+                    if history.len() == HISTORY_SIZE {
+                        history.pop_front();
                     }
+                    history.push_back(buffer.clone());
+                }
 
-                    // Handle command
-                    unsafe {
-                        handle_command(writer, &buffer[..idx], &HISTORY, HISTORY_COUNT, HISTORY_POS);
-                    }
+                // Handle command
+                handle_command(writer, &buffer, &history);
 
-                    // Reset
-                    idx = 0;
-                    buffer.fill(0);
-                    print(writer, PROMPT);
+                // Reset
+                buffer.clear();
+                print(writer, PROMPT);
+            }
+            8 | b'\x7F' => { // Backspace ou DEL
+                if buffer.pop().is_some() {
+                    print(writer, "\x08 \x08"); // Retrocede, espaço, retrocede
                 }
-                8 | b'\x7F' => { // Backspace ou DEL
-                    if idx > 0 {
-                        idx -= 1;
-                        print(writer, "\x08 \x08"); // Retrocede, espaço, retrocede
+            }
+            0x80..=0xFF => {
+                pending.push(byte);
+                let complete = match core::str::from_utf8(&pending) {
+                    Ok(ch) => {
+                        if buffer.len() + ch.len() < CMD_BUF_SIZE {
+                            buffer.push_str(ch);
+                            print(writer, ch);
+                        }
+                        true
                     }
+                    // Sequência inválida é descartada; incompleta espera o resto
+                    Err(err) => err.error_len().is_some(),
+                };
+                if complete {
+                    pending.clear();
                 }
-                _ => {
-                    if idx < CMD_BUF_SIZE - 1 && (byte.is_ascii_graphic() || byte == b' ') {
-                        buffer[idx] = byte;
-                        idx += 1;
-                        // Eco (converte pra str pra print)
-                        let buf = [byte]; // Correção do E0716
-                        let echo = core::str::from_utf8(&buf).unwrap_or("?");
-                        print(writer, echo);
-                    }
+            }
+            _ => {
+                if buffer.len() < CMD_BUF_SIZE - 1 && (byte.is_ascii_graphic() || byte == b' ') {
+                    buffer.push(byte as char);
+                    // Eco (converte pra str pra print)
+                    let buf = [byte]; // Correção do E0716
+                    let echo = core::str::from_utf8(&buf).unwrap_or("?");
+                    print(writer, echo);
                 }
             }
         }
     }
 }
 
-fn handle_command(
-    writer: &mut dyn Writer,
-    cmd: &[u8],
-    history: &[[u8; CMD_BUF_SIZE]; HISTORY_SIZE],
-    history_count: usize,
-    history_pos: usize,
-) {
-    let cmd_str = str::from_utf8(cmd).unwrap_or("");
-    match cmd_str.trim() {
+fn handle_command(writer: &mut dyn Writer, cmd: &str, history: &VecDeque<String>) {
+    let line = cmd.trim();
+    let (name, args) = match line.split_once(' ') {
+        Some((name, args)) => (name, args.trim()),
+        None => (line, ""),
+    };
+    match name {
         "help" => {
             print(writer, "Comandos disponíveis:\n");
             print(writer, "  help    - mostra esta ajuda\n");
             print(writer, "  hello   - mensagem de teste\n");
-            print(writer, "  tri-ratio - stats da compressão TRI\n");
+            print(writer, "  tri-ratio [texto] - stats da compressão TRI\n");
             print(writer, "  halt    - para o kernel\n");
+            print(writer, "  poweroff - desliga a máquina (ACPI S5)\n");
+            print(writer, "  reboot  - reinicia a máquina\n");
             print(writer, "  history - mostra os últimos comandos\n");
+            print(writer, "  meminfo - uso da memória física\n");
+            print(writer, "  vtop <endereço> - endereço físico de um virtual do kernel\n");
+            print(writer, "  heap    - estatísticas do heap do kernel\n");
+            print(writer, "  slabinfo  - estatísticas por classe do slab\n");
+            print(writer, "  slabbench - compara slab x heap geral\n");
+            print(writer, "  uptime  - tempo desde o boot\n");
+            print(writer, "  sleep <ms> - dorme pelo tempo dado\n");
+            print(writer, "  time <comando> - mede o tempo de um comando\n");
+            print(writer, "  bg <comando> - roda um comando numa thread própria\n");
+            print(writer, "  wait <tid> - espera uma thread do bg terminar\n");
+            print(writer, "  ps      - lista as threads do kernel\n");
+            print(writer, "  exec <arquivo> [args] - roda um programa ELF como processo filho (Ctrl+C mata)\n");
+            print(writer, "  kill <pid> - termina um processo\n");
+            print(writer, "  alarm [-p] <ms> - agenda um aviso no timer wheel (-p: periódico)\n");
+            print(writer, "  cancel <id> - cancela um alarme\n");
+            print(writer, "  timers  - quantidade de timers pendentes\n");
+            print(writer, "  date    - data e hora (RTC)\n");
+            print(writer, "  dmesg   - mensagens do log do kernel\n");
+            print(writer, "  ls      - lista os arquivos do FS virtual\n");
+            print(writer, "  cat <arquivo> - mostra um arquivo\n");
+            print(writer, "  acpi    - tabelas ACPI (MADT, FADT, HPET)\n");
+            print(writer, "  cpuinfo - modelo e recursos da CPU\n");
+            print(writer, "  keys    - mostra os eventos do teclado (Esc sai)\n");
+            print(writer, "  loadkeys [layout] - troca o layout do teclado (us, abnt2)\n");
+            print(writer, "  mouse [n] - mostra os próximos n eventos do mouse (padrão 10)\n");
+            print(writer, "  serial [comN [baud,8N1 | send texto | recv]] - portas seriais\n");
         }
         "hello" => {
             print(writer, "Olá, TRI Kernel! Bem-vindo ao mini-shell bare-metal.\n");
         }
+        "tri-ratio" if !args.is_empty() => {
+            use crate::tri_compress;
+            let compressed = tri_compress::compress_bytes(args.as_bytes());
+            let ok = tri_compress::decompress_bytes(&compressed) == args.as_bytes();
+            let ratio = args.len() * 100 / compressed.len();
+            let _ = writeln!(writer, "TRI Ratio: {}% ({} -> {} bytes, decomp {})",
+                ratio, args.len(), compressed.len(), if ok { "OK" } else { "FAIL" });
+        }
         "tri-ratio" => {
             use crate::tri_compress;
             let original: [u8; 32] = *b"TRI Test no Shell!!!\0\0\0\0\0\0\0\0\0\0\0\0";
@@ -133,18 +176,22 @@ fn handle_command(
         }
         "halt" => {
             print(writer, "Haltando TRI Kernel...\n");
-            loop {
-                x86_64::instructions::hlt();
-            }
+            crate::power::halt();
+        }
+        "poweroff" => {
+            print(writer, "Desligando...\n");
+            crate::power::poweroff();
+        }
+        "reboot" => {
+            print(writer, "Reiniciando...\n");
+            crate::power::reboot();
         }
         "history" => {
             print(writer, "Histórico de comandos:\n");
-            if history_count == 0 {
+            if history.is_empty() {
                 print(writer, "  (vazio)\n");
             } else {
-                for i in 0..history_count {
-                    let pos = (history_pos + i - history_count + HISTORY_SIZE) % HISTORY_SIZE;
-                    let cmd_entry = str::from_utf8(&history[pos]).unwrap_or("?");
+                for (i, cmd_entry) in history.iter().enumerate() {
                     print(writer, "  ");
                     print(writer, u32_to_str((i + 1) as u32));
                     print(writer, ": ");
@@ -153,6 +200,293 @@ fn handle_command(
                 }
             }
         }
+        "meminfo" => {
+            let mem = crate::frame_allocator::stats();
+            let _ = writeln!(writer, "Total:      {:>8} KiB", mem.total_bytes / 1024);
+            let _ = writeln!(writer, "Reservado:  {:>8} KiB", mem.reserved_bytes / 1024);
+            let _ = writeln!(writer, "Utilizavel: {:>8} KiB", mem.usable_bytes / 1024);
+            let _ = writeln!(writer, "Usado:      {:>8} KiB", mem.used_bytes / 1024);
+            let _ = writeln!(writer, "Livre:      {:>8} KiB", mem.free_bytes / 1024);
+        }
+        "vtop" => {
+            let hex = args.trim_start_matches("0x");
+            match u64::from_str_radix(hex, 16).ok().and_then(|addr| x86_64::VirtAddr::try_new(addr).ok()) {
+                Some(addr) => match crate::memory::translate(addr) {
+                    Some(phys) => { let _ = writeln!(writer, "{:#x} -> {:#x}", addr.as_u64(), phys.as_u64()); }
+                    None => { let _ = writeln!(writer, "{:#x} não está mapeado", addr.as_u64()); }
+                },
+                None => print(writer, "Uso: vtop <endereço em hex>\n"),
+            }
+        }
+        "heap" => {
+            let heap = crate::allocator::stats();
+            let _ = writeln!(writer, "Heap:        {:>8} KiB", heap.heap_size / 1024);
+            let _ = writeln!(writer, "Em uso:      {:>8} bytes (pico {})", heap.in_use, heap.peak);
+            let _ = writeln!(writer, "Alocacoes:   {:>8} (frees {})", heap.allocations, heap.frees);
+            let _ = writeln!(writer, "Blocos livres: {:>6} (maior {} bytes)", heap.free_blocks, heap.largest_free);
+            let _ = writeln!(writer, "Fragmentacao: {:>6}%", heap.fragmentation());
+        }
+        "slabinfo" => {
+            let _ = writeln!(writer, "Classe  Alocs     Frees     Em uso  Slabs");
+            for class in crate::allocator::slab_stats().iter() {
+                let _ = writeln!(writer, "{:>6}  {:<8}  {:<8}  {:<6}  {}",
+                    class.size, class.allocations, class.frees, class.in_use, class.slabs);
+            }
+        }
+        "slabbench" => {
+            print(writer, "Medindo (ciclos TSC por alloc+free)...\n");
+            for result in crate::slab::benchmark() {
+                let _ = writeln!(writer, "  {:>5} bytes: slab {:>6}  heap {:>6}",
+                    result.size, result.slab_cycles, result.heap_cycles);
+            }
+        }
+        "uptime" => {
+            let ms = crate::timer::uptime_ms();
+            let secs = ms / 1000;
+            let _ = writeln!(writer, "Uptime: {}h {:02}m {:02}.{:03}s ({} ticks)",
+                secs / 3600, (secs / 60) % 60, secs % 60, ms % 1000, crate::timer::ticks());
+        }
+        "sleep" => match args.parse::<u64>() {
+            Ok(ms) => crate::timer::sleep_ms(ms),
+            Err(_) => print(writer, "Uso: sleep <ms>\n"),
+        },
+        "time" if !args.is_empty() => {
+            let start = crate::clock::nanos();
+            handle_command(writer, args, history);
+            let elapsed = crate::clock::nanos() - start;
+            let _ = writeln!(writer, "real {}.{:06} ms ({})",
+                elapsed / 1_000_000, elapsed % 1_000_000, crate::clock::source());
+        }
+        "time" => print(writer, "Uso: time <comando>\n"),
+        "bg" if !args.is_empty() => {
+            let command = String::from(args);
+            let name = String::from(command.split(' ').next().unwrap_or("bg"));
+            let spawned = crate::scheduler::spawn(&name, move || {
+                handle_command(&mut crate::vga::Console, &command, &VecDeque::new());
+            });
+            match spawned {
+                Ok(id) => { let _ = writeln!(writer, "[{}] {}", id, args); }
+                Err(err) => { let _ = writeln!(writer, "bg: {}", err); }
+            }
+        }
+        "bg" => print(writer, "Uso: bg <comando>\n"),
+        "wait" => match args.parse::<u64>() {
+            Ok(id) if id == crate::scheduler::current() => print(writer, "wait: a thread não pode esperar a si mesma\n"),
+            Ok(id) if crate::scheduler::join(id) => { let _ = writeln!(writer, "[{}] terminou", id); }
+            Ok(id) => { let _ = writeln!(writer, "wait: thread {} não existe ou não pode ser esperada (idle/detached)", id); }
+            Err(_) => print(writer, "Uso: wait <tid>\n"),
+        },
+        "ps" => {
+            let _ = writeln!(writer, "  TID   PID  ESTADO    CPU(ms)  NOME");
+            for thread in crate::scheduler::threads() {
+                let state = match thread.state {
+                    crate::scheduler::ThreadState::Ready => "pronta",
+                    crate::scheduler::ThreadState::Running => "rodando",
+                    crate::scheduler::ThreadState::Blocked => "bloqueada",
+                    crate::scheduler::ThreadState::Sleeping => "dormindo",
+                    crate::scheduler::ThreadState::Dead => "morta",
+                };
+                let pid = crate::process::pid_of_thread(thread.id)
+                    .map_or(String::from("-"), |pid| alloc::format!("{}", pid));
+                let _ = writeln!(writer, "{:>5} {:>5}  {:<9} {:>8}  {}", thread.id, pid, state, thread.cpu_ms, thread.name);
+            }
+        }
+        "exec" if !args.is_empty() => {
+            let mut words = args.split_whitespace();
+            let path = words.next().unwrap_or("");
+            let argv: Vec<&str> = words.collect();
+            // Threads do bg não são processos: os filhos delas ficam com o PID 1
+            let parent = crate::process::current_pid().unwrap_or(crate::process::INIT_PID);
+            match crate::process::spawn(parent, path, &argv) {
+                Ok(pid) => {
+                    // Só o shell (PID 1) tem primeiro plano; no bg, Ctrl+C não o atinge
+                    let foreground = crate::process::current_pid() == Some(crate::process::INIT_PID);
+                    if foreground {
+                        crate::process::set_foreground(Some(pid));
+                    }
+                    let result = crate::process::wait(parent, Some(pid), false);
+                    if foreground {
+                        crate::process::set_foreground(None);
+                    }
+                    match result {
+                        Ok(Some((_, status))) => { let _ = writeln!(writer, "[{}] {} {}", pid, path, status); }
+                        Ok(None) => {}
+                        Err(err) => { let _ = writeln!(writer, "exec: wait: {}", err); }
+                    }
+                }
+                Err(err) => { let _ = writeln!(writer, "exec: {}: {}", path, err); }
+            }
+        }
+        "exec" => print(writer, "Uso: exec <arquivo> [args]\n"),
+        "kill" => match args.parse::<u64>() {
+            Ok(pid) => match crate::process::kill(pid) {
+                Ok(()) => { let _ = writeln!(writer, "[{}] kill enviado", pid); }
+                Err(err) => { let _ = writeln!(writer, "kill: {}", err); }
+            },
+            Err(_) => print(writer, "Uso: kill <pid>\n"),
+        },
+        "alarm" => {
+            let (periodic, ms) = match args.strip_prefix("-p") {
+                Some(rest) => (true, rest.trim()),
+                None => (false, args),
+            };
+            match ms.parse::<u64>() {
+                Ok(ms) if periodic => {
+                    let handle = crate::timer_wheel::schedule_periodic(ms, move || {
+                        crate::println!("\n[alarme] mais {} ms", ms);
+                    });
+                    let _ = writeln!(writer, "Alarme {} a cada {} ms (cancel {} para parar)", handle.id(), ms, handle.id());
+                }
+                Ok(ms) => {
+                    let handle = crate::timer_wheel::schedule_once(ms, move || {
+                        crate::println!("\n[alarme] {} ms se passaram", ms);
+                    });
+                    let _ = writeln!(writer, "Alarme {} agendado para daqui a {} ms", handle.id(), ms);
+                }
+                Err(_) => print(writer, "Uso: alarm [-p] <ms>\n"),
+            }
+        }
+        "cancel" => match args.parse::<u64>() {
+            Ok(id) if crate::timer_wheel::cancel(crate::timer_wheel::TimerHandle::from_id(id)) => {
+                let _ = writeln!(writer, "Timer {} cancelado", id);
+            }
+            Ok(id) => { let _ = writeln!(writer, "cancel: timer {} não está pendente", id); }
+            Err(_) => print(writer, "Uso: cancel <id>\n"),
+        },
+        "timers" => {
+            let _ = writeln!(writer, "Timers pendentes: {}", crate::timer_wheel::pending_count());
+        }
+        "date" => {
+            let _ = writeln!(writer, "{} UTC (unix {})", crate::rtc::now(), crate::rtc::timestamp());
+            let _ = writeln!(writer, "RTC IRQ 8: {} ticks", crate::rtc::periodic_ticks());
+        }
+        "dmesg" => {
+            for line in crate::log::lines() {
+                print(writer, &line);
+                print(writer, "\n");
+            }
+        }
+        "ls" => {
+            for path in crate::virtual_fs::list_files() {
+                if let Some(meta) = crate::virtual_fs::metadata(&path) {
+                    let modified = crate::rtc::DateTime::from_unix(meta.modified);
+                    let _ = writeln!(writer, "{}  {:>6}  {}", modified, meta.size, path);
+                }
+            }
+        }
+        "cat" => {
+            match crate::virtual_fs::read_file(args) {
+                Some(content) => {
+                    print(writer, &String::from_utf8_lossy(&content));
+                    print(writer, "\n");
+                }
+                None => print(writer, "Arquivo não encontrado.\n"),
+            }
+        }
+        "acpi" => {
+            let Some(rsdp) = crate::acpi::rsdp() else {
+                print(writer, "ACPI não inicializado.\n");
+                return;
+            };
+            let _ = writeln!(writer, "RSDP: revisão {}, OEM '{}'",
+                rsdp.revision, core::str::from_utf8(&rsdp.oem_id).unwrap_or("?"));
+            for (signature, addr) in crate::acpi::tables() {
+                let header = crate::acpi::read_header(addr);
+                let _ = writeln!(writer, "  {}  {:#010x}  {:>6} bytes  rev {}  OEM '{}'",
+                    core::str::from_utf8(&signature).unwrap_or("????"), addr.as_u64(),
+                    { header.length }, header.revision,
+                    core::str::from_utf8(&header.oem_id).unwrap_or("?"));
+            }
+            if let Some(madt) = crate::acpi::madt() {
+                let _ = writeln!(writer, "MADT: LAPIC {:#x}, {} CPU(s), 8259 presente: {}",
+                    madt.local_apic_address, madt.local_apic_ids.len(), madt.has_legacy_pics);
+                for io in madt.io_apics.iter() {
+                    let _ = writeln!(writer, "  I/O APIC {} em {:#x}, GSI base {}", io.id, io.address, io.gsi_base);
+                }
+                for o in madt.overrides.iter() {
+                    let _ = writeln!(writer, "  IRQ {} -> GSI {} (flags {:#x})", o.source, o.gsi, o.flags);
+                }
+            }
+            if let Some(fadt) = crate::acpi::fadt() {
+                let _ = writeln!(writer, "FADT: rev {}, flags {:#x}, SCI {}, SMI_CMD {:#x} (enable {:#x}, disable {:#x}), 8042: {}",
+                    fadt.revision, fadt.flags, fadt.sci_interrupt, fadt.smi_command, fadt.acpi_enable,
+                    fadt.acpi_disable, fadt.has_8042);
+                let _ = writeln!(writer, "  PM1a evt {:#x} cnt {:#x}, PM1b evt {:#x} cnt {:#x}, PM timer {:#x} ({} bits)",
+                    fadt.pm1a_event, fadt.pm1a_control, fadt.pm1b_event, fadt.pm1b_control, fadt.pm_timer,
+                    if fadt.pm_timer_32bit { 32 } else { 24 });
+                if let Some((reg, value)) = fadt.reset {
+                    let _ = writeln!(writer, "  Reset: {:#x} (espaço {}, {} bits em {}, acesso {}) <- {:#x}",
+                        reg.address, reg.address_space, reg.bit_width, reg.bit_offset, reg.access_size, value);
+                }
+            }
+            if let Some(hpet) = crate::acpi::hpet() {
+                let _ = writeln!(writer, "HPET {}: {:#x}, {} comparadores, {} bits, tick mínimo {}",
+                    hpet.hpet_number, hpet.base.address, hpet.comparators, if hpet.counter_64bit { 64 } else { 32 },
+                    hpet.minimum_tick);
+                let _ = writeln!(writer, "  fabricante {:#06x}, rev {}, legacy replacement: {}",
+                    hpet.vendor_id, hpet.hardware_revision, hpet.legacy_replacement);
+            }
+        }
+        "cpuinfo" => {
+            let cpu = crate::cpuid::info();
+            let _ = writeln!(writer, "Fabricante: {}", cpu.vendor_str());
+            let _ = writeln!(writer, "Modelo:     {}", cpu.brand_str());
+            let _ = writeln!(writer, "Família {:#x}, modelo {:#x}, stepping {}, {} CPU(s) lógicas",
+                cpu.family, cpu.model, cpu.stepping, cpu.logical_cpus);
+            if let Some(hypervisor) = cpu.hypervisor_str() {
+                let _ = writeln!(writer, "Hypervisor: {}", hypervisor);
+            }
+            if crate::clock::tsc_khz() != 0 {
+                let _ = writeln!(writer, "TSC:        {} MHz", crate::clock::tsc_khz() / 1000);
+            }
+            print(writer, "Recursos:  ");
+            for feature in cpu.features() {
+                let _ = write!(writer, " {}", feature.name());
+            }
+            print(writer, "\n");
+        }
+        "keys" => {
+            use crate::keyboard::{self, KeyCode};
+            print(writer, "Eventos do teclado (Esc para sair):\n");
+            // Descarta os eventos da digitação do próprio comando
+            while keyboard::next_event().is_some() {}
+            loop {
+                let event = keyboard::wait_event();
+                let mods = event.modifiers;
+                let _ = writeln!(writer, "{:?} {}{} shift={} ctrl={} alt={} leds={:03b} ch={:?}",
+                    event.key, if event.pressed { "aperta" } else { "solta" },
+                    if event.repeat { " (repete)" } else { "" },
+                    mods.shift(), mods.ctrl(), mods.alt(), mods.leds(), event.ch);
+                if event.pressed && event.key == KeyCode::Escape {
+                    break;
+                }
+            }
+            while keyboard::get_key().is_some() {}
+        }
+        "mouse" => match if args.is_empty() { Ok(10) } else { args.parse::<usize>() } {
+            Ok(count) => {
+                while crate::mouse::next_event().is_some() {}
+                for _ in 0..count {
+                    let _ = writeln!(writer, "{:?}", crate::mouse::wait_event());
+                }
+            }
+            Err(_) => print(writer, "Uso: mouse [n]\n"),
+        },
+        "serial" => serial_command(writer, args),
+        "loadkeys" if !args.is_empty() => match crate::keymap::find(args) {
+            Some(layout) => {
+                crate::keyboard::set_layout(layout);
+                let _ = writeln!(writer, "Layout: {} ({})", layout.name, layout.description);
+            }
+            None => { let _ = writeln!(writer, "loadkeys: layout desconhecido: {}", args); }
+        },
+        "loadkeys" => {
+            let current = crate::keyboard::layout();
+            for layout in crate::keymap::LAYOUTS {
+                let mark = if core::ptr::eq(layout, current) { '*' } else { ' ' };
+                let _ = writeln!(writer, "{} {:<6} {}", mark, layout.name, layout.description);
+            }
+        }
         "" => {} // Enter vazio
         _ => {
             print(writer, "Comando não reconhecido. Digite 'help'.\n");
@@ -160,6 +494,53 @@ fn handle_command(
     }
 }
 
+/// `serial`: lista as portas; `serial comN ...` configura, envia ou lê.
+fn serial_command(writer: &mut dyn Writer, args: &str) {
+    use crate::serial::{self, Com, LineConfig, State};
+    if args.is_empty() {
+        for com in Com::ALL {
+            let (state, config) = serial::status(com);
+            let _ = write!(writer, "{}  {:#x}  IRQ {}  ", com, com.base(), com.irq());
+            let _ = match state {
+                State::Absent => writeln!(writer, "ausente"),
+                State::Faulty => writeln!(writer, "falhou no loopback"),
+                State::Polled => writeln!(writer, "{} (polling)", config),
+                State::Interrupts => writeln!(writer, "{}", config),
+            };
+        }
+        return;
+    }
+    let (name, rest) = args.split_once(' ').unwrap_or((args, ""));
+    let Some(com) = Com::parse(name) else {
+        print(writer, "Uso: serial [comN [baud,8N1 | send texto | recv]]\n");
+        return;
+    };
+    match rest.split_once(' ').unwrap_or((rest, "")) {
+        ("send", text) => {
+            serial::write(com, text.as_bytes());
+            serial::write(com, b"\r\n");
+        }
+        ("recv", _) => {
+            let mut received = Vec::new();
+            while let Some(byte) = serial::read(com) {
+                received.push(byte);
+            }
+            let _ = writeln!(writer, "{} bytes: {}", received.len(), String::from_utf8_lossy(&received));
+        }
+        ("", _) => {
+            let (_, config) = serial::status(com);
+            let _ = writeln!(writer, "{}: {}", com, config);
+        }
+        (config, _) => match LineConfig::parse(config) {
+            Some(config) => match serial::configure(com, config) {
+                Ok(()) => { let _ = writeln!(writer, "{}: {}", com, config); }
+                Err(err) => { let _ = writeln!(writer, "serial: {}: {}", com, err); }
+            },
+            None => { let _ = writeln!(writer, "serial: configuração inválida: {}", config); }
+        },
+    }
+}
+
 // Função para formatar u8 como string
 fn u8_to_str(n: u8) -> &'static str {
     static mut BUF: [u8; 3] = [0; 3]; // 2 dígitos + null
@@ -0,0 +1,160 @@
+// src/slab.rs
+// ====================
+// SLAB - Classes de tamanho fixo na frente do heap geral
+// ====================
+
+use core::alloc::Layout;
+use core::ptr;
+use alloc::vec::Vec;
+use crate::allocator::{self, LinkedListHeap};
+
+pub const CLASS_SIZES: [usize; 8] = [16, 32, 64, 128, 256, 512, 1024, 2048];
+pub const CLASS_COUNT: usize = CLASS_SIZES.len();
+
+// Cada slab é uma página pedida ao heap geral e fatiada em objetos iguais
+const SLAB_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassStats {
+    pub size: usize,
+    pub allocations: u64,
+    pub frees: u64,
+    pub in_use: usize,
+    pub slabs: usize,
+}
+
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+struct SizeClass {
+    free: *mut FreeObject,
+    stats: ClassStats,
+}
+
+pub struct SlabAllocator {
+    classes: [SizeClass; CLASS_COUNT],
+}
+
+// Mesmo raciocínio do LinkedListHeap: só acessado com o lock do alocador
+unsafe impl Send for SlabAllocator {}
+
+impl SlabAllocator {
+    pub const fn new() -> Self {
+        const EMPTY: SizeClass = SizeClass {
+            free: ptr::null_mut(),
+            stats: ClassStats { size: 0, allocations: 0, frees: 0, in_use: 0, slabs: 0 },
+        };
+        let mut classes = [EMPTY; CLASS_COUNT];
+        let mut i = 0;
+        while i < CLASS_COUNT {
+            classes[i].stats.size = CLASS_SIZES[i];
+            i += 1;
+        }
+        SlabAllocator { classes }
+    }
+
+    /// Classe que atende `layout`, ou None para o caminho do heap geral.
+    /// Os objetos ficam alinhados ao próprio tamanho (potência de 2).
+    pub fn class_for(layout: Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align());
+        CLASS_SIZES.iter().position(|&class| size <= class)
+    }
+
+    pub fn allocate(&mut self, class: usize, backing: &mut LinkedListHeap) -> *mut u8 {
+        if self.classes[class].free.is_null() && !self.refill(class, backing) {
+            return ptr::null_mut();
+        }
+        let class = &mut self.classes[class];
+        let object = class.free;
+        unsafe { class.free = (*object).next; }
+        class.stats.allocations += 1;
+        class.stats.in_use += 1;
+        object as *mut u8
+    }
+
+    /// # Safety
+    /// `ptr` precisa ter vindo de `allocate` na mesma classe.
+    pub unsafe fn deallocate(&mut self, class: usize, ptr: *mut u8) {
+        let class = &mut self.classes[class];
+        let object = ptr as *mut FreeObject;
+        object.write(FreeObject { next: class.free });
+        class.free = object;
+        class.stats.frees += 1;
+        class.stats.in_use -= 1;
+    }
+
+    // Pede um slab novo ao heap geral. As páginas nunca voltam ao heap:
+    // o ganho vem justamente de não passar mais pela lista ligada.
+    fn refill(&mut self, class: usize, backing: &mut LinkedListHeap) -> bool {
+        let layout = Layout::from_size_align(SLAB_SIZE, SLAB_SIZE).unwrap();
+        let slab = backing.allocate(layout);
+        if slab.is_null() {
+            return false;
+        }
+        let size = CLASS_SIZES[class];
+        let class = &mut self.classes[class];
+        for offset in (0..SLAB_SIZE).step_by(size).rev() {
+            unsafe {
+                let object = slab.add(offset) as *mut FreeObject;
+                object.write(FreeObject { next: class.free });
+                class.free = object;
+            }
+        }
+        class.stats.slabs += 1;
+        true
+    }
+
+    pub fn stats(&self) -> [ClassStats; CLASS_COUNT] {
+        core::array::from_fn(|i| self.classes[i].stats)
+    }
+}
+
+// --- Benchmark: slab vs. heap geral ---
+
+#[derive(Debug, Clone, Copy)]
+pub struct BenchResult {
+    pub size: usize,
+    pub slab_cycles: u64,
+    pub heap_cycles: u64,
+}
+
+const BENCH_SIZES: [usize; 4] = [16, 64, 256, 1024];
+const BENCH_OBJECTS: usize = 256;
+const BENCH_ROUNDS: usize = 16;
+
+/// Ciclos (TSC) por par alloc+free em cada caminho. Os objetos são liberados
+/// em ordem intercalada para o heap geral sentir a fragmentação real.
+pub fn benchmark() -> Vec<BenchResult> {
+    let mut ptrs: Vec<*mut u8> = Vec::with_capacity(BENCH_OBJECTS);
+    BENCH_SIZES
+        .iter()
+        .map(|&size| {
+            let layout = Layout::from_size_align(size, 8).unwrap();
+            BenchResult {
+                size,
+                slab_cycles: bench_path(layout, true, &mut ptrs),
+                heap_cycles: bench_path(layout, false, &mut ptrs),
+            }
+        })
+        .collect()
+}
+
+fn bench_path(layout: Layout, via_slab: bool, ptrs: &mut Vec<*mut u8>) -> u64 {
+    let start = unsafe { core::arch::x86_64::_rdtsc() };
+    for _ in 0..BENCH_ROUNDS {
+        for _ in 0..BENCH_OBJECTS {
+            let ptr = unsafe { allocator::bench_alloc(layout, via_slab) };
+            assert!(!ptr.is_null(), "heap esgotado no benchmark");
+            ptrs.push(ptr);
+        }
+        for parity in 0..2 {
+            for ptr in ptrs.iter().skip(parity).step_by(2) {
+                unsafe { allocator::bench_dealloc(*ptr, layout, via_slab); }
+            }
+        }
+        ptrs.clear();
+    }
+    let end = unsafe { core::arch::x86_64::_rdtsc() };
+    (end - start) / (BENCH_ROUNDS * BENCH_OBJECTS) as u64
+}
@@ -0,0 +1,333 @@
+// src/sync.rs
+// ====================
+// SINCRONIZAÇÃO - Spinlock com IRQs desligadas, filas de espera, Mutex,
+// Semaphore e Condvar que bloqueiam a thread em vez de girar
+// ====================
+//
+// Regra de uso:
+// - Estado tocado por handlers de interrupção: `IrqSpinlock` (seção curta,
+//   nunca dorme).
+// - Estado de threads com seções longas ou que esperam: `Mutex`/`Condvar`/
+//   `Semaphore` (nunca em contexto de interrupção).
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use alloc::collections::VecDeque;
+use x86_64::instructions::interrupts;
+use crate::scheduler::{self, ThreadId};
+
+// --- IrqSpinlock ---
+
+/// Spinlock que desliga as interrupções enquanto está travado: um ISR nunca
+/// encontra o lock ocupado pela thread que ele interrompeu.
+pub struct IrqSpinlock<T> {
+    inner: spin::Mutex<T>,
+}
+
+pub struct IrqSpinlockGuard<'a, T> {
+    guard: Option<spin::MutexGuard<'a, T>>,
+    were_enabled: bool,
+}
+
+impl<T> IrqSpinlock<T> {
+    pub const fn new(value: T) -> Self {
+        IrqSpinlock { inner: spin::Mutex::new(value) }
+    }
+
+    pub fn lock(&self) -> IrqSpinlockGuard<'_, T> {
+        let were_enabled = interrupts::are_enabled();
+        interrupts::disable();
+        IrqSpinlockGuard { guard: Some(self.inner.lock()), were_enabled }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+
+    /// # Safety
+    /// Só em caminhos fatais, quando o dono do lock nunca mais vai rodar.
+    pub unsafe fn force_unlock(&self) {
+        self.inner.force_unlock();
+    }
+}
+
+impl<T> Deref for IrqSpinlockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for IrqSpinlockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.guard.as_mut().unwrap()
+    }
+}
+
+impl<T> Drop for IrqSpinlockGuard<'_, T> {
+    fn drop(&mut self) {
+        // Solta o lock antes de religar as interrupções
+        drop(self.guard.take());
+        if self.were_enabled {
+            interrupts::enable();
+        }
+    }
+}
+
+// --- WaitQueue ---
+
+/// Fila de threads esperando uma condição. `wake_*` pode ser chamado de ISR.
+pub struct WaitQueue {
+    waiters: IrqSpinlock<VecDeque<ThreadId>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        WaitQueue { waiters: IrqSpinlock::new(VecDeque::new()) }
+    }
+
+    /// Bloqueia até `ready()` ser verdadeiro. A condição é conferida com as
+    /// interrupções desligadas junto com a entrada na fila, então um
+    /// `wake_*` vindo de ISR entre os dois não se perde.
+    pub fn wait_until(&self, mut ready: impl FnMut() -> bool) {
+        loop {
+            let done = interrupts::without_interrupts(|| {
+                if ready() {
+                    return true;
+                }
+                if scheduler::is_running() {
+                    self.waiters.lock().push_back(scheduler::current());
+                    scheduler::block();
+                } else {
+                    // Antes do escalonador: espera o próximo IRQ
+                    interrupts::enable_and_hlt();
+                }
+                false
+            });
+            if done {
+                return;
+            }
+        }
+    }
+
+    pub fn wake_one(&self) {
+        if let Some(id) = self.waiters.lock().pop_front() {
+            scheduler::unblock(id);
+        }
+    }
+
+    pub fn wake_all(&self) {
+        let waiters = core::mem::take(&mut *self.waiters.lock());
+        for id in waiters {
+            scheduler::unblock(id);
+        }
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        WaitQueue::new()
+    }
+}
+
+// --- Detecção de deadlock (só em debug) ---
+
+#[cfg(debug_assertions)]
+mod deadlock {
+    use alloc::collections::BTreeMap;
+    use super::IrqSpinlock;
+    use crate::scheduler::ThreadId;
+
+    // Grafo de espera: mutex -> dono, thread -> mutex que ela espera
+    struct LockGraph {
+        owners: BTreeMap<usize, ThreadId>,
+        waiting: BTreeMap<ThreadId, usize>,
+    }
+
+    static GRAPH: IrqSpinlock<LockGraph> = IrqSpinlock::new(LockGraph {
+        owners: BTreeMap::new(),
+        waiting: BTreeMap::new(),
+    });
+
+    /// Antes de bloquear em `lock`: segue dono -> mutex esperado -> dono...
+    /// e entra em pânico se o caminho voltar para `me`.
+    pub fn before_wait(lock: usize, me: ThreadId) {
+        let mut graph = GRAPH.lock();
+        let mut target = lock;
+        while let Some(&owner) = graph.owners.get(&target) {
+            if owner == me {
+                drop(graph);
+                panic!("deadlock: thread {} espera o mutex {:#x}, que depende dela", me, lock);
+            }
+            match graph.waiting.get(&owner) {
+                Some(&next) => target = next,
+                None => break,
+            }
+        }
+        graph.waiting.insert(me, lock);
+    }
+
+    pub fn acquired(lock: usize, me: ThreadId) {
+        let mut graph = GRAPH.lock();
+        graph.waiting.remove(&me);
+        graph.owners.insert(lock, me);
+    }
+
+    pub fn released(lock: usize) {
+        GRAPH.lock().owners.remove(&lock);
+    }
+}
+
+// --- Mutex ---
+
+/// Mutex que bloqueia a thread enquanto outro dono segura o lock.
+pub struct Mutex<T> {
+    locked: AtomicBool,
+    queue: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Mutex { locked: AtomicBool::new(false), queue: WaitQueue::new(), data: UnsafeCell::new(value) }
+    }
+
+    fn id(&self) -> usize {
+        self as *const _ as usize
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return None;
+        }
+        #[cfg(debug_assertions)]
+        deadlock::acquired(self.id(), scheduler::current());
+        Some(MutexGuard { mutex: self })
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        if let Some(guard) = self.try_lock() {
+            return guard;
+        }
+        #[cfg(debug_assertions)]
+        deadlock::before_wait(self.id(), scheduler::current());
+        let mut guard = None;
+        self.queue.wait_until(|| {
+            guard = self.try_lock();
+            guard.is_some()
+        });
+        guard.unwrap()
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        deadlock::released(self.mutex.id());
+        self.mutex.locked.store(false, Ordering::Release);
+        self.mutex.queue.wake_one();
+    }
+}
+
+// --- Semaphore ---
+// Semaphore e Condvar completam a API das regras de uso acima; os
+// subsistemas atuais só precisam de Mutex e WaitQueue, daí o allow.
+
+#[allow(dead_code)]
+pub struct Semaphore {
+    count: AtomicUsize,
+    queue: WaitQueue,
+}
+
+#[allow(dead_code)]
+impl Semaphore {
+    pub const fn new(count: usize) -> Self {
+        Semaphore { count: AtomicUsize::new(count), queue: WaitQueue::new() }
+    }
+
+    pub fn try_acquire(&self) -> bool {
+        self.count.fetch_update(Ordering::Acquire, Ordering::Relaxed, |n| n.checked_sub(1)).is_ok()
+    }
+
+    /// P(): bloqueia até haver uma unidade disponível.
+    pub fn acquire(&self) {
+        self.queue.wait_until(|| self.try_acquire());
+    }
+
+    /// V(): pode ser chamado de ISR.
+    pub fn release(&self) {
+        self.count.fetch_add(1, Ordering::Release);
+        self.queue.wake_one();
+    }
+
+    pub fn available(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+// --- Condvar ---
+
+#[allow(dead_code)]
+pub struct Condvar {
+    waiters: IrqSpinlock<VecDeque<ThreadId>>,
+}
+
+#[allow(dead_code)]
+impl Condvar {
+    pub const fn new() -> Self {
+        Condvar { waiters: IrqSpinlock::new(VecDeque::new()) }
+    }
+
+    /// Solta o mutex e dorme até um `notify_*`; volta com o mutex travado.
+    /// Como em qualquer condvar, quem chama confere a condição num laço.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex;
+        // Entrar na fila, soltar o mutex e bloquear sem um notify no meio
+        interrupts::without_interrupts(|| {
+            self.waiters.lock().push_back(scheduler::current());
+            drop(guard);
+            scheduler::block();
+        });
+        mutex.lock()
+    }
+
+    pub fn notify_one(&self) {
+        if let Some(id) = self.waiters.lock().pop_front() {
+            scheduler::unblock(id);
+        }
+    }
+
+    pub fn notify_all(&self) {
+        let waiters = core::mem::take(&mut *self.waiters.lock());
+        for id in waiters {
+            scheduler::unblock(id);
+        }
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Condvar::new()
+    }
+}
@@ -0,0 +1,407 @@
+// src/syscall.rs
+// ====================
+// SYSCALLS - Entrada via SYSCALL/SYSRET (MSRs) e `int 0x80`, despacho e ABI
+// ====================
+//
+// ABI (números e registradores no estilo Linux x86_64):
+//   rax = número, argumentos em rdi, rsi, rdx, r10, r8, r9
+//   retorno em rax; erro = -errno
+// O SYSCALL estraga rcx (RIP de retorno) e r11 (RFLAGS); o resto volta
+// intacto. O `int 0x80` usa os mesmos números e registradores.
+//
+//   0 read(fd, buf, len)          1 write(fd, buf, len)
+//   2 open(path, flags)           3 close(fd)
+//  35 nanosleep(req, rem)        39 getpid()
+//  60 exit(code)                 61 wait4(pid, status, options, rusage)
+//  62 kill(pid, sig)             79 getcwd(buf, size)
+//  80 chdir(path)               110 getppid()
+
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::interrupts;
+use x86_64::registers::model_specific::{Efer, EferFlags, LStar, SFMask, Star};
+use x86_64::registers::rflags::RFlags;
+use x86_64::VirtAddr;
+use crate::process::{self, ExitStatus, FileDescriptor, WaitError};
+use crate::usermode;
+
+pub const SYS_READ: u64 = 0;
+pub const SYS_WRITE: u64 = 1;
+pub const SYS_OPEN: u64 = 2;
+pub const SYS_CLOSE: u64 = 3;
+pub const SYS_NANOSLEEP: u64 = 35;
+pub const SYS_GETPID: u64 = 39;
+pub const SYS_EXIT: u64 = 60;
+pub const SYS_WAIT4: u64 = 61;
+pub const SYS_KILL: u64 = 62;
+pub const SYS_GETCWD: u64 = 79;
+pub const SYS_CHDIR: u64 = 80;
+pub const SYS_GETPPID: u64 = 110;
+
+pub const EPERM: i64 = 1;
+pub const ENOENT: i64 = 2;
+pub const ESRCH: i64 = 3;
+pub const EINTR: i64 = 4;
+pub const EBADF: i64 = 9;
+pub const ECHILD: i64 = 10;
+pub const EFAULT: i64 = 14;
+pub const EINVAL: i64 = 22;
+pub const EMFILE: i64 = 24;
+pub const ERANGE: i64 = 34;
+pub const ENOSYS: i64 = 38;
+
+/// Só leitura por enquanto: o FS virtual não tem escrita parcial.
+pub const O_RDONLY: u64 = 0;
+pub const WNOHANG: u64 = 1;
+
+const PATH_MAX: usize = 256;
+// Teto por chamada de read/write: evita prender o console por muito tempo
+const MAX_IO: u64 = 64 * 1024;
+
+// --- Entrada ---
+
+// Topo da pilha do kernel da thread atual (= TSS.RSP0) e rascunho para o
+// RSP do usuário: o SYSCALL não troca de pilha sozinho. O FMASK desliga IF,
+// então nada interrompe até o RSP do usuário estar salvo na pilha do kernel.
+#[no_mangle]
+static SYSCALL_KERNEL_RSP: AtomicU64 = AtomicU64::new(0);
+#[no_mangle]
+static SYSCALL_USER_RSP: AtomicU64 = AtomicU64::new(0);
+
+/// Argumentos empilhados pelos stubs, na ordem do struct.
+#[repr(C)]
+struct SyscallFrame {
+    number: u64,
+    args: [u64; 6],
+}
+
+global_asm!(r#"
+.global tri_syscall_entry
+tri_syscall_entry:
+    mov [rip + SYSCALL_USER_RSP], rsp
+    mov rsp, [rip + SYSCALL_KERNEL_RSP]
+    push qword ptr [rip + SYSCALL_USER_RSP]
+    push rcx
+    push r11
+    push r9
+    push r8
+    push r10
+    push rdx
+    push rsi
+    push rdi
+    push rax
+    mov rdi, rsp
+    call tri_syscall_dispatch
+    cli
+    add rsp, 8
+    pop rdi
+    pop rsi
+    pop rdx
+    pop r10
+    pop r8
+    pop r9
+    pop r11
+    pop rcx
+    pop rsp
+    sysretq
+
+.global tri_int80_entry
+tri_int80_entry:
+    push rcx
+    push r11
+    push r9
+    push r8
+    push r10
+    push rdx
+    push rsi
+    push rdi
+    push rax
+    mov rdi, rsp
+    call tri_syscall_dispatch
+    cli
+    add rsp, 8
+    pop rdi
+    pop rsi
+    pop rdx
+    pop r10
+    pop r8
+    pop r9
+    pop r11
+    pop rcx
+    iretq
+"#);
+
+extern "C" {
+    fn tri_syscall_entry();
+    fn tri_int80_entry();
+}
+
+/// Liga o SYSCALL/SYSRET. Precisa da GDT do kernel carregada.
+pub fn init() {
+    unsafe { Efer::update(|efer| efer.insert(EferFlags::SYSTEM_CALL_EXTENSIONS)) };
+    Star::write(
+        crate::gdt::user_code_selector(),
+        crate::gdt::user_data_selector(),
+        crate::gdt::kernel_code_selector(),
+        crate::gdt::kernel_data_selector(),
+    ).expect("ordem da GDT incompatível com o SYSRET");
+    LStar::write(VirtAddr::new(tri_syscall_entry as *const () as u64));
+    // Entra no kernel com IF, DF, TF e AC limpos
+    SFMask::write(RFlags::INTERRUPT_FLAG | RFlags::DIRECTION_FLAG
+        | RFlags::TRAP_FLAG | RFlags::ALIGNMENT_CHECK);
+    crate::klog!("Syscalls: SYSCALL/SYSRET e int 0x80 prontos");
+}
+
+/// Endereço do stub do `int 0x80` (entrada DPL 3 na IDT).
+pub fn int80_entry() -> VirtAddr {
+    VirtAddr::new(tri_int80_entry as *const () as u64)
+}
+
+/// Chamado por `gdt::set_kernel_stack` a cada troca de thread.
+pub fn set_kernel_stack(top: VirtAddr) {
+    SYSCALL_KERNEL_RSP.store(top.as_u64(), Ordering::Relaxed);
+}
+
+#[no_mangle]
+extern "C" fn tri_syscall_dispatch(frame: &SyscallFrame) -> i64 {
+    // Os stubs entram com IF desligado; a syscall pode dormir e ser preemptada
+    interrupts::enable();
+    let [a0, a1, a2, ..] = frame.args;
+    let result = match frame.number {
+        SYS_READ => sys_read(a0, a1, a2),
+        SYS_WRITE => sys_write(a0, a1, a2),
+        SYS_OPEN => sys_open(a0, a1),
+        SYS_CLOSE => sys_close(a0),
+        SYS_NANOSLEEP => sys_nanosleep(a0, a1),
+        SYS_GETPID => process::current_pid().map_or(-ESRCH, |pid| pid as i64),
+        SYS_EXIT => process::exit(ExitStatus::Exited(a0 as i32)),
+        SYS_WAIT4 => sys_wait4(a0 as i64, a1, a2),
+        SYS_KILL => sys_kill(a0 as i64, a1),
+        SYS_GETCWD => sys_getcwd(a0, a1),
+        SYS_CHDIR => sys_chdir(a0),
+        SYS_GETPPID => sys_getppid(),
+        _ => -ENOSYS,
+    };
+    // Um kill recebido durante a syscall vale antes de voltar ao ring 3
+    process::check_killed();
+    result
+}
+
+// --- Arquivos ---
+
+// O que fazer com um fd depois de olhar a tabela (com o lock solto)
+enum Target {
+    Console,
+    Done(i64),
+}
+
+fn sys_read(fd: u64, buf: u64, len: u64) -> i64 {
+    let Some(out) = usermode::user_slice_mut(buf, len.min(MAX_IO)) else {
+        return -EFAULT;
+    };
+    let target = process::with_fds(|fds| match fds.get_mut(fd as usize) {
+        Some(Some(FileDescriptor::Console)) => Target::Console,
+        Some(Some(FileDescriptor::File { data, offset })) => {
+            let count = out.len().min(data.len() - *offset);
+            out[..count].copy_from_slice(&data[*offset..*offset + count]);
+            *offset += count;
+            Target::Done(count as i64)
+        }
+        _ => Target::Done(-EBADF),
+    }).unwrap_or(Target::Done(-EBADF));
+    match target {
+        Target::Console if out.is_empty() => 0,
+        Target::Console => read_console(out),
+        Target::Done(result) => result,
+    }
+}
+
+// Bloqueia até a primeira tecla (ou um kill); depois pega o que já estiver
+// no buffer, parando no fim da linha
+fn read_console(out: &mut [u8]) -> i64 {
+    let mut count = 0;
+    let mut key = crate::keyboard::wait_key_unless(process::is_killed);
+    if key.is_none() {
+        return -EINTR;
+    }
+    while let Some(byte) = key {
+        out[count] = byte;
+        count += 1;
+        if byte == b'\n' || count == out.len() {
+            break;
+        }
+        key = crate::keyboard::get_key();
+    }
+    count as i64
+}
+
+fn sys_write(fd: u64, buf: u64, len: u64) -> i64 {
+    let Some(data) = usermode::user_slice(buf, len.min(MAX_IO)) else {
+        return -EFAULT;
+    };
+    let target = process::with_fds(|fds| match fds.get(fd as usize) {
+        Some(Some(FileDescriptor::Console)) => Target::Console,
+        // Arquivos abertos só para leitura
+        _ => Target::Done(-EBADF),
+    }).unwrap_or(Target::Done(-EBADF));
+    match target {
+        Target::Console => {
+            let mut writer = crate::vga::get_writer().lock();
+            for &byte in data {
+                writer.write_byte(byte);
+            }
+            drop(writer);
+            // Como a saída do shell (`vga::Console`), também no console serial
+            crate::serial::console_write(data);
+            data.len() as i64
+        }
+        Target::Done(result) => result,
+    }
+}
+
+fn sys_open(path: u64, flags: u64) -> i64 {
+    let Some(path) = usermode::user_cstr(path, PATH_MAX) else {
+        return -EFAULT;
+    };
+    if flags != O_RDONLY {
+        return -EINVAL;
+    }
+    let cwd = process::current_pid().and_then(process::cwd).unwrap_or_else(|| alloc::string::String::from("/"));
+    let Some(data) = crate::virtual_fs::read_file(&process::resolve(&cwd, &path)) else {
+        return -ENOENT;
+    };
+    process::with_fds(|fds| {
+        process::install_fd(fds, FileDescriptor::File { data, offset: 0 }).map_or(-EMFILE, |fd| fd as i64)
+    }).unwrap_or(-EBADF)
+}
+
+fn sys_close(fd: u64) -> i64 {
+    process::with_fds(|fds| match fds.get_mut(fd as usize) {
+        Some(slot @ Some(_)) => {
+            *slot = None;
+            0
+        }
+        _ => -EBADF,
+    }).unwrap_or(-EBADF)
+}
+
+/// struct timespec { tv_sec: i64, tv_nsec: i64 }. Um kill acorda a thread
+/// antes do prazo, mas ela morre sem voltar ao ring 3; `rem` volta zerado.
+fn sys_nanosleep(req: u64, rem: u64) -> i64 {
+    let Some(bytes) = usermode::user_slice(req, 16) else {
+        return -EFAULT;
+    };
+    let seconds = i64::from_le_bytes(bytes[..8].try_into().unwrap());
+    let nanos = i64::from_le_bytes(bytes[8..].try_into().unwrap());
+    let Some(ms) = timespec_to_ms(seconds, nanos) else {
+        return -EINVAL;
+    };
+    crate::timer::sleep_ms(ms);
+    if rem != 0 {
+        match usermode::user_slice_mut(rem, 16) {
+            Some(out) => out.fill(0),
+            None => return -EFAULT,
+        }
+    }
+    0
+}
+
+// Arredonda para cima: nunca dorme menos que o pedido
+fn timespec_to_ms(seconds: i64, nanos: i64) -> Option<u64> {
+    if seconds < 0 || !(0..1_000_000_000).contains(&nanos) {
+        return None;
+    }
+    (seconds as u64).checked_mul(1000)?.checked_add((nanos as u64).div_ceil(1_000_000))
+}
+
+// --- Processos ---
+
+/// wait4(pid, status, options, rusage): pid -1 = qualquer filho. `rusage`
+/// não é preenchido.
+fn sys_wait4(pid: i64, status: u64, options: u64) -> i64 {
+    let Some(me) = process::current_pid() else {
+        return -ECHILD;
+    };
+    if options & !WNOHANG != 0 || (pid != -1 && pid <= 0) {
+        return -EINVAL;
+    }
+    if status != 0 && !usermode::is_user_range(status, 4, true) {
+        return -EFAULT;
+    }
+    let wanted = if pid == -1 { None } else { Some(pid as u64) };
+    match process::wait(me, wanted, options & WNOHANG != 0) {
+        Ok(Some((child, exit))) => {
+            if let Some(out) = usermode::user_slice_mut(status, 4).filter(|_| status != 0) {
+                out.copy_from_slice(&exit.wait_status().to_le_bytes());
+            }
+            child as i64
+        }
+        Ok(None) => 0,
+        Err(WaitError::NoChild) => -ECHILD,
+        Err(WaitError::Interrupted) => -EINTR,
+    }
+}
+
+/// Só SIGKILL (e o sinal 0, que só testa se o processo existe).
+fn sys_kill(pid: i64, signal: u64) -> i64 {
+    if pid <= 0 {
+        return -EINVAL;
+    }
+    match signal {
+        0 => process::parent_of(pid as u64).map_or(-ESRCH, |_| 0),
+        9 => match process::kill(pid as u64) {
+            Ok(()) => 0,
+            Err(_) if pid as u64 == process::INIT_PID => -EPERM,
+            Err(_) => -ESRCH,
+        },
+        _ => -EINVAL,
+    }
+}
+
+fn sys_getppid() -> i64 {
+    process::current_pid().and_then(process::parent_of).map_or(-ESRCH, |pid| pid as i64)
+}
+
+/// Copia o cwd com o zero final; devolve o tamanho escrito.
+fn sys_getcwd(buf: u64, size: u64) -> i64 {
+    let Some(cwd) = process::current_pid().and_then(process::cwd) else {
+        return -ESRCH;
+    };
+    if (cwd.len() + 1) as u64 > size {
+        return -ERANGE;
+    }
+    let Some(out) = usermode::user_slice_mut(buf, cwd.len() as u64 + 1) else {
+        return -EFAULT;
+    };
+    out[..cwd.len()].copy_from_slice(cwd.as_bytes());
+    out[cwd.len()] = 0;
+    out.len() as i64
+}
+
+fn sys_chdir(path: u64) -> i64 {
+    let Some(path) = usermode::user_cstr(path, PATH_MAX) else {
+        return -EFAULT;
+    };
+    let Some(me) = process::current_pid() else {
+        return -ESRCH;
+    };
+    match process::chdir(me, &path) {
+        Ok(()) => 0,
+        Err(_) => -ENOENT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timespec_rounds_up_and_rejects_invalid() {
+        assert_eq!(timespec_to_ms(0, 0), Some(0));
+        assert_eq!(timespec_to_ms(1, 1), Some(1001));
+        assert_eq!(timespec_to_ms(0, 999_999_999), Some(1000));
+        assert_eq!(timespec_to_ms(-1, 0), None);
+        assert_eq!(timespec_to_ms(0, 1_000_000_000), None);
+        assert_eq!(timespec_to_ms(i64::MAX, 0), None);
+    }
+}
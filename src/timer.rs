@@ -0,0 +1,65 @@
+// src/timer.rs
+// ====================
+// PIT 8253/8254 - Tick global, uptime e sleep
+// ====================
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::port::Port;
+use x86_64::instructions::interrupts;
+
+/// Frequência programada no canal 0 (1 tick = 1 ms).
+pub const TIMER_HZ: u64 = 1000;
+const PIT_BASE_HZ: u64 = 1_193_182;
+
+const PIT_CHANNEL0: u16 = 0x40;
+const PIT_COMMAND: u16 = 0x43;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Canal 0, acesso lobyte/hibyte, modo 3 (onda quadrada).
+pub fn init() {
+    let divisor = (PIT_BASE_HZ / TIMER_HZ) as u16;
+    let mut command = Port::<u8>::new(PIT_COMMAND);
+    let mut channel0 = Port::<u8>::new(PIT_CHANNEL0);
+    unsafe {
+        command.write(0x36);
+        channel0.write((divisor & 0xFF) as u8);
+        channel0.write((divisor >> 8) as u8);
+    }
+}
+
+/// Chamado pelo handler do IRQ 0.
+pub fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Converte milissegundos em ticks, arredondando para cima.
+pub fn ms_to_ticks(ms: u64) -> u64 {
+    (ms * TIMER_HZ).div_ceil(1000)
+}
+
+pub fn uptime_ms() -> u64 {
+    ticks() * 1000 / TIMER_HZ
+}
+
+/// Com o escalonador rodando, bloqueia só a thread atual. Antes dele, dorme
+/// com `hlt` até o prazo, rodando os timers adiados durante a espera.
+pub fn sleep_ms(ms: u64) {
+    if crate::scheduler::is_running() {
+        crate::scheduler::sleep_ticks(ms_to_ticks(ms));
+        return;
+    }
+    let deadline = ticks() + ms_to_ticks(ms);
+    let were_enabled = interrupts::are_enabled();
+    while ticks() < deadline {
+        crate::timer_wheel::run_pending();
+        interrupts::enable_and_hlt();
+    }
+    if !were_enabled {
+        interrupts::disable();
+    }
+}
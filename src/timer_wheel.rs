@@ -0,0 +1,209 @@
+// src/timer_wheel.rs
+// ====================
+// TIMER WHEEL - Callbacks adiados (one-shot e periódicos) fora do IRQ
+// ====================
+//
+// O IRQ 0 só incrementa `timer::ticks()`. Os callbacks vencidos rodam em
+// `run_pending()` (bottom half), na thread `timerd` ou, antes do
+// escalonador, nos laços ociosos do kernel.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use spin::Mutex;
+use crate::timer;
+
+const WHEEL_SLOTS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerHandle(u64);
+
+impl TimerHandle {
+    /// Número que o shell mostra e aceita de volta no `cancel`.
+    pub fn id(self) -> u64 {
+        self.0
+    }
+
+    pub fn from_id(id: u64) -> Self {
+        TimerHandle(id)
+    }
+}
+
+enum Callback {
+    Once(Box<dyn FnOnce() + Send>),
+    Periodic(Box<dyn FnMut() + Send>, u64),
+}
+
+struct Timer {
+    id: u64,
+    deadline: u64,
+    callback: Callback,
+}
+
+struct TimerWheel {
+    // Hashed wheel: slot = deadline % WHEEL_SLOTS; timers com mais de uma
+    // volta de distância ficam no slot até o deadline chegar.
+    slots: [Vec<Timer>; WHEEL_SLOTS],
+    last_run: u64,
+    next_id: u64,
+    count: usize,
+    // Timers vencidos que o `run_pending` ainda vai rodar ou está rodando
+    // (fora dos slots), e os que foram cancelados nesse meio-tempo
+    in_flight: Vec<u64>,
+    cancelled: Vec<u64>,
+}
+
+impl TimerWheel {
+    const fn new() -> Self {
+        const EMPTY: Vec<Timer> = Vec::new();
+        TimerWheel {
+            slots: [EMPTY; WHEEL_SLOTS],
+            last_run: 0,
+            next_id: 1,
+            count: 0,
+            in_flight: Vec::new(),
+            cancelled: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, timer: Timer) {
+        // Deadline no passado: entra no próximo tick processado
+        let deadline = timer.deadline.max(self.last_run + 1);
+        self.slots[(deadline % WHEEL_SLOTS as u64) as usize].push(Timer { deadline, ..timer });
+        self.count += 1;
+    }
+
+    fn remove(&mut self, id: u64) -> bool {
+        for slot in self.slots.iter_mut() {
+            if let Some(pos) = slot.iter().position(|t| t.id == id) {
+                slot.swap_remove(pos);
+                self.count -= 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    // Tira `id` dos timers em execução; true se ele foi cancelado
+    fn finish(&mut self, id: u64) -> bool {
+        self.in_flight.retain(|&other| other != id);
+        match self.cancelled.iter().position(|&other| other == id) {
+            Some(pos) => {
+                self.cancelled.swap_remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Retira tudo que venceu até `now`
+    fn expire(&mut self, now: u64, expired: &mut Vec<Timer>) {
+        // Depois de uma volta completa todos os slots já foram visitados
+        let first = self.last_run + 1;
+        let first = first.max(now.saturating_sub(WHEEL_SLOTS as u64 - 1));
+        for tick in first..=now {
+            let slot = &mut self.slots[(tick % WHEEL_SLOTS as u64) as usize];
+            let mut i = 0;
+            while i < slot.len() {
+                if slot[i].deadline <= now {
+                    expired.push(slot.swap_remove(i));
+                    self.count -= 1;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.last_run = now;
+    }
+}
+
+static WHEEL: Mutex<TimerWheel> = Mutex::new(TimerWheel::new());
+
+fn schedule(delay_ms: u64, callback: Callback) -> TimerHandle {
+    let mut wheel = WHEEL.lock();
+    let id = wheel.next_id;
+    wheel.next_id += 1;
+    let deadline = timer::ticks() + timer::ms_to_ticks(delay_ms);
+    wheel.insert(Timer { id, deadline, callback });
+    TimerHandle(id)
+}
+
+/// Executa `f` uma vez daqui a `delay_ms`.
+pub fn schedule_once(delay_ms: u64, f: impl FnOnce() + Send + 'static) -> TimerHandle {
+    schedule(delay_ms, Callback::Once(Box::new(f)))
+}
+
+/// Executa `f` a cada `period_ms` até ser cancelado.
+pub fn schedule_periodic(period_ms: u64, f: impl FnMut() + Send + 'static) -> TimerHandle {
+    let period = timer::ms_to_ticks(period_ms).max(1);
+    schedule(period_ms, Callback::Periodic(Box::new(f), period))
+}
+
+/// Cancela o timer. Pode ser chamado de dentro do próprio callback.
+/// Retorna false se o timer já tinha disparado (one-shot) ou sido cancelado.
+pub fn cancel(handle: TimerHandle) -> bool {
+    let mut wheel = WHEEL.lock();
+    if wheel.remove(handle.0) {
+        return true;
+    }
+    // Vencido mas ainda não terminou: um one-shot deixa de rodar e um
+    // periódico não volta para o wheel
+    if wheel.in_flight.contains(&handle.0) && !wheel.cancelled.contains(&handle.0) {
+        wheel.cancelled.push(handle.0);
+        return true;
+    }
+    false
+}
+
+pub fn pending_count() -> usize {
+    WHEEL.lock().count
+}
+
+/// Bottom half: roda os callbacks vencidos com o lock solto, para que
+/// eles possam agendar ou cancelar outros timers.
+pub fn run_pending() {
+    let now = timer::ticks();
+    let mut expired = Vec::new();
+    {
+        let mut wheel = WHEEL.lock();
+        if now <= wheel.last_run {
+            return;
+        }
+        wheel.expire(now, &mut expired);
+        wheel.in_flight.extend(expired.iter().map(|timer| timer.id));
+    }
+
+    for timer in expired {
+        match timer.callback {
+            Callback::Once(f) => {
+                // Sai do in_flight antes de rodar: cancelar de dentro do
+                // callback já é tarde demais
+                if !WHEEL.lock().finish(timer.id) {
+                    f();
+                }
+            }
+            Callback::Periodic(mut f, period) => {
+                {
+                    let mut wheel = WHEEL.lock();
+                    if wheel.cancelled.contains(&timer.id) {
+                        wheel.finish(timer.id);
+                        continue;
+                    }
+                }
+                f();
+                let mut wheel = WHEEL.lock();
+                if !wheel.finish(timer.id) {
+                    let deadline = timer.deadline + period;
+                    wheel.insert(Timer { id: timer.id, deadline, callback: Callback::Periodic(f, period) });
+                }
+            }
+        }
+    }
+}
+
+/// Corpo da thread `timerd`: confere o wheel a cada tick.
+pub fn timerd() {
+    loop {
+        run_pending();
+        crate::scheduler::sleep_ticks(1);
+    }
+}
@@ -3,6 +3,8 @@
 // DRIVER NATIVO TRI - Compressão no Metal
 // ====================
 
+use alloc::vec::Vec;
+
 pub fn compress(data: &[u8; 32]) -> [u8; 64] {
     let mut compressed = [0u8; 64];
     let mut idx = 0usize;
@@ -60,3 +62,31 @@ pub fn stats(_original: &[u8; 32], compressed: &[u8; 64]) -> (u32, u32, u8) {
     let ratio = if comp_len > 0 { (orig_len * 100 / comp_len) as u8 } else { 100 };
     (orig_len, comp_len, ratio)
 }
+
+// Versões com heap: tamanho arbitrário, saída exata em pares (byte, contagem)
+pub fn compress_bytes(data: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    let mut iter = data.iter();
+    let Some(&first) = iter.next() else { return compressed; };
+    let mut last = first;
+    let mut count: u8 = 1;
+    for &byte in iter {
+        if byte == last && count < 255 {
+            count += 1;
+        } else {
+            compressed.extend_from_slice(&[last, count]);
+            last = byte;
+            count = 1;
+        }
+    }
+    compressed.extend_from_slice(&[last, count]);
+    compressed
+}
+
+pub fn decompress_bytes(compressed: &[u8]) -> Vec<u8> {
+    let mut decompressed = Vec::new();
+    for pair in compressed.chunks_exact(2) {
+        decompressed.extend(core::iter::repeat_n(pair[0], pair[1] as usize));
+    }
+    decompressed
+}
@@ -32,6 +32,8 @@ impl TriMotor {
     }
     
     /// Estima unicidade dos dados para padrão temporal
+    /// f32 aqui é seguro: o kernel é soft-float e não toca os registradores
+    /// x87/SSE (política em fpu.rs)
     fn estimate_uniqueness(data: &[u8]) -> f32 {
         if data.is_empty() { return 0.0; }
         
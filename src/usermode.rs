@@ -0,0 +1,271 @@
+// src/usermode.rs
+// ====================
+// RING 3 - Entrada em modo usuário, faixa de endereços do usuário e
+// validação de ponteiros vindos de syscalls
+// ====================
+//
+// Layout de um espaço de usuário (entradas de nível 4 livres do kernel):
+// - imagem onde o ELF pedir; os programas embutidos começam em
+//   0x7000_0000_0000, já que a metade baixa perto de zero é do kernel
+// - pilha logo abaixo de USER_STACK_TOP, com USER_STACK_SIZE bytes
+// A última página antes do buraco não canônico nunca é mapeada: assim o
+// RIP de retorno do SYSRET é sempre canônico.
+
+use core::arch::{asm, global_asm};
+use x86_64::structures::paging::{Page, PageTableFlags, PhysFrame, Size4KiB};
+use x86_64::VirtAddr;
+use crate::memory::{self, AddressSpace};
+use crate::{frame_allocator, gdt};
+
+/// Fim (exclusivo) da metade baixa canônica.
+pub const USER_END: u64 = 0x0000_8000_0000_0000;
+pub const USER_STACK_TOP: u64 = 0x0000_7FFF_FFFF_F000;
+pub const USER_STACK_SIZE: u64 = 64 * 1024;
+
+// IF ligado + bit 1 reservado
+const USER_RFLAGS: u64 = 0x202;
+
+/// Salta para `entry` no ring 3 com a pilha `stack`, via `iretq`. Usa o
+/// espaço de endereçamento ativo; os registradores gerais vão zerados para
+/// não vazar nada do kernel.
+pub fn enter_user(entry: VirtAddr, stack: VirtAddr) -> ! {
+    let cs = u64::from(gdt::user_code_selector().0);
+    let ss = u64::from(gdt::user_data_selector().0);
+    unsafe {
+        asm!(
+            "push {ss}",
+            "push {stack}",
+            "push {rflags}",
+            "push {cs}",
+            "push {entry}",
+            "xor eax, eax",
+            "xor ebx, ebx",
+            "xor ecx, ecx",
+            "xor edx, edx",
+            "xor esi, esi",
+            "xor edi, edi",
+            "xor ebp, ebp",
+            "xor r8d, r8d",
+            "xor r9d, r9d",
+            "xor r10d, r10d",
+            "xor r11d, r11d",
+            "xor r12d, r12d",
+            "xor r13d, r13d",
+            "xor r14d, r14d",
+            "xor r15d, r15d",
+            "iretq",
+            ss = in(reg) ss,
+            stack = in(reg) stack.as_u64(),
+            rflags = in(reg) USER_RFLAGS,
+            cs = in(reg) cs,
+            entry = in(reg) entry.as_u64(),
+            options(noreturn),
+        )
+    }
+}
+
+// --- Validação de ponteiros ---
+
+/// `[addr, addr + len)` está na metade do usuário e mapeado para o ring 3
+/// no espaço ativo (e gravável, se `writable`).
+pub fn is_user_range(addr: u64, len: u64, writable: bool) -> bool {
+    match addr.checked_add(len) {
+        Some(end) if end <= USER_END => memory::is_user_range(VirtAddr::new(addr), len, writable),
+        _ => false,
+    }
+}
+
+/// Fatia de leitura sobre memória do usuário, validada antes.
+pub fn user_slice<'a>(addr: u64, len: u64) -> Option<&'a [u8]> {
+    if !is_user_range(addr, len, false) {
+        return None;
+    }
+    Some(unsafe { core::slice::from_raw_parts(addr as *const u8, len as usize) })
+}
+
+pub fn user_slice_mut<'a>(addr: u64, len: u64) -> Option<&'a mut [u8]> {
+    if !is_user_range(addr, len, true) {
+        return None;
+    }
+    Some(unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len as usize) })
+}
+
+/// Lê uma string C (terminada em zero) de até `max` bytes, sem contar o zero.
+pub fn user_cstr(addr: u64, max: usize) -> Option<alloc::string::String> {
+    let mut bytes = alloc::vec::Vec::new();
+    for i in 0..=max as u64 {
+        // Valida byte a byte só ao cruzar uma página
+        let ptr = addr.checked_add(i)?;
+        if (i == 0 || ptr % 4096 == 0) && !is_user_range(ptr, 1, false) {
+            return None;
+        }
+        let byte = unsafe { *(ptr as *const u8) };
+        if byte == 0 {
+            return alloc::string::String::from_utf8(bytes).ok();
+        }
+        bytes.push(byte);
+    }
+    None
+}
+
+// --- Carga de imagens ---
+
+/// Mapeia `[addr, addr + size)` no espaço `space` com frames novos zerados.
+pub fn map_user_region(space: &mut AddressSpace, addr: u64, size: u64, flags: PageTableFlags)
+    -> Result<(), &'static str>
+{
+    if size == 0 {
+        return Ok(());
+    }
+    if addr.checked_add(size).is_none_or(|end| end > USER_END) {
+        return Err("região fora da faixa do usuário");
+    }
+    let first = Page::<Size4KiB>::containing_address(VirtAddr::new(addr));
+    let last = Page::<Size4KiB>::containing_address(VirtAddr::new(addr + size - 1));
+    for page in Page::range_inclusive(first, last) {
+        if space.is_kernel_slot(page.start_address()) {
+            return Err("região colide com o kernel");
+        }
+        if space.translate(page.start_address()).is_some() {
+            continue;
+        }
+        let frame = frame_allocator::allocate_frame().ok_or("sem memória")?;
+        zero_frame(frame);
+        space.map_page(page, frame, flags | PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE)
+            .map_err(|_| "falha ao mapear")?;
+    }
+    Ok(())
+}
+
+/// Copia `data` para `addr` em `space` (já mapeado), pelo mapeamento físico:
+/// o espaço não precisa estar ativo.
+pub fn copy_to_space(space: &mut AddressSpace, addr: u64, data: &[u8]) -> Result<(), &'static str> {
+    let mut done = 0;
+    while done < data.len() {
+        let va = addr + done as u64;
+        let phys = space.translate(VirtAddr::new(va)).ok_or("destino não mapeado")?;
+        let chunk = (4096 - (va % 4096) as usize).min(data.len() - done);
+        let dst = memory::phys_to_virt(phys).as_mut_ptr::<u8>();
+        unsafe { core::ptr::copy_nonoverlapping(data[done..].as_ptr(), dst, chunk) };
+        done += chunk;
+    }
+    Ok(())
+}
+
+fn zero_frame(frame: PhysFrame) {
+    let ptr = memory::phys_to_virt(frame.start_address()).as_mut_ptr::<u8>();
+    unsafe { core::ptr::write_bytes(ptr, 0, 4096) };
+}
+
+/// Pilha padrão do usuário (não executável).
+pub fn map_user_stack(space: &mut AddressSpace) -> Result<VirtAddr, &'static str> {
+    let flags = PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+    map_user_region(space, USER_STACK_TOP - USER_STACK_SIZE, USER_STACK_SIZE, flags)?;
+    Ok(VirtAddr::new(USER_STACK_TOP))
+}
+
+// --- Programas embutidos ---
+
+// ELF64 estáticos mínimos montados aqui mesmo: cabeçalho, um PT_LOAD R+X
+// com o arquivo inteiro em TRI_USER_BASE e o código logo depois.
+//   /bin/hello: imprime uma saudação e cada argv numa linha, pede o pid
+//               via `int 0x80` e sai com código 0
+//   /bin/spin:  laço infinito no ring 3 (para testar preempção e kill)
+global_asm!(r#"
+.set TRI_USER_BASE, 0x700000000000
+
+.macro TRI_ELF_HEADER name
+    .balign 16
+    .global tri_\name\()_elf_start
+    .global tri_\name\()_elf_end
+tri_\name\()_elf_start:
+    .byte 0x7f, 0x45, 0x4c, 0x46, 2, 1, 1, 0
+    .quad 0
+    .word 2, 0x3e
+    .long 1
+    .quad TRI_USER_BASE + (.L\name\()_entry - tri_\name\()_elf_start)
+    .quad .L\name\()_phdr - tri_\name\()_elf_start
+    .quad 0
+    .long 0
+    .word 64, 56, 1, 64, 0, 0
+.L\name\()_phdr:
+    .long 1, 5
+    .quad 0
+    .quad TRI_USER_BASE
+    .quad TRI_USER_BASE
+    .quad tri_\name\()_elf_end - tri_\name\()_elf_start
+    .quad tri_\name\()_elf_end - tri_\name\()_elf_start
+    .quad 0x1000
+.L\name\()_entry:
+.endm
+
+.section .rodata
+
+TRI_ELF_HEADER hello
+    mov r12, [rsp]
+    lea r13, [rsp + 8]
+    lea rsi, [rip + .Lhello_msg]
+    lea rdx, [rip + .Lhello_msg_end]
+    sub rdx, rsi
+    mov edi, 1
+    mov eax, 1
+    syscall
+.Lhello_next_arg:
+    test r12, r12
+    jz .Lhello_done
+    mov rsi, [r13]
+    xor edx, edx
+.Lhello_strlen:
+    cmp byte ptr [rsi + rdx], 0
+    je .Lhello_print
+    inc rdx
+    jmp .Lhello_strlen
+.Lhello_print:
+    mov edi, 1
+    mov eax, 1
+    syscall
+    lea rsi, [rip + .Lhello_msg_end - 1]
+    mov edx, 1
+    mov eax, 1
+    syscall
+    add r13, 8
+    dec r12
+    jmp .Lhello_next_arg
+.Lhello_done:
+    mov eax, 39
+    int 0x80
+    mov eax, 60
+    xor edi, edi
+    syscall
+    ud2
+.Lhello_msg:
+    .ascii "Ola do ring 3! argv:\n"
+.Lhello_msg_end:
+tri_hello_elf_end:
+
+TRI_ELF_HEADER spin
+    pause
+    jmp .Lspin_entry
+tri_spin_elf_end:
+
+.previous
+"#);
+
+extern "C" {
+    static tri_hello_elf_start: u8;
+    static tri_hello_elf_end: u8;
+    static tri_spin_elf_start: u8;
+    static tri_spin_elf_end: u8;
+}
+
+fn embedded(start: *const u8, end: *const u8) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(start, end as usize - start as usize) }
+}
+
+/// Binários que o `virtual_fs::init` instala em /bin.
+pub fn builtin_programs() -> [(&'static str, &'static [u8]); 2] {
+    [
+        ("/bin/hello", embedded(&raw const tri_hello_elf_start, &raw const tri_hello_elf_end)),
+        ("/bin/spin", embedded(&raw const tri_spin_elf_start, &raw const tri_spin_elf_end)),
+    ]
+}
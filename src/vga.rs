@@ -1,5 +1,6 @@
 use core::fmt;
-use spin::Mutex;
+use alloc::string::String;
+use crate::sync::IrqSpinlock;
 use lazy_static::lazy_static;
 use volatile::Volatile;
 
@@ -40,16 +41,21 @@ pub struct Writer {
     column: usize,
     color: u8,
     buffer: &'static mut [Volatile<VgaChar>; 25 * 80],
+    // Overlay do mouse: célula do cursor e seleção (índices row * 80 + col)
+    mouse: Option<usize>,
+    selection: Option<(usize, usize)>,
 }
 
 lazy_static! {
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
+    pub static ref WRITER: IrqSpinlock<Writer> = IrqSpinlock::new(Writer {
         row: 0,
         column: 0,
         color: make_color(Color::White, Color::Black),
         buffer: unsafe {
             &mut *(0xb8000 as *mut [Volatile<VgaChar>; 25 * 80])
         },
+        mouse: None,
+        selection: None,
     });
 }
 
@@ -57,8 +63,64 @@ fn make_color(fg: Color, bg: Color) -> u8 {
     fg as u8 | (bg as u8) << 4
 }
 
+// --- CP437 ---
+
+// Glifos 0x80..=0xAF da fonte da BIOS (code page 437)
+const CP437_HIGH: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»";
+
+// Sem glifo próprio: cai para a letra sem acento ou um parecido
+const CP437_FALLBACK: [(&str, u8); 13] = [
+    ("ÁÀÂÃ", b'A'), ("ÈÊË", b'E'), ("ÍÌÎÏ", b'I'), ("ÓÒÔÕ", b'O'), ("ÚÙÛ", b'U'), ("ÝŸ", b'Y'),
+    ("ã", b'a'), ("õ", b'o'), ("ý", b'y'), ("´", b'\''), ("¨", b'"'),
+    ("¹", b'1'), ("³", b'3'),
+];
+
+/// Caminho inverso, para copiar texto da tela.
+fn cp437_to_char(byte: u8) -> char {
+    match byte {
+        0 => ' ',
+        0x20..=0x7E => byte as char,
+        0x80..=0xAF => CP437_HIGH.chars().nth(usize::from(byte - 0x80)).unwrap_or('?'),
+        0xF8 => '°',
+        0xFA => '·',
+        0xFD => '²',
+        0x15 => '§',
+        _ => '?',
+    }
+}
+
+/// Byte da fonte VGA para um caractere Unicode (■ quando não há glifo).
+pub fn cp437(ch: char) -> u8 {
+    if ch.is_ascii() {
+        return ch as u8;
+    }
+    if let Some(i) = CP437_HIGH.chars().position(|glyph| glyph == ch) {
+        return 0x80 + i as u8;
+    }
+    match ch {
+        '°' => 0xF8,
+        '·' => 0xFA,
+        '²' => 0xFD,
+        '§' => 0x15,
+        _ => CP437_FALLBACK.iter()
+            .find(|(chars, _)| chars.contains(ch))
+            .map_or(0xFE, |&(_, byte)| byte),
+    }
+}
+
+// Cursor do mouse e seleção invertem as cores da célula com um XOR, que se
+// desfaz aplicando de novo. Toda escrita tira o overlay da tela antes (o
+// texto rolado não pode levar células invertidas junto) e a seleção some.
+const OVERLAY_XOR: u8 = 0x77;
+
 impl Writer {
     pub fn write_byte(&mut self, byte: u8) {
+        self.hide_overlay();
+        self.put_byte(byte);
+        self.toggle_overlay();
+    }
+
+    fn put_byte(&mut self, byte: u8) {
         match byte {
             b'\n' => self.new_line(),
             8 | b'\x7F' => {
@@ -84,10 +146,13 @@ impl Writer {
         }
     }
 
+    /// Escreve texto UTF-8; cada caractere vira um glifo da fonte CP437.
     pub fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            self.write_byte(byte);
+        self.hide_overlay();
+        for ch in s.chars() {
+            self.put_byte(cp437(ch));
         }
+        self.toggle_overlay();
     }
 
     fn new_line(&mut self) {
@@ -112,6 +177,7 @@ impl Writer {
     }
 
     pub fn clear_screen(&mut self) {
+        self.hide_overlay();
         for row in 0..25 {
             for col in 0..80 {
                 self.buffer[row * 80 + col] = Volatile::new(VgaChar {
@@ -122,6 +188,62 @@ impl Writer {
         }
         self.row = 0;
         self.column = 0;
+        self.toggle_overlay();
+    }
+
+    // --- Overlay do mouse ---
+
+    fn toggle_cell(&mut self, index: usize) {
+        let mut cell = self.buffer[index].read();
+        cell.color ^= OVERLAY_XOR;
+        self.buffer[index] = Volatile::new(cell);
+    }
+
+    fn toggle_overlay(&mut self) {
+        if let Some((start, end)) = self.selection {
+            for index in start..=end {
+                self.toggle_cell(index);
+            }
+        }
+        if let Some(index) = self.mouse {
+            self.toggle_cell(index);
+        }
+    }
+
+    /// Tira o overlay da tela antes de uma escrita; a seleção é descartada.
+    fn hide_overlay(&mut self) {
+        self.toggle_overlay();
+        self.selection = None;
+    }
+
+    /// Move o cursor do mouse para a célula (coluna, linha), ou o esconde.
+    pub fn set_mouse_cursor(&mut self, cell: Option<(usize, usize)>) {
+        self.toggle_overlay();
+        self.mouse = cell.map(|(col, row)| row.min(24) * 80 + col.min(79));
+        self.toggle_overlay();
+    }
+
+    /// Destaca as células `start..=end` (índices row * 80 + col).
+    pub fn set_selection(&mut self, range: Option<(usize, usize)>) {
+        self.toggle_overlay();
+        self.selection = range.map(|(start, end)| (start.min(end), start.max(end).min(25 * 80 - 1)));
+        self.toggle_overlay();
+    }
+
+    /// Texto das células `start..=end` em ordem de leitura: cada linha sem
+    /// os espaços à direita, separadas por '\n'.
+    pub fn text(&self, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        for row in start / 80..=end.min(25 * 80 - 1) / 80 {
+            let from = if row == start / 80 { start % 80 } else { 0 };
+            let to = if row == end / 80 { end % 80 } else { 79 };
+            if row != start / 80 {
+                text.push('\n');
+            }
+            let line: String = (from..=to).map(|col| cp437_to_char(self.buffer[row * 80 + col].read().ascii)).collect();
+            text.push_str(line.trim_end());
+        }
+        text
     }
 }
 
@@ -143,6 +265,23 @@ impl crate::shell::Writer for Writer {
     }
 }
 
+/// Writer do shell que trava o WRITER só durante cada escrita, deixando
+/// callbacks e outros subsistemas imprimirem enquanto o shell espera input.
+/// Tudo também sai no console serial, para usar o shell sem monitor.
+pub struct Console;
+
+impl crate::shell::Writer for Console {
+    fn write_byte(&mut self, byte: u8) {
+        WRITER.lock().write_byte(byte);
+        crate::serial::console_write(&[byte]);
+    }
+
+    fn write_string(&mut self, s: &str) {
+        WRITER.lock().write_string(s);
+        crate::serial::console_write(s.as_bytes());
+    }
+}
+
 // --- VGA Init ---
 pub fn init_vga(fg: Color, bg: Color) {
     let mut writer = WRITER.lock();
@@ -150,10 +289,18 @@ pub fn init_vga(fg: Color, bg: Color) {
     writer.clear_screen();
 }
 
-pub fn get_writer() -> &'static Mutex<Writer> {
+pub fn get_writer() -> &'static IrqSpinlock<Writer> {
     &WRITER
 }
 
+/// Libera o WRITER à força. Só para caminhos fatais (exceções), onde o dono
+/// do lock (ex.: shell_loop) nunca mais vai rodar.
+pub unsafe fn force_unlock() {
+    if WRITER.is_locked() {
+        WRITER.force_unlock();
+    }
+}
+
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => {
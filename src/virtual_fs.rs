@@ -1,15 +1,72 @@
-pub static FILES: [(&'static str, &'static [u8]); 2] = [
+use core::sync::atomic::{AtomicBool, Ordering};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::sync::Mutex;
+
+// Conteúdo embutido na imagem; copiado para o FS em memória no init()
+pub static FILES: [(&str, &[u8]); 2] = [
     ("/bin/shell", b"#!/bin/tri\n# Shell TRI v0.1 - echo 'Booted!'"),
-    ("/etc/tri-shellrc", b"export TRI_RATIO=177\nset prompt='tri-root@kernel:~#>'"),
+    ("/etc/tri-shellrc", b"export TRI_RATIO=177\nset prompt='tri-root@kernel:~#>'\nset irqchip=apic\nset keymap=us\nset serial=115200,8N1"),
 ];
 
-pub fn read_file(path: &str) -> Option<&'static [u8]> {
-    for &(name, content) in FILES.iter() {
-        if name == path { return Some(content); }
+struct File {
+    data: Vec<u8>,
+    modified: u64, // segundos Unix (rtc::timestamp)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    pub size: usize,
+    pub modified: u64,
+}
+
+static FS: Mutex<BTreeMap<String, File>> = Mutex::new(BTreeMap::new());
+// Depois do unmount o FS fica só leitura até o próximo boot
+static MOUNTED: AtomicBool = AtomicBool::new(false);
+
+pub fn init() {
+    let now = crate::rtc::timestamp();
+    let mut fs = FS.lock();
+    for &(name, content) in FILES.iter().chain(crate::usermode::builtin_programs().iter()) {
+        fs.insert(String::from(name), File { data: Vec::from(content), modified: now });
     }
-    None
+    MOUNTED.store(true, Ordering::SeqCst);
+}
+
+/// Desmonta no desligamento: nenhuma escrita passa depois daqui.
+pub fn unmount() {
+    MOUNTED.store(false, Ordering::SeqCst);
+}
+
+pub fn is_mounted() -> bool {
+    MOUNTED.load(Ordering::SeqCst)
+}
+
+pub fn read_file(path: &str) -> Option<Vec<u8>> {
+    FS.lock().get(path).map(|file| file.data.clone())
+}
+
+pub fn write_file(path: &str, content: &[u8]) -> Result<(), &'static str> {
+    if !is_mounted() {
+        return Err("FS não montado");
+    }
+    let file = File { data: Vec::from(content), modified: crate::rtc::timestamp() };
+    FS.lock().insert(String::from(path), file);
+    Ok(())
+}
+
+pub fn metadata(path: &str) -> Option<Metadata> {
+    FS.lock().get(path).map(|file| Metadata { size: file.data.len(), modified: file.modified })
+}
+
+/// Diretórios são implícitos: `path` existe como diretório se algum
+/// arquivo mora abaixo dele. A raiz sempre existe.
+pub fn is_dir(path: &str) -> bool {
+    let prefix = alloc::format!("{}/", path.trim_end_matches('/'));
+    prefix == "/" || FS.lock().keys().any(|name| name.starts_with(&prefix))
 }
 
-pub fn list_files() -> &'static [&'static str] {
-    &["/bin/shell", "/etc/tri-shellrc"]
+pub fn list_files() -> Vec<String> {
+    FS.lock().keys().cloned().collect()
 }